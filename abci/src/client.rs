@@ -1,55 +1,402 @@
 //! Blocking ABCI client.
 
-use std::net::{TcpStream, ToSocketAddrs};
+use std::{
+    cmp::Reverse,
+    future::{poll_fn, Future},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    ops::RangeInclusive,
+    pin::{pin, Pin},
+    task::Poll,
+    time::{Duration, Instant},
+};
 
+use bytes::{Bytes, BytesMut};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use tendermint_proto::google::protobuf::Timestamp;
 use tendermint_proto::v0_38::abci::{
-    request, response, Request, RequestApplySnapshotChunk, RequestCheckTx, RequestCommit,
-    RequestEcho, RequestExtendVote, RequestFinalizeBlock, RequestFlush, RequestInfo,
-    RequestInitChain, RequestListSnapshots, RequestLoadSnapshotChunk, RequestOfferSnapshot,
-    RequestQuery, RequestVerifyVoteExtension, ResponseApplySnapshotChunk, ResponseCheckTx,
-    ResponseCommit, ResponseEcho, ResponseExtendVote, ResponseFinalizeBlock, ResponseFlush,
-    ResponseInfo, ResponseInitChain, ResponseListSnapshots, ResponseLoadSnapshotChunk,
-    ResponseOfferSnapshot, ResponseQuery, ResponseVerifyVoteExtension,
+    request, response, response_apply_snapshot_chunk, response_offer_snapshot, Request,
+    RequestApplySnapshotChunk, RequestCheckTx, RequestCommit, RequestEcho, RequestExtendVote,
+    RequestFinalizeBlock, RequestFlush, RequestInfo, RequestInitChain, RequestListSnapshots,
+    RequestLoadSnapshotChunk, RequestOfferSnapshot, RequestQuery, RequestVerifyVoteExtension,
+    Response, ResponseApplySnapshotChunk, ResponseCheckTx, ResponseCommit, ResponseEcho,
+    ResponseExtendVote, ResponseFinalizeBlock, ResponseFlush, ResponseInfo, ResponseInitChain,
+    ResponseListSnapshots, ResponseLoadSnapshotChunk, ResponseOfferSnapshot, ResponseQuery,
+    ResponseVerifyVoteExtension, Snapshot,
+};
+#[cfg(feature = "test-helpers")]
+use tendermint_proto::v0_38::abci::{CheckTxType, ExecTxResult};
+use tracing::warn;
+
+use crate::{
+    channel::ChannelStream,
+    codec::{decode_length_delimited, encode_length_delimited, ClientCodec},
+    Error,
 };
 
-use crate::{codec::ClientCodec, Error};
+mod health;
+
+pub use health::{HealthHandle, HealthStatus};
 
 /// The size of the read buffer for the client in its receiving of responses
 /// from the server.
 pub const DEFAULT_CLIENT_READ_BUF_SIZE: usize = 1024;
 
+/// The maximum number of requests of a batch sent to the server
+/// before reading the response to the first of them.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+
+/// A closure setting options on the client socket, see [`ClientBuilder::configure_socket`].
+type ConfigureSocket = Box<dyn FnOnce(&Socket) -> io::Result<()> + Send>;
+
 /// Builder for a blocking ABCI client.
 pub struct ClientBuilder {
     read_buf_size: usize,
+    lazy: bool,
+    handshake: bool,
+    max_in_flight: usize,
+    bind_addr: Option<SocketAddr>,
+    forbid_implicit_latest_query: bool,
+    configure_socket: Option<ConfigureSocket>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    app_versions: Option<RangeInclusive<u64>>,
 }
 
 impl ClientBuilder {
     /// Builder constructor.
     pub fn new(read_buf_size: usize) -> Self {
-        Self { read_buf_size }
+        Self {
+            read_buf_size,
+            lazy: false,
+            handshake: false,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            bind_addr: None,
+            forbid_implicit_latest_query: false,
+            configure_socket: None,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+            app_versions: None,
+        }
+    }
+
+    /// Whether to defer connecting to the server until the first request is performed,
+    /// instead of connecting as soon as the client is constructed.
+    ///
+    /// In lazy mode, connection errors are reported by the first request.
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Whether to issue an `Info` request as soon as the connection is established,
+    /// to learn the version and state of the application.
+    ///
+    /// The response is then available from [`Client::server_info`].
+    pub fn handshake_on_connect(mut self, handshake: bool) -> Self {
+        self.handshake = handshake;
+        self
+    }
+
+    /// The maximum number of requests of a batch which may be awaiting a response,
+    /// see [`Client::send_batch`]. Defaults to [`DEFAULT_MAX_IN_FLIGHT`], ie. 16.
+    ///
+    /// A value of zero is treated as one.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Bind the client socket to the given local address before connecting,
+    /// eg. to make connections originate from a specific interface.
+    ///
+    /// This only applies to TCP connections, ie. not to [`Self::connect_stream`].
+    pub fn bind(mut self, addr: SocketAddr) -> Self {
+        self.bind_addr = Some(addr);
+        self
+    }
+
+    /// Whether [`Client::query`] should reject queries with a height of zero, which
+    /// by ABCI convention query the latest height, instead of sending them.
+    ///
+    /// This guards against querying the latest height by mistake, eg. with a height
+    /// left uninitialized. The latest height can still be queried explicitly with
+    /// [`Client::query_latest`]. Disabled by default.
+    pub fn forbid_implicit_latest_query(mut self, forbid: bool) -> Self {
+        self.forbid_implicit_latest_query = forbid;
+        self
+    }
+
+    /// Set arbitrary options on the client socket, eg. its type of service, with the
+    /// given closure. Errors it returns fail the connection with `Error::io`.
+    ///
+    /// The closure is called once the connection is established, before the stream is
+    /// wrapped in the codec and before the handshake, if enabled. As with
+    /// [`Self::bind`], this only applies to TCP connections.
+    pub fn configure_socket(
+        mut self,
+        configure: impl FnOnce(&Socket) -> io::Result<()> + Send + 'static,
+    ) -> Self {
+        self.configure_socket = Some(Box::new(configure));
+        self
+    }
+
+    /// Set the size of the kernel receive buffer of the client socket, ie. `SO_RCVBUF`,
+    /// eg. to save syscalls when reading large `FinalizeBlock` responses. Failing to set
+    /// it fails the connection with `Error::io`.
+    ///
+    /// This is distinct from the read buffer of the client, whose size is given to
+    /// [`Self::new`]. The operating system may clamp, or round up, the requested size.
+    /// The size is set again on every connection, before the closure given to
+    /// [`Self::configure_socket`] is called, and only applies to TCP connections.
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Set the size of the kernel send buffer of the client socket, ie. `SO_SNDBUF`,
+    /// as [`Self::recv_buffer_size`] does for the receive buffer.
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Refuse to talk to an application whose app version, as reported by the handshake,
+    /// is outside the given range, eg. an older binary left running after an upgrade.
+    /// Connecting then fails with `UnsupportedAppVersion`, as does reconnecting.
+    ///
+    /// This only has an effect if [`Self::handshake_on_connect`] is enabled.
+    pub fn require_app_version(mut self, supported: RangeInclusive<u64>) -> Self {
+        self.app_versions = Some(supported);
+        self
     }
 
     /// Client constructor that attempts to connect to the given network
     /// address.
     pub fn connect<A: ToSocketAddrs>(self, addr: A) -> Result<Client, Error> {
-        let stream = TcpStream::connect(addr).map_err(Error::io)?;
-        Ok(Client {
-            codec: ClientCodec::new(stream, self.read_buf_size),
-        })
+        let addrs = addr.to_socket_addrs().map_err(Error::io)?.collect();
+        self.build(addrs, None)
+    }
+
+    /// Client constructor that attempts to connect to the given socket
+    /// address, giving up after the given timeout.
+    pub fn connect_timeout(self, addr: &SocketAddr, timeout: Duration) -> Result<Client, Error> {
+        self.build(vec![*addr], Some(timeout))
+    }
+
+    /// Client constructor that uses the given in-process stream instead of a
+    /// network connection, see [`channel_pair`](crate::channel_pair).
+    pub fn connect_stream(self, stream: ChannelStream) -> Result<Client, Error> {
+        let mut client = Client {
+            codec: None,
+            addrs: Vec::new(),
+            connect_timeout: None,
+            read_buf_size: self.read_buf_size,
+            handshake: self.handshake,
+            server_info: None,
+            max_in_flight: self.max_in_flight,
+            bind_addr: self.bind_addr,
+            forbid_implicit_latest_query: self.forbid_implicit_latest_query,
+            configure_socket: self.configure_socket,
+            recv_buffer_size: self.recv_buffer_size,
+            send_buffer_size: self.send_buffer_size,
+            app_versions: self.app_versions,
+            max_frame_seen: 0,
+        };
+
+        client.codec = Some(client.establish(Stream::Channel(stream))?);
+
+        Ok(client)
+    }
+
+    fn build(self, addrs: Vec<SocketAddr>, timeout: Option<Duration>) -> Result<Client, Error> {
+        let mut client = Client {
+            codec: None,
+            addrs,
+            connect_timeout: timeout,
+            read_buf_size: self.read_buf_size,
+            handshake: self.handshake,
+            server_info: None,
+            max_in_flight: self.max_in_flight,
+            bind_addr: self.bind_addr,
+            forbid_implicit_latest_query: self.forbid_implicit_latest_query,
+            configure_socket: self.configure_socket,
+            recv_buffer_size: self.recv_buffer_size,
+            send_buffer_size: self.send_buffer_size,
+            app_versions: self.app_versions,
+            max_frame_seen: 0,
+        };
+
+        if !self.lazy {
+            client.codec()?;
+        }
+
+        Ok(client)
     }
 }
 
 impl Default for ClientBuilder {
     fn default() -> Self {
-        Self {
-            read_buf_size: DEFAULT_CLIENT_READ_BUF_SIZE,
+        Self::new(DEFAULT_CLIENT_READ_BUF_SIZE)
+    }
+}
+
+/// A snapshot chunk, as fetched from a peer during state sync.
+#[derive(Clone, Debug)]
+pub struct SnapshotChunk {
+    /// The raw chunk data.
+    pub chunk: Bytes,
+    /// The peer the chunk was fetched from.
+    pub sender: String,
+}
+
+/// A `FinalizeBlock` response, along with the block it was returned for,
+/// as returned by [`Client::finalize_block_summary`].
+#[derive(Clone, Debug)]
+pub struct FinalizeSummary {
+    /// Height of the finalized block.
+    pub height: i64,
+    /// Hash of the finalized block.
+    pub hash: Bytes,
+    /// Time of the finalized block.
+    pub time: Option<Timestamp>,
+    /// Address of the validator which proposed the finalized block.
+    pub proposer_address: Bytes,
+    /// Round in which the previous block was committed, as per the commit
+    /// included in the finalized block, if any.
+    pub last_commit_round: Option<i32>,
+    /// The response of the application.
+    pub response: ResponseFinalizeBlock,
+}
+
+/// The responses of the application to a transaction pushed through
+/// [`Client::simulate_tx`].
+#[cfg(feature = "test-helpers")]
+#[derive(Clone, Debug)]
+pub struct TxSimulationResult {
+    /// The response to the `CheckTx` request.
+    pub check_tx: ResponseCheckTx,
+    /// The response to the `FinalizeBlock` request including the transaction,
+    /// or `None` if the transaction was rejected by `CheckTx`.
+    pub finalize_block: Option<ResponseFinalizeBlock>,
+}
+
+#[cfg(feature = "test-helpers")]
+impl TxSimulationResult {
+    /// The result of executing the transaction, if it was included in a block.
+    pub fn tx_result(&self) -> Option<&ExecTxResult> {
+        self.finalize_block.as_ref()?.tx_results.first()
+    }
+}
+
+/// Builder for a [`RequestQuery`], querying the latest height
+/// without a proof unless told otherwise.
+///
+/// See [`Client::query_builder`].
+#[derive(Clone, Debug, Default)]
+pub struct QueryBuilder {
+    path: String,
+    data: Bytes,
+    height: i64,
+    prove: bool,
+}
+
+impl QueryBuilder {
+    /// The path of the query, eg. `/store/bank/key`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// The raw query data, eg. the key to look up.
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Query the state of the application at the given height.
+    ///
+    /// By default, the latest height is queried. A height of zero also means the latest.
+    pub fn height(mut self, height: i64) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Query the state of the application at the latest height. This is the default.
+    pub fn latest(mut self) -> Self {
+        self.height = 0;
+        self
+    }
+
+    /// Whether to ask the application for a Merkle proof of the result.
+    /// Defaults to `false`.
+    pub fn prove(mut self, prove: bool) -> Self {
+        self.prove = prove;
+        self
+    }
+
+    /// Build the request.
+    pub fn build(self) -> RequestQuery {
+        RequestQuery {
+            data: self.data,
+            path: self.path,
+            height: self.height,
+            prove: self.prove,
         }
     }
 }
 
+impl From<QueryBuilder> for RequestQuery {
+    fn from(builder: QueryBuilder) -> Self {
+        builder.build()
+    }
+}
+
 /// Blocking ABCI client.
 pub struct Client {
-    codec: ClientCodec<TcpStream>,
+    /// Only `None` until a lazy client performs its first request
+    codec: Option<ClientCodec<Stream>>,
+    addrs: Vec<SocketAddr>,
+    connect_timeout: Option<Duration>,
+    read_buf_size: usize,
+    handshake: bool,
+    /// Only set once the handshake has completed, if enabled
+    server_info: Option<ResponseInfo>,
+    max_in_flight: usize,
+    bind_addr: Option<SocketAddr>,
+    forbid_implicit_latest_query: bool,
+    /// Only `None` once the connection is established, or if not configured
+    configure_socket: Option<ConfigureSocket>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    app_versions: Option<RangeInclusive<u64>>,
+    /// Largest frame decoded by the codecs of the previous connections
+    max_frame_seen: usize,
+}
+
+impl Drop for Client {
+    /// Warn about the requests which were sent but could not be written to the stream,
+    /// which are lost, and try writing them one last time.
+    fn drop(&mut self) {
+        let Some(codec) = self.codec.as_mut() else {
+            return;
+        };
+
+        let unflushed = codec.unflushed();
+        if unflushed == 0 {
+            return;
+        }
+
+        warn!(
+            "Client dropped with {} bytes of requests not written to the server, \
+             trying to write them before closing the connection",
+            unflushed
+        );
+
+        if let Err(e) = codec.flush() {
+            warn!("Failed to write pending requests, they are lost: {}", e);
+        }
+    }
 }
 
 macro_rules! perform {
@@ -64,23 +411,167 @@ macro_rules! perform {
 }
 
 impl Client {
+    /// Encode the given request into the exact bytes the client writes
+    /// to the stream to send it, including the length prefix.
+    ///
+    /// This does not perform any I/O.
+    pub fn encode_request(req: &Request) -> Result<Vec<u8>, Error> {
+        let mut buf = BytesMut::new();
+        encode_length_delimited(req.clone(), &mut buf)?;
+        Ok(buf.to_vec())
+    }
+
+    /// Decode a response from the exact bytes a server writes to the stream,
+    /// including the length prefix.
+    ///
+    /// The bytes must hold exactly one frame. This does not perform any I/O.
+    pub fn decode_response(bytes: &[u8]) -> Result<Response, Error> {
+        let mut buf = BytesMut::from(bytes);
+        let response =
+            decode_length_delimited::<Response>(&mut buf)?.ok_or_else(Error::incomplete_frame)?;
+
+        if !buf.is_empty() {
+            return Err(Error::trailing_bytes(buf.len()));
+        }
+
+        Ok(response)
+    }
+
+    /// The response of the application to the `Info` request issued on connection,
+    /// if [`ClientBuilder::handshake_on_connect`] was enabled.
+    ///
+    /// This is `None` until the connection is established.
+    pub fn server_info(&self) -> Option<&ResponseInfo> {
+        self.server_info.as_ref()
+    }
+
+    /// Size in bytes of the largest response frame received over the lifetime of the client,
+    /// including its length prefix and across reconnections, eg. to tune the read buffer
+    /// size given to [`ClientBuilder::new`].
+    pub fn max_frame_seen(&self) -> usize {
+        let current = self.codec.as_ref().map_or(0, ClientCodec::max_frame_seen);
+        self.max_frame_seen.max(current)
+    }
+
+    /// Drop the connection to the server, and connect again to the same address.
+    ///
+    /// If [`ClientBuilder::handshake_on_connect`] is enabled, the response to the new
+    /// handshake is checked against the one of the previous connection: if the name,
+    /// version or app version of the application changed, which means another application
+    /// is now listening at the address, this fails with `ServerIdentityChanged` and
+    /// [`Self::server_info`] keeps the previous response. Otherwise, it is refreshed.
+    ///
+    /// This only applies to TCP connections, and the closure given to
+    /// [`ClientBuilder::configure_socket`] is not called again. A client built with
+    /// [`ClientBuilder::connect_stream`] fails with `NotReconnectable` and keeps its stream.
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        if self.addrs.is_empty() {
+            return Err(Error::not_reconnectable());
+        }
+
+        self.max_frame_seen = self.max_frame_seen();
+        self.codec = None;
+        self.codec()?;
+        Ok(())
+    }
+
+    /// Move the client to a background thread issuing an `Info` request every `interval`,
+    /// whose latest outcome can be read from the returned handle.
+    ///
+    /// Failed requests are reported as such, but the connection is not re-established:
+    /// call [`HealthHandle::stop`] to take the client back, eg. to [`Self::reconnect`].
+    pub fn start_health_poll(self, interval: Duration) -> HealthHandle {
+        health::start(self, interval)
+    }
+
+    /// Whether the connection to the server is established.
+    ///
+    /// This is only `false` for a lazy client which did not perform any request yet.
+    pub fn is_connected(&self) -> bool {
+        self.codec.is_some()
+    }
+
     /// Ask the ABCI server to echo back a message.
     pub fn echo(&mut self, req: RequestEcho) -> Result<ResponseEcho, Error> {
         perform!(self, Echo, req)
     }
 
+    /// Ask the ABCI server to echo back the given bytes, which need not be valid UTF-8.
+    ///
+    /// The message of an `Echo` request is a protobuf `string`, which [`Self::echo`]
+    /// requires to be valid UTF-8 both ways. This encodes and decodes it as raw bytes
+    /// instead, eg. to test applications with binary echo payloads.
+    pub fn echo_bytes(&mut self, msg: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let codec = self.codec()?;
+        codec.send_message(raw_echo::Request {
+            echo: Some(raw_echo::Echo { message: msg }),
+        })?;
+
+        let res: raw_echo::Response = codec
+            .next_message()
+            .ok_or_else(Error::server_connection_terminated)??;
+
+        res.echo
+            .map(|echo| echo.message)
+            .ok_or_else(Error::malformed_server_response)
+    }
+
     /// Request information about the ABCI application.
     pub fn info(&mut self, req: RequestInfo) -> Result<ResponseInfo, Error> {
         perform!(self, Info, req)
     }
 
+    /// The height of the latest block committed by the application, as reported in the
+    /// `last_block_height` of its response to an `Info` request.
+    pub fn latest_height(&mut self) -> Result<i64, Error> {
+        self.info(RequestInfo::default())
+            .map(|info| info.last_block_height)
+    }
+
+    /// Whether the given height is the one of the latest block committed by the
+    /// application, see [`Self::latest_height`].
+    pub fn is_tip(&mut self, height: i64) -> Result<bool, Error> {
+        Ok(self.latest_height()? == height)
+    }
+
     /// To be called once upon genesis.
     pub fn init_chain(&mut self, req: RequestInitChain) -> Result<ResponseInitChain, Error> {
         perform!(self, InitChain, req)
     }
 
+    /// Start building a query, for the latest height and without a proof by default.
+    ///
+    /// The builder can be passed to [`Self::query`] as is.
+    pub fn query_builder() -> QueryBuilder {
+        QueryBuilder::default()
+    }
+
     /// Query the application for data at the current or past height.
-    pub fn query(&mut self, req: RequestQuery) -> Result<ResponseQuery, Error> {
+    ///
+    /// This accepts either a [`RequestQuery`] or a [`QueryBuilder`].
+    ///
+    /// Fails with `ImplicitLatestQuery` if the height is zero and
+    /// [`ClientBuilder::forbid_implicit_latest_query`] is enabled.
+    pub fn query(&mut self, req: impl Into<RequestQuery>) -> Result<ResponseQuery, Error> {
+        let req = req.into();
+        if req.height == 0 && self.forbid_implicit_latest_query {
+            return Err(Error::implicit_latest_query(req.path));
+        }
+
+        perform!(self, Query, req)
+    }
+
+    /// Query the application for data at the latest height, whatever the height
+    /// of the given request.
+    ///
+    /// Unlike [`Self::query`], this is never rejected by
+    /// [`ClientBuilder::forbid_implicit_latest_query`].
+    pub fn query_latest(&mut self, req: impl Into<RequestQuery>) -> Result<ResponseQuery, Error> {
+        let req = RequestQuery {
+            height: 0,
+            ..req.into()
+        };
+
         perform!(self, Query, req)
     }
 
@@ -103,6 +594,26 @@ impl Client {
         perform!(self, ListSnapshots, RequestListSnapshots {})
     }
 
+    /// Same as [`Self::list_snapshots`], but sorted by descending height, and then by
+    /// descending format for snapshots of the same height.
+    pub fn list_snapshots_sorted(&mut self) -> Result<Vec<Snapshot>, Error> {
+        let mut snapshots = self.list_snapshots()?.snapshots;
+        snapshots.sort_by_key(|snapshot| Reverse((snapshot.height, snapshot.format)));
+        Ok(snapshots)
+    }
+
+    /// The snapshot of greatest height among those at or above `min_height`, if any,
+    /// picking the one of greatest format in case of a tie.
+    pub fn best_snapshot(&mut self, min_height: u64) -> Result<Option<Snapshot>, Error> {
+        let best = self
+            .list_snapshots_sorted()?
+            .into_iter()
+            .next()
+            .filter(|snapshot| snapshot.height >= min_height);
+
+        Ok(best)
+    }
+
     /// Called when bootstrapping the node using state sync.
     pub fn offer_snapshot(
         &mut self,
@@ -111,6 +622,18 @@ impl Client {
         perform!(self, OfferSnapshot, req)
     }
 
+    /// Offer a snapshot to the application, as [`Client::offer_snapshot`], failing unless
+    /// the application accepts it.
+    ///
+    /// ## Error conditions
+    /// - [`Error::snapshot_aborted`] if the application aborts the state sync
+    /// - [`Error::snapshot_rejected`] if it rejects the snapshot, its format or its sender,
+    ///   or returns an unknown result
+    pub fn offer_snapshot_checked(&mut self, req: RequestOfferSnapshot) -> Result<(), Error> {
+        let offer = self.offer_snapshot(req)?;
+        check_offer(&offer)
+    }
+
     /// Used during state sync to retrieve chunks of snapshots from peers.
     pub fn load_snapshot_chunk(
         &mut self,
@@ -145,12 +668,690 @@ impl Client {
         perform!(self, FinalizeBlock, req)
     }
 
+    /// Finalize the given block, and pair the response with the height, hash,
+    /// time and proposer of the block into a [`FinalizeSummary`].
+    pub fn finalize_block_summary(
+        &mut self,
+        req: RequestFinalizeBlock,
+    ) -> Result<FinalizeSummary, Error> {
+        let height = req.height;
+        let hash = req.hash.clone();
+        let time = req.time.clone();
+        let proposer_address = req.proposer_address.clone();
+        let last_commit_round = req.decided_last_commit.as_ref().map(|c| c.round);
+
+        let response = self.finalize_block(req)?;
+
+        Ok(FinalizeSummary {
+            height,
+            hash,
+            time,
+            proposer_address,
+            last_commit_round,
+            response,
+        })
+    }
+
+    /// Push a transaction through `CheckTx`, then through `FinalizeBlock` as the
+    /// only transaction of a block at the given height, as a node would.
+    ///
+    /// The block is otherwise empty: it has no hash, time, proposer nor last commit.
+    /// If `CheckTx` rejects the transaction, ie. returns a non-zero code, the block is
+    /// not finalized. The block is never committed, see [`Self::commit`].
+    ///
+    /// This is meant for application integration tests.
+    #[cfg(feature = "test-helpers")]
+    pub fn simulate_tx(&mut self, tx: Vec<u8>, height: i64) -> Result<TxSimulationResult, Error> {
+        let check_tx = self.check_tx(RequestCheckTx {
+            tx: tx.clone().into(),
+            r#type: CheckTxType::New as i32,
+        })?;
+
+        if check_tx.code != 0 {
+            return Ok(TxSimulationResult {
+                check_tx,
+                finalize_block: None,
+            });
+        }
+
+        let finalize_block = self.finalize_block(RequestFinalizeBlock {
+            txs: vec![tx.into()],
+            height,
+            ..Default::default()
+        })?;
+
+        Ok(TxSimulationResult {
+            check_tx,
+            finalize_block: Some(finalize_block),
+        })
+    }
+
+    /// Send the given requests to the server, and return their responses in order.
+    ///
+    /// Requests are pipelined: up to [`ClientBuilder::max_in_flight`] requests are
+    /// sent before reading the first response, and a new request is only sent once
+    /// a response has been read. This bounds the amount of responses the server has
+    /// to buffer, and avoids a deadlock where both ends block writing to each other.
+    pub fn send_batch<I>(&mut self, requests: I) -> Result<Vec<Response>, Error>
+    where
+        I: IntoIterator<Item = Request>,
+    {
+        let window = self.max_in_flight.max(1);
+        let codec = self.codec()?;
+
+        let mut requests = requests.into_iter();
+        let mut responses = Vec::new();
+        let mut in_flight = 0;
+
+        loop {
+            while in_flight < window {
+                match requests.next() {
+                    Some(req) => {
+                        codec.send(req)?;
+                        in_flight += 1;
+                    },
+                    None => break,
+                }
+            }
+
+            if in_flight == 0 {
+                return Ok(responses);
+            }
+
+            let res = codec
+                .next()
+                .ok_or_else(Error::server_connection_terminated)??;
+            responses.push(res);
+            in_flight -= 1;
+        }
+    }
+
+    /// Send the given request to the server and return its response, waiting for it
+    /// for at most `timeout`.
+    ///
+    /// The read timeout of the connection only applies to this call: the previous one
+    /// is restored afterwards, whether the request succeeded or not. This allows giving
+    /// slow requests, eg. `FinalizeBlock`, a larger budget than others.
+    ///
+    /// A response which arrives after the timeout is left on the connection. See
+    /// [`Self::drain_pending`] to discard it.
+    pub fn perform_with_timeout(
+        &mut self,
+        req: Request,
+        timeout: Duration,
+    ) -> Result<Response, Error> {
+        let codec = self.codec()?;
+        let previous = codec.get_mut().read_timeout()?;
+        codec.get_mut().set_read_timeout(Some(timeout))?;
+
+        let res = codec.send(req).and_then(|_| {
+            codec
+                .next()
+                .ok_or_else(Error::server_connection_terminated)?
+        });
+
+        let restored = codec.get_mut().set_read_timeout(previous);
+        let res = res?;
+        restored?;
+
+        Ok(res)
+    }
+
+    /// Restore the application's state from the given snapshot, within the given time budget.
+    ///
+    /// The snapshot is first offered to the application, then its chunks are fetched
+    /// with `fetch_chunk` and applied in order, until all of them have been accepted.
+    /// `fetch_chunk` is given the index of the chunk to fetch, along with the senders
+    /// the application asked to reject so far, which must not be fetched from anymore.
+    ///
+    /// The results returned by the application drive the loop:
+    /// - `RETRY` applies the same chunk again, `RETRY_SNAPSHOT` starts over from the offer.
+    /// - Chunks listed in `refetch_chunks` are fetched and applied again.
+    /// - `ABORT` fails with [`Error::snapshot_aborted`], and `REJECT_SNAPSHOT` or any
+    ///   rejected offer with [`Error::snapshot_rejected`].
+    ///
+    /// If the whole restoration takes longer than `budget`, it fails with
+    /// [`Error::snapshot_timeout`]. As this may interrupt the application in the
+    /// middle of a request, the client should not be used any further in that case.
+    pub fn apply_snapshot<F>(
+        &mut self,
+        snapshot: Snapshot,
+        app_hash: Bytes,
+        budget: Duration,
+        mut fetch_chunk: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u32, &[String]) -> Result<SnapshotChunk, Error>,
+    {
+        let deadline = Instant::now() + budget;
+        let result = self.apply_snapshot_until(snapshot, app_hash, deadline, &mut fetch_chunk);
+
+        // Restore blocking I/O for subsequent requests
+        self.set_timeout(None)?;
+
+        match result {
+            Err(e) if is_timeout(&e) => Err(Error::snapshot_timeout(budget)),
+            result => result,
+        }
+    }
+
+    fn apply_snapshot_until<F>(
+        &mut self,
+        snapshot: Snapshot,
+        app_hash: Bytes,
+        deadline: Instant,
+        fetch_chunk: &mut F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u32, &[String]) -> Result<SnapshotChunk, Error>,
+    {
+        let mut rejected_senders = Vec::new();
+
+        'offer: loop {
+            self.set_deadline(deadline)?;
+            self.offer_snapshot_checked(offer_request(&snapshot, &app_hash))?;
+
+            let mut restore = SnapshotRestore::new(&snapshot);
+            while let Some(index) = restore.next_pending() {
+                let chunk = match restore.take_fetched(index) {
+                    Some(chunk) => chunk,
+                    None => fetch_chunk(index, &rejected_senders)?,
+                };
+
+                self.set_deadline(deadline)?;
+                let response = self.apply_snapshot_chunk(chunk_request(index, &chunk))?;
+                if restore.record(index, chunk, response, &mut rejected_senders)?
+                    == ChunkOutcome::RetrySnapshot
+                {
+                    continue 'offer;
+                }
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Same as [`Self::apply_snapshot`], but fetching chunks asynchronously, until the
+    /// given `cancel` future completes, if it does first.
+    ///
+    /// On cancellation, this stops fetching chunks and fails with [`Error::cancelled`].
+    /// The ABCI requests themselves are still blocking, and cancellation is only checked
+    /// in between them and while fetching chunks, so that the connection is never left in
+    /// the middle of a request. A snapshot whose chunks were only partly applied is never
+    /// reported as restored: as per the ABCI specification, the application does not
+    /// switch to the restored state before it accepted the last chunk, and drops the
+    /// partial state once offered another snapshot.
+    ///
+    /// There is no time budget: cancel this after a timeout instead, if needed.
+    pub async fn apply_snapshot_cancellable<F, Fut, C>(
+        &mut self,
+        snapshot: Snapshot,
+        app_hash: Bytes,
+        mut fetch_chunk: F,
+        cancel: C,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u32, &[String]) -> Fut,
+        Fut: Future<Output = Result<SnapshotChunk, Error>>,
+        C: Future<Output = ()>,
+    {
+        let mut cancel = pin!(cancel);
+        let mut rejected_senders = Vec::new();
+
+        'offer: loop {
+            check_cancelled(cancel.as_mut()).await?;
+            self.offer_snapshot_checked(offer_request(&snapshot, &app_hash))?;
+
+            let mut restore = SnapshotRestore::new(&snapshot);
+            while let Some(index) = restore.next_pending() {
+                let chunk = match restore.take_fetched(index) {
+                    Some(chunk) => chunk,
+                    None => {
+                        let fetch = fetch_chunk(index, &rejected_senders);
+                        unless_cancelled(fetch, cancel.as_mut()).await?
+                    },
+                };
+
+                check_cancelled(cancel.as_mut()).await?;
+                let response = self.apply_snapshot_chunk(chunk_request(index, &chunk))?;
+                if restore.record(index, chunk, response, &mut rejected_senders)?
+                    == ChunkOutcome::RetrySnapshot
+                {
+                    continue 'offer;
+                }
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Read and discard any response pending on the connection, for up to `timeout`,
+    /// returning how many were discarded.
+    ///
+    /// This allows resynchronizing the client with the server after an interrupted
+    /// operation left unread responses behind. Reads are non-blocking, so that this
+    /// returns as soon as `timeout` has elapsed, and immediately after a single pass
+    /// over the available data if `timeout` is zero.
+    pub fn drain_pending(&mut self, timeout: Duration) -> Result<usize, Error> {
+        let codec = match &mut self.codec {
+            Some(codec) => codec,
+            None => return Ok(0),
+        };
+
+        codec.get_mut().set_nonblocking(true)?;
+        let drained = drain(codec, Instant::now() + timeout);
+        codec.get_mut().set_nonblocking(false)?;
+
+        drained
+    }
+
+    /// Bound the next reads and writes on the connection by the given deadline.
+    fn set_deadline(&mut self, deadline: Instant) -> Result<(), Error> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::io(io::ErrorKind::TimedOut.into()));
+        }
+
+        self.codec()?.get_mut().set_timeout(Some(remaining))
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        match &mut self.codec {
+            Some(codec) => codec.get_mut().set_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the codec for the connection to the server, connecting first if need be.
+    fn codec(&mut self) -> Result<&mut ClientCodec<Stream>, Error> {
+        let codec = match self.codec.take() {
+            Some(codec) => codec,
+            None => {
+                let stream = self.dial()?;
+                self.configure(&SockRef::from(&stream)).map_err(Error::io)?;
+                self.establish(Stream::Tcp(stream))?
+            },
+        };
+
+        Ok(self.codec.insert(codec))
+    }
+
+    /// Set the configured options on a newly connected socket.
+    fn configure(&mut self, socket: &Socket) -> io::Result<()> {
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        match self.configure_socket.take() {
+            Some(configure) => configure(socket),
+            None => Ok(()),
+        }
+    }
+
+    /// Set up a codec over the given stream, and perform the handshake if enabled.
+    fn establish(&mut self, stream: Stream) -> Result<ClientCodec<Stream>, Error> {
+        let mut codec = ClientCodec::new(stream, self.read_buf_size);
+
+        if self.handshake {
+            codec.send(Request {
+                value: Some(request::Value::Info(RequestInfo::default())),
+            })?;
+            let res = codec
+                .next()
+                .ok_or_else(Error::server_connection_terminated)??;
+
+            match res.value.ok_or_else(Error::malformed_server_response)? {
+                response::Value::Info(info) => self.check_server_identity(info)?,
+                r => {
+                    return Err(Error::unexpected_server_response_type(
+                        "Info".to_string(),
+                        r,
+                    ))
+                },
+            }
+        }
+
+        Ok(codec)
+    }
+
+    /// Record the given response to the handshake, failing if it comes from another
+    /// application than the previous one, if any.
+    fn check_server_identity(&mut self, info: ResponseInfo) -> Result<(), Error> {
+        if let Some(supported) = &self.app_versions {
+            if !supported.contains(&info.app_version) {
+                return Err(Error::unsupported_app_version(
+                    info.app_version,
+                    supported.clone(),
+                ));
+            }
+        }
+
+        if let Some(old) = &self.server_info {
+            if (&old.data, &old.version, old.app_version)
+                != (&info.data, &info.version, info.app_version)
+            {
+                return Err(Error::server_identity_changed(old.clone(), info));
+            }
+        }
+
+        self.server_info = Some(info);
+        Ok(())
+    }
+
+    fn dial(&self) -> Result<TcpStream, Error> {
+        if self.connect_timeout.is_none() && self.bind_addr.is_none() {
+            return TcpStream::connect(self.addrs.as_slice()).map_err(Error::io);
+        }
+
+        let mut last_err = None;
+        for addr in &self.addrs {
+            let stream = match (self.bind_addr, self.connect_timeout) {
+                (Some(local), timeout) => connect_from(local, addr, timeout),
+                (None, Some(timeout)) => TcpStream::connect_timeout(addr, timeout),
+                (None, None) => TcpStream::connect(addr),
+            };
+
+            match stream {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(Error::io(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        })))
+    }
+
     fn perform(&mut self, req: request::Value) -> Result<response::Value, Error> {
-        self.codec.send(Request { value: Some(req) })?;
-        let res = self
-            .codec
+        let codec = self.codec()?;
+        codec.send(Request { value: Some(req) })?;
+        let res = codec
             .next()
             .ok_or_else(Error::server_connection_terminated)??;
         res.value.ok_or_else(Error::malformed_server_response)
     }
 }
+
+/// Wire-compatible counterparts of the `Echo` messages, with a `bytes` message
+/// rather than a `string` one, for [`Client::echo_bytes`].
+mod raw_echo {
+    /// A `Request` whose only variant is `echo`.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct Request {
+        #[prost(message, optional, tag = "1")]
+        pub(super) echo: Option<Echo>,
+    }
+
+    /// A `Response` whose only variant is `echo`, any other being skipped.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct Response {
+        #[prost(message, optional, tag = "2")]
+        pub(super) echo: Option<Echo>,
+    }
+
+    /// A `RequestEcho` or a `ResponseEcho`.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct Echo {
+        #[prost(bytes = "vec", tag = "1")]
+        pub(super) message: Vec<u8>,
+    }
+}
+
+/// Connect to the given address from a socket bound to the given local address.
+fn connect_from(
+    local: SocketAddr,
+    addr: &SocketAddr,
+    timeout: Option<Duration>,
+) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(*addr),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.bind(&local.into())?;
+
+    match timeout {
+        Some(timeout) => socket.connect_timeout(&(*addr).into(), timeout)?,
+        None => socket.connect(&(*addr).into())?,
+    }
+
+    Ok(socket.into())
+}
+
+/// How long to wait before polling the connection again while draining it.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+fn drain(codec: &mut ClientCodec<Stream>, deadline: Instant) -> Result<usize, Error> {
+    let mut drained = 0;
+
+    loop {
+        match codec.next() {
+            Some(Ok(_)) => drained += 1,
+            Some(Err(e)) if is_timeout(&e) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(drained);
+                }
+                std::thread::sleep(remaining.min(DRAIN_POLL_INTERVAL));
+            },
+            Some(Err(e)) => return Err(e),
+            None => return Err(Error::server_connection_terminated()),
+        }
+    }
+}
+
+/// The transport underlying a client connection.
+enum Stream {
+    Tcp(TcpStream),
+    Channel(ChannelStream),
+}
+
+impl Stream {
+    /// Bound the time reads and writes may block for.
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        match self {
+            Self::Tcp(stream) => {
+                stream.set_read_timeout(timeout).map_err(Error::io)?;
+                stream.set_write_timeout(timeout).map_err(Error::io)
+            },
+            // Writes to a channel never block
+            Self::Channel(stream) => {
+                stream.set_read_timeout(timeout);
+                Ok(())
+            },
+        }
+    }
+
+    fn read_timeout(&self) -> Result<Option<Duration>, Error> {
+        match self {
+            Self::Tcp(stream) => stream.read_timeout().map_err(Error::io),
+            Self::Channel(stream) => Ok(stream.read_timeout()),
+        }
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        match self {
+            Self::Tcp(stream) => stream.set_read_timeout(timeout).map_err(Error::io),
+            Self::Channel(stream) => {
+                stream.set_read_timeout(timeout);
+                Ok(())
+            },
+        }
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> Result<(), Error> {
+        match self {
+            Self::Tcp(stream) => stream.set_nonblocking(nonblocking).map_err(Error::io),
+            Self::Channel(stream) => {
+                stream.set_nonblocking(nonblocking);
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Channel(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            Self::Channel(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            Self::Channel(stream) => stream.flush(),
+        }
+    }
+}
+
+fn offer_request(snapshot: &Snapshot, app_hash: &Bytes) -> RequestOfferSnapshot {
+    RequestOfferSnapshot {
+        snapshot: Some(snapshot.clone()),
+        app_hash: app_hash.clone(),
+    }
+}
+
+fn chunk_request(index: u32, chunk: &SnapshotChunk) -> RequestApplySnapshotChunk {
+    RequestApplySnapshotChunk {
+        index,
+        chunk: chunk.chunk.clone(),
+        sender: chunk.sender.clone(),
+    }
+}
+
+/// Fail unless the application accepted the offered snapshot.
+fn check_offer(offer: &ResponseOfferSnapshot) -> Result<(), Error> {
+    match response_offer_snapshot::Result::try_from(offer.result) {
+        Ok(response_offer_snapshot::Result::Accept) => Ok(()),
+        Ok(response_offer_snapshot::Result::Abort) => Err(Error::snapshot_aborted()),
+        Ok(result) => Err(Error::snapshot_rejected(result.as_str_name().into())),
+        Err(_) => Err(Error::snapshot_rejected(offer.result.to_string())),
+    }
+}
+
+/// What to do next after a chunk was applied, unless the restoration failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ChunkOutcome {
+    Continue,
+    RetrySnapshot,
+}
+
+/// The chunks of an accepted snapshot which are applied, or fetched but not accepted yet.
+struct SnapshotRestore {
+    /// Chunks fetched but not yet accepted, indexed by chunk index
+    fetched: Vec<Option<SnapshotChunk>>,
+    applied: Vec<bool>,
+}
+
+impl SnapshotRestore {
+    fn new(snapshot: &Snapshot) -> Self {
+        Self {
+            fetched: vec![None; snapshot.chunks as usize],
+            applied: vec![false; snapshot.chunks as usize],
+        }
+    }
+
+    /// The chunk to apply next, if any is left.
+    ///
+    /// Chunks must be applied sequentially, so always go for the lowest pending one.
+    fn next_pending(&self) -> Option<u32> {
+        self.applied
+            .iter()
+            .position(|done| !done)
+            .map(|index| index as u32)
+    }
+
+    /// The given chunk, if it was already fetched and is to be applied again.
+    fn take_fetched(&mut self, index: u32) -> Option<SnapshotChunk> {
+        self.fetched[index as usize].take()
+    }
+
+    /// Follow the response of the application to the given chunk.
+    fn record(
+        &mut self,
+        index: u32,
+        chunk: SnapshotChunk,
+        response: ResponseApplySnapshotChunk,
+        rejected_senders: &mut Vec<String>,
+    ) -> Result<ChunkOutcome, Error> {
+        for sender in response.reject_senders {
+            if !rejected_senders.contains(&sender) {
+                rejected_senders.push(sender);
+            }
+        }
+
+        match response_apply_snapshot_chunk::Result::try_from(response.result) {
+            Ok(response_apply_snapshot_chunk::Result::Accept) => {
+                self.applied[index as usize] = true
+            },
+            Ok(response_apply_snapshot_chunk::Result::Retry) => {
+                self.fetched[index as usize] = Some(chunk)
+            },
+            Ok(response_apply_snapshot_chunk::Result::RetrySnapshot) => {
+                return Ok(ChunkOutcome::RetrySnapshot)
+            },
+            Ok(response_apply_snapshot_chunk::Result::Abort) => {
+                return Err(Error::snapshot_aborted())
+            },
+            Ok(result) => return Err(Error::snapshot_rejected(result.as_str_name().into())),
+            Err(_) => return Err(Error::snapshot_rejected(response.result.to_string())),
+        }
+
+        for refetch in response.refetch_chunks {
+            if let Some(done) = self.applied.get_mut(refetch as usize) {
+                *done = false;
+                self.fetched[refetch as usize] = None;
+            }
+        }
+
+        Ok(ChunkOutcome::Continue)
+    }
+}
+
+/// Fail with [`Error::cancelled`] if the given cancellation future has completed,
+/// without waiting for it otherwise.
+async fn check_cancelled<C: Future<Output = ()>>(cancel: Pin<&mut C>) -> Result<(), Error> {
+    unless_cancelled(async { Ok(()) }, cancel).await
+}
+
+/// Wait for the given future, unless the given cancellation future completes first,
+/// in which case this fails with [`Error::cancelled`].
+async fn unless_cancelled<T, C: Future<Output = ()>>(
+    fut: impl Future<Output = Result<T, Error>>,
+    mut cancel: Pin<&mut C>,
+) -> Result<T, Error> {
+    let mut fut = pin!(fut);
+    poll_fn(|cx| {
+        if cancel.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Err(Error::cancelled()));
+        }
+        fut.as_mut().poll(cx)
+    })
+    .await
+}
+
+fn is_timeout(e: &Error) -> bool {
+    match e.detail() {
+        crate::error::ErrorDetail::Io(e) => matches!(
+            e.source.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        ),
+        _ => false,
+    }
+}
@@ -0,0 +1,81 @@
+//! Background polling of the application's `Info`, see [`Client::start_health_poll`].
+
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use tendermint_proto::v0_38::abci::{RequestInfo, ResponseInfo};
+
+use super::Client;
+
+/// Outcome of the latest `Info` request issued by a health poll.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HealthStatus {
+    /// When the request was issued.
+    pub checked_at: Instant,
+    /// The response of the application, or the error the request failed with.
+    pub info: Result<ResponseInfo, String>,
+}
+
+/// Handle on a client polling the application in the background,
+/// as returned by [`Client::start_health_poll`].
+///
+/// Dropping the handle stops the poll, without waiting for it to return.
+pub struct HealthHandle {
+    latest: Arc<Mutex<Option<HealthStatus>>>,
+    stop: Sender<()>,
+    poll: JoinHandle<Client>,
+}
+
+impl HealthHandle {
+    /// The outcome of the latest `Info` request, or `None` if none completed yet.
+    pub fn latest(&self) -> Option<HealthStatus> {
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Stop polling, and take the client back once the request in flight, if any,
+    /// has completed.
+    pub fn stop(self) -> Client {
+        // The poll may have exited already, if it panicked
+        let _ = self.stop.send(());
+
+        match self.poll.join() {
+            Ok(client) => client,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+pub(super) fn start(mut client: Client, interval: Duration) -> HealthHandle {
+    let latest = Arc::new(Mutex::new(None));
+    let (stop, stopped) = mpsc::channel();
+
+    let poll = {
+        let latest = latest.clone();
+        thread::spawn(move || loop {
+            let checked_at = Instant::now();
+            let info = client
+                .info(RequestInfo::default())
+                .map_err(|e| e.to_string());
+
+            *latest.lock().unwrap_or_else(PoisonError::into_inner) =
+                Some(HealthStatus { checked_at, info });
+
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                // Stopped, or the handle was dropped
+                _ => return client,
+            }
+        })
+    };
+
+    HealthHandle { latest, stop, poll }
+}
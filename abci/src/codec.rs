@@ -5,13 +5,14 @@
 //! [tsp]: https://github.com/tendermint/tendermint/blob/v0.34.x/spec/abci/client-server.md#tsp
 
 use std::{
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
     marker::PhantomData,
 };
 
 use bytes::{Buf, BufMut, BytesMut};
 use prost::Message;
 use tendermint_proto::v0_38::abci::{Request, Response};
+use tracing::warn;
 
 use crate::error::Error;
 
@@ -35,6 +36,10 @@ pub struct Codec<S, I, O> {
     // Fixed-length read window
     read_window: Vec<u8>,
     write_buf: BytesMut,
+    // Whether we already warned about a frame exceeding the read window
+    warned_oversized_frame: bool,
+    // Size of the largest frame decoded so far
+    max_frame_seen: usize,
     _incoming: PhantomData<I>,
     _outgoing: PhantomData<O>,
 }
@@ -52,10 +57,18 @@ where
             read_buf: BytesMut::new(),
             read_window: vec![0_u8; read_buf_size],
             write_buf: BytesMut::new(),
+            warned_oversized_frame: false,
+            max_frame_seen: 0,
             _incoming: Default::default(),
             _outgoing: Default::default(),
         }
     }
+
+    /// Get a mutable reference to the underlying stream.
+    #[cfg(feature = "client")]
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
 }
 
 // Iterating over a codec produces instances of `Result<I>`.
@@ -67,23 +80,49 @@ where
     type Item = Result<I, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_message()
+    }
+}
+
+impl<S, I, O> Codec<S, I, O>
+where
+    S: Read,
+{
+    /// Read the next incoming message, decoding it as `M` rather than as `I`.
+    pub(crate) fn next_message<M>(&mut self) -> Option<Result<M, Error>>
+    where
+        M: Message + Default,
+    {
         loop {
             // Try to decode an incoming message from our buffer first
-            match decode_length_delimited::<I>(&mut self.read_buf) {
-                Ok(Some(incoming)) => return Some(Ok(incoming)),
+            let buffered = self.read_buf.len();
+            match decode_length_delimited::<M>(&mut self.read_buf) {
+                Ok(Some(incoming)) => {
+                    self.check_frame_size(buffered - self.read_buf.len());
+                    return Some(Ok(incoming));
+                },
                 Err(e) => return Some(Err(e)),
                 _ => (), // not enough data to decode a message, let's continue.
             }
 
             // If we don't have enough data to decode a message, try to read
-            // more
+            // more. A read interrupted by a signal did not consume anything, and
+            // leaves the partial frame buffered, so it is simply retried.
             let bytes_read = match self.stream.read(self.read_window.as_mut()) {
                 Ok(br) => br,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
                 Err(e) => return Some(Err(Error::io(e))),
             };
             if bytes_read == 0 {
-                // The underlying stream terminated
-                return None;
+                // The underlying stream terminated, either cleanly between two frames,
+                // or in the middle of one, which is then discarded
+                if self.read_buf.is_empty() {
+                    return None;
+                }
+
+                let buffered = self.read_buf.len();
+                self.read_buf.clear();
+                return Some(Err(Error::unexpected_eof(buffered)));
             }
             self.read_buf
                 .extend_from_slice(&self.read_window[..bytes_read]);
@@ -91,6 +130,30 @@ where
     }
 }
 
+impl<S, I, O> Codec<S, I, O> {
+    /// Size in bytes of the largest frame decoded so far, including its length prefix.
+    #[cfg(feature = "client")]
+    pub(crate) fn max_frame_seen(&self) -> usize {
+        self.max_frame_seen
+    }
+
+    /// Record the size of a decoded frame, and warn, once per codec, about a frame
+    /// which did not fit in the read window, as it had to be reassembled over several reads.
+    fn check_frame_size(&mut self, frame_size: usize) {
+        self.max_frame_seen = self.max_frame_seen.max(frame_size);
+
+        let read_buf_size = self.read_window.len();
+        if frame_size > read_buf_size && !self.warned_oversized_frame {
+            self.warned_oversized_frame = true;
+            warn!(
+                "Decoded a frame of {} bytes, larger than the read buffer of {} bytes; \
+                 consider raising the read buffer size",
+                frame_size, read_buf_size,
+            );
+        }
+    }
+}
+
 impl<S, I, O> Codec<S, I, O>
 where
     S: Write,
@@ -98,7 +161,23 @@ where
 {
     /// Send a message using this codec.
     pub fn send(&mut self, message: O) -> Result<(), Error> {
+        self.send_message(message)
+    }
+
+    /// Send a message of another type than `O`, with the same framing.
+    pub(crate) fn send_message<M: Message>(&mut self, message: M) -> Result<(), Error> {
         encode_length_delimited(message, &mut self.write_buf)?;
+        self.flush()
+    }
+
+    /// Number of bytes of the messages sent so far which are not written to the stream yet,
+    /// as writing them failed.
+    pub(crate) fn unflushed(&self) -> usize {
+        self.write_buf.len()
+    }
+
+    /// Write the bytes of the messages sent so far to the stream, and flush it.
+    pub(crate) fn flush(&mut self) -> Result<(), Error> {
         while !self.write_buf.is_empty() {
             let bytes_written = self
                 .stream
@@ -1,7 +1,9 @@
 //! tendermint-abci errors
 
+use std::{ops::RangeInclusive, time::Duration};
+
 use flex_error::{define_error, DisplayError};
-use tendermint_proto::v0_38::abci::response::Value;
+use tendermint_proto::v0_38::abci::{response::Value, ResponseInfo};
 
 define_error! {
     Error {
@@ -20,9 +22,26 @@ define_error! {
         ServerConnectionTerminated
             | _ | { "server connection terminated" },
 
+        UnexpectedEof
+            { buffered: usize }
+            | e | {
+                format_args!("stream ended in the middle of a frame, after {0} bytes of it",
+                    e.buffered)
+            },
+
         MalformedServerResponse
             | _ | { "malformed server response" },
 
+        IncompleteFrame
+            | _ | { "incomplete length-delimited frame" },
+
+        TrailingBytes
+            { len: usize }
+            | e | {
+                format_args!("{0} trailing bytes after length-delimited frame",
+                    e.len)
+            },
+
         UnexpectedServerResponseType
             {
                 expected: String,
@@ -33,12 +52,77 @@ define_error! {
                     e.expected, e.got)
             },
 
+        ImplicitLatestQuery
+            { path: String }
+            | e | {
+                format_args!("query for path {0:?} has a height of zero, which implicitly queries the latest height",
+                    e.path)
+            },
+
+        ServerIdentityChanged
+            {
+                old: ResponseInfo,
+                new: ResponseInfo,
+            }
+            | e | {
+                format_args!("server identity changed: was {0:?} version {1:?} (app version {2}), is now {3:?} version {4:?} (app version {5})",
+                    e.old.data, e.old.version, e.old.app_version,
+                    e.new.data, e.new.version, e.new.app_version)
+            },
+
+        NotReconnectable
+            | _ | { "client connected over an in-process stream cannot reconnect" },
+
+        UnsupportedAppVersion
+            {
+                got: u64,
+                supported: RangeInclusive<u64>,
+            }
+            | e | {
+                format_args!("unsupported app version {0}, expected one within {1:?}",
+                    e.got, e.supported)
+            },
+
         ChannelSend
             | _ | { "channel send error" },
 
         ChannelRecv
             [ DisplayError<std::sync::mpsc::RecvError> ]
             | _ | { "channel recv error" },
+
+        SnapshotTimeout
+            { budget: Duration }
+            | e | {
+                format_args!("snapshot could not be applied within {0:?}",
+                    e.budget)
+            },
+
+        SnapshotRejected
+            { result: String }
+            | e | {
+                format_args!("snapshot rejected by the application: {0}",
+                    e.result)
+            },
+
+        SnapshotAborted
+            | _ | { "snapshot restoration aborted by the application" },
+
+        Cancelled
+            | _ | { "operation cancelled" },
+
+        MalformedProof
+            { reason: String }
+            | e | {
+                format_args!("malformed query proof: {0}",
+                    e.reason)
+            },
+
+        UnsupportedProofOp
+            { op_type: String }
+            | e | {
+                format_args!("unsupported query proof operation type {0:?}",
+                    e.op_type)
+            },
     }
 }
 
@@ -46,4 +130,16 @@ impl Error {
     pub fn send<T>(_e: std::sync::mpsc::SendError<T>) -> Error {
         Error::channel_send()
     }
+
+    /// Whether this error means that the server actively refused the connection,
+    /// eg. because nothing is listening on its port yet.
+    ///
+    /// This is typically worth retrying, as opposed to eg. a failure to resolve
+    /// the server address.
+    pub fn is_connection_refused(&self) -> bool {
+        match self.detail() {
+            ErrorDetail::Io(e) => e.source.kind() == std::io::ErrorKind::ConnectionRefused,
+            _ => false,
+        }
+    }
 }
@@ -3,10 +3,12 @@
 //! [Tendermint]: https://tendermint.com
 
 mod application;
+mod channel;
 #[cfg(feature = "client")]
 mod client;
 mod codec;
 pub mod error;
+mod proof;
 mod server;
 
 // Common exports
@@ -16,7 +18,14 @@ pub use application::echo::EchoApp;
 #[cfg(feature = "kvstore-app")]
 pub use application::kvstore::{KeyValueStoreApp, KeyValueStoreDriver};
 pub use application::Application;
+pub use channel::{channel_pair, ChannelStream};
+#[cfg(feature = "test-helpers")]
+pub use client::TxSimulationResult;
 #[cfg(feature = "client")]
-pub use client::{Client, ClientBuilder};
+pub use client::{
+    Client, ClientBuilder, FinalizeSummary, HealthHandle, HealthStatus, QueryBuilder,
+    SnapshotChunk, DEFAULT_MAX_IN_FLIGHT,
+};
 pub use error::Error;
+pub use proof::{verify_query_proof, AbsenceOp, Neighbour, ABSENCE_OP_TYPE, VALUE_OP_TYPE};
 pub use server::{Server, ServerBuilder};
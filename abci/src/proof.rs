@@ -0,0 +1,255 @@
+//! Verification of the Merkle proofs returned by `Query` requests.
+//!
+//! A proof is a chain of [`ProofOp`]s, as in Tendermint's `crypto/merkle` package: the
+//! first operation proves the queried key against the root of the application's innermost
+//! tree, and each following one proves the root computed so far against the root of an
+//! outer tree, eg. a store of a multi-store, until the root of the outermost tree, which
+//! must be the app hash.
+//!
+//! Two kinds of operations are supported, both over RFC 6962 simple Merkle trees whose
+//! leaves are key-value pairs sorted by key:
+//! - [`VALUE_OP_TYPE`], Tendermint's `simple:v` operation, proving that a key is bound to
+//!   a value, whose data is a protobuf-encoded [`ValueOp`]
+//! - [`ABSENCE_OP_TYPE`], proving that no leaf of the tree holds a key, by proving the
+//!   leaves right below and right above it, whose data is a protobuf-encoded
+//!   [`AbsenceOp`]. It may only be used as the first operation.
+//!
+//! Tendermint itself defines no absence operation, so [`ABSENCE_OP_TYPE`] and
+//! [`AbsenceOp`] are specific to this crate: they are only understood by applications
+//! which build their proofs of absence for it, and any other operation type, eg. the
+//! `ics23:*` ones of the Cosmos SDK, fails with [`Error::unsupported_proof_op`].
+
+use sha2::{Digest, Sha256};
+use tendermint_proto::v0_38::crypto::{Proof, ProofOp, ProofOps, ValueOp};
+
+use crate::Error;
+
+/// Type of the operations proving the value of a key, see [`ValueOp`].
+pub const VALUE_OP_TYPE: &str = "simple:v";
+
+/// Type of the operations proving the absence of a key, see [`AbsenceOp`].
+///
+/// This operation is specific to this crate, and not part of Tendermint's `crypto/merkle`
+/// package, which has no proof of absence.
+pub const ABSENCE_OP_TYPE: &str = "simple:absent";
+
+/// The data of an [`ABSENCE_OP_TYPE`] operation, proving that no leaf holds its key.
+///
+/// The neighbours must be adjacent leaves of the same tree, whose keys surround the
+/// missing key. Either neighbour is omitted if the key is lower, or higher, than any
+/// other in the tree, and both are in an empty tree.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AbsenceOp {
+    /// The leaf holding the highest key below the missing one.
+    #[prost(message, optional, tag = "1")]
+    pub left: Option<Neighbour>,
+    /// The leaf holding the lowest key above the missing one.
+    #[prost(message, optional, tag = "2")]
+    pub right: Option<Neighbour>,
+}
+
+/// A leaf neighbouring a missing key, see [`AbsenceOp`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Neighbour {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: Vec<u8>,
+    /// The SHA-256 hash of the value of the leaf.
+    #[prost(bytes = "vec", tag = "2")]
+    pub value_hash: Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub proof: Option<Proof>,
+}
+
+/// Verify that the given proof, as returned in a `ResponseQuery`, binds the given key to
+/// the given value under the given app hash, or proves that the key is absent if the
+/// value is `None`.
+///
+/// This returns `Ok(false)` if the proof is well-formed but does not prove the claim,
+/// eg. as it proves another value or another app hash.
+///
+/// ## Error conditions
+/// - The proof has no operation, or an operation of an unsupported type
+/// - The data of an operation cannot be decoded
+/// - A Merkle proof of an operation is malformed, eg. its index is out of bounds
+pub fn verify_query_proof(
+    proof: &ProofOps,
+    app_hash: &[u8],
+    key: &[u8],
+    value: Option<&[u8]>,
+) -> Result<bool, Error> {
+    let (first, outer) = proof
+        .ops
+        .split_first()
+        .ok_or_else(|| Error::malformed_proof("the proof has no operation".to_string()))?;
+
+    if first.key != key {
+        return Ok(false);
+    }
+
+    let inner_root = match (first.r#type.as_str(), value) {
+        (VALUE_OP_TYPE, Some(value)) => run_value_op(first, value)?,
+        (ABSENCE_OP_TYPE, None) => run_absence_op(first)?,
+        // A proof of presence for an absent key, or the other way round
+        (VALUE_OP_TYPE, None) | (ABSENCE_OP_TYPE, Some(_)) => return Ok(false),
+        (other, _) => return Err(Error::unsupported_proof_op(other.to_string())),
+    };
+
+    let mut root = match inner_root {
+        Some(root) => root,
+        None => return Ok(false),
+    };
+
+    for op in outer {
+        if op.r#type != VALUE_OP_TYPE {
+            return Err(Error::unsupported_proof_op(op.r#type.clone()));
+        }
+
+        root = match run_value_op(op, &root)? {
+            Some(root) => root,
+            None => return Ok(false),
+        };
+    }
+
+    Ok(root == app_hash)
+}
+
+/// The root of the tree in which the given `simple:v` operation binds its key to the given
+/// value, or `None` if it binds it to another value.
+fn run_value_op(op: &ProofOp, value: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let value_op =
+        <ValueOp as prost::Message>::decode(op.data.as_slice()).map_err(Error::decode)?;
+    let proof = value_op
+        .proof
+        .ok_or_else(|| Error::malformed_proof("value operation without proof".to_string()))?;
+
+    if value_op.key != op.key || kv_leaf_hash(&op.key, &Sha256::digest(value)) != proof.leaf_hash {
+        return Ok(None);
+    }
+
+    root_hash(&proof).map(Some)
+}
+
+/// The root of the tree in which the given absence operation proves that its key is
+/// missing, or `None` if its neighbours do not surround the key.
+fn run_absence_op(op: &ProofOp) -> Result<Option<Vec<u8>>, Error> {
+    let absence =
+        <AbsenceOp as prost::Message>::decode(op.data.as_slice()).map_err(Error::decode)?;
+    let key = op.key.as_slice();
+
+    let left = absence.left.as_ref().map(neighbour_proof).transpose()?;
+    let right = absence.right.as_ref().map(neighbour_proof).transpose()?;
+
+    let surrounded = match (left, right) {
+        (Some((left_key, left)), Some((right_key, right))) => {
+            left_key < key
+                && key < right_key
+                && left.total == right.total
+                && left.index + 1 == right.index
+        },
+        // The key is below the first leaf, or above the last one
+        (None, Some((right_key, right))) => key < right_key && right.index == 0,
+        (Some((left_key, left)), None) => left_key < key && left.index + 1 == left.total,
+        // The tree is empty
+        (None, None) => return Ok(Some(Sha256::digest([]).to_vec())),
+    };
+
+    if !surrounded {
+        return Ok(None);
+    }
+
+    let mut roots = left.iter().chain(&right).map(|(_, proof)| root_hash(proof));
+    let root = roots.next().expect("at least one neighbour")?;
+    for other in roots {
+        if other? != root {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(root))
+}
+
+/// The key of the given neighbour, and its Merkle proof with the leaf hash checked
+/// against the key and value hash.
+fn neighbour_proof(neighbour: &Neighbour) -> Result<(&[u8], &Proof), Error> {
+    let proof = neighbour
+        .proof
+        .as_ref()
+        .ok_or_else(|| Error::malformed_proof("neighbour without proof".to_string()))?;
+
+    if kv_leaf_hash(&neighbour.key, &neighbour.value_hash) != proof.leaf_hash {
+        return Err(Error::malformed_proof(
+            "neighbour leaf hash does not match its key and value".to_string(),
+        ));
+    }
+
+    Ok((&neighbour.key, proof))
+}
+
+/// The hash of the leaf holding the given key and value hash, as in Tendermint's
+/// `SimpleMap`: both are prefixed with their length as an unsigned varint.
+fn kv_leaf_hash(key: &[u8], value_hash: &[u8]) -> Vec<u8> {
+    let mut leaf = Vec::with_capacity(key.len() + value_hash.len() + 20);
+    for bytes in [key, value_hash] {
+        prost::encoding::encode_varint(bytes.len() as u64, &mut leaf);
+        leaf.extend_from_slice(bytes);
+    }
+
+    leaf_hash(&leaf)
+}
+
+fn leaf_hash(leaf: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([0x00])
+        .chain_update(leaf)
+        .finalize()
+        .to_vec()
+}
+
+fn inner_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([0x01])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .to_vec()
+}
+
+/// The root of the tree computed from the leaf hash and aunts of the given proof.
+fn root_hash(proof: &Proof) -> Result<Vec<u8>, Error> {
+    if proof.total <= 0 || proof.index < 0 || proof.index >= proof.total {
+        return Err(Error::malformed_proof(format!(
+            "leaf index {} out of bounds for {} leaves",
+            proof.index, proof.total
+        )));
+    }
+
+    hash_from_aunts(proof.index, proof.total, &proof.leaf_hash, &proof.aunts).ok_or_else(|| {
+        Error::malformed_proof(format!(
+            "{} aunts do not match a tree of {} leaves",
+            proof.aunts.len(),
+            proof.total
+        ))
+    })
+}
+
+/// As `computeHashFromAunts` in Tendermint: the aunts are ordered from the leaf up.
+fn hash_from_aunts(index: i64, total: i64, leaf_hash: &[u8], aunts: &[Vec<u8>]) -> Option<Vec<u8>> {
+    if total == 1 {
+        return aunts.is_empty().then(|| leaf_hash.to_vec());
+    }
+
+    let (aunt, below) = aunts.split_last()?;
+    let split = split_point(total);
+    if index < split {
+        let left = hash_from_aunts(index, split, leaf_hash, below)?;
+        Some(inner_hash(&left, aunt))
+    } else {
+        let right = hash_from_aunts(index - split, total - split, leaf_hash, below)?;
+        Some(inner_hash(aunt, &right))
+    }
+}
+
+/// The largest power of two strictly below the given number of leaves, which is at least 2.
+fn split_point(total: i64) -> i64 {
+    1 << (63 - (total - 1).leading_zeros())
+}
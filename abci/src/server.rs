@@ -1,13 +1,17 @@
 //! ABCI application server interface.
 
 use std::{
-    net::{TcpListener, TcpStream, ToSocketAddrs},
+    io::{Read, Write},
+    net::{TcpListener, ToSocketAddrs},
     thread,
 };
 
 use tracing::{error, info};
 
-use crate::{application::RequestDispatcher, codec::ServerCodec, error::Error, Application};
+use crate::{
+    application::RequestDispatcher, channel::ChannelStream, codec::ServerCodec, error::Error,
+    Application,
+};
 
 /// The size of the read buffer for each incoming connection to the ABCI
 /// server (1MB).
@@ -48,6 +52,19 @@ impl ServerBuilder {
             read_buf_size: self.read_buf_size,
         })
     }
+
+    /// Serve the given ABCI application over the given in-process stream, see
+    /// [`channel_pair`](crate::channel_pair).
+    ///
+    /// Requests are handled in a separate thread, until the other end of the
+    /// stream is dropped.
+    pub fn serve_stream<App>(self, stream: ChannelStream, app: App) -> thread::JoinHandle<()>
+    where
+        App: Application,
+    {
+        let read_buf_size = self.read_buf_size;
+        thread::spawn(move || handle_client(stream, "channel".to_string(), app, read_buf_size))
+    }
 }
 
 impl Default for ServerBuilder {
@@ -87,37 +104,44 @@ impl<App: Application> Server<App> {
         self.local_addr.clone()
     }
 
-    fn spawn_client_handler(&self, stream: TcpStream, addr: String) {
+    fn spawn_client_handler<S>(&self, stream: S, addr: String)
+    where
+        S: Read + Write + Send + 'static,
+    {
         let app = self.app.clone();
         let read_buf_size = self.read_buf_size;
-        let _ = thread::spawn(move || Self::handle_client(stream, addr, app, read_buf_size));
+        let _ = thread::spawn(move || handle_client(stream, addr, app, read_buf_size));
     }
+}
 
-    fn handle_client(stream: TcpStream, addr: String, app: App, read_buf_size: usize) {
-        let mut codec = ServerCodec::new(stream, read_buf_size);
-        info!("Listening for incoming requests from {}", addr);
-        loop {
-            let request = match codec.next() {
-                Some(result) => match result {
-                    Ok(r) => r,
-                    Err(e) => {
-                        error!(
-                            "Failed to read incoming request from client {}: {:?}",
-                            addr, e
-                        );
-                        return;
-                    },
-                },
-                None => {
-                    info!("Client {} terminated stream", addr);
+fn handle_client<S, App>(stream: S, addr: String, app: App, read_buf_size: usize)
+where
+    S: Read + Write,
+    App: Application,
+{
+    let mut codec = ServerCodec::new(stream, read_buf_size);
+    info!("Listening for incoming requests from {}", addr);
+    loop {
+        let request = match codec.next() {
+            Some(result) => match result {
+                Ok(r) => r,
+                Err(e) => {
+                    error!(
+                        "Failed to read incoming request from client {}: {:?}",
+                        addr, e
+                    );
                     return;
                 },
-            };
-            let response = app.handle(request);
-            if let Err(e) = codec.send(response) {
-                error!("Failed sending response to client {}: {:?}", addr, e);
+            },
+            None => {
+                info!("Client {} terminated stream", addr);
                 return;
-            }
+            },
+        };
+        let response = app.handle(request);
+        if let Err(e) = codec.send(response) {
+            error!("Failed sending response to client {}: {:?}", addr, e);
+            return;
         }
     }
 }
@@ -0,0 +1,551 @@
+//! Integration tests for the ABCI client against a raw socket.
+
+#[cfg(feature = "client")]
+mod client_integration {
+    use std::{
+        io::{self, Read, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicI64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use prost::Message;
+    use tendermint_abci::{
+        channel_pair, error::ErrorDetail, Application, Client, ClientBuilder, ServerBuilder,
+    };
+    use tendermint_proto::v0_38::abci::{
+        request, response, Request, RequestEcho, RequestInfo, Response, ResponseEcho,
+        ResponseFlush, ResponseInfo,
+    };
+
+    #[test]
+    fn refused_connections_are_told_apart() {
+        // Grab a free port, and stop listening on it
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let err = ClientBuilder::default().connect(addr).err().unwrap();
+        assert!(err.is_connection_refused());
+
+        let err = ClientBuilder::default()
+            .connect("not a socket address")
+            .err()
+            .unwrap();
+        assert!(!err.is_connection_refused());
+    }
+
+    #[test]
+    fn drain_pending_discards_stale_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let stale = Response {
+                value: Some(response::Value::Flush(ResponseFlush {})),
+            };
+            for _ in 0..3 {
+                stream
+                    .write_all(&stale.encode_length_delimited_to_vec())
+                    .unwrap();
+            }
+            // Keep the connection open until the client is done
+            std::thread::sleep(Duration::from_millis(500));
+        });
+
+        let mut client = ClientBuilder::default().connect(addr).unwrap();
+
+        let drained = client.drain_pending(Duration::from_millis(200)).unwrap();
+        assert_eq!(drained, 3);
+
+        // The socket is quiet by now
+        let drained = client.drain_pending(Duration::ZERO).unwrap();
+        assert_eq!(drained, 0);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn per_call_timeout_is_restored_afterwards() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Answer well after the timeout of the first request
+            std::thread::sleep(Duration::from_millis(300));
+            let response = Response {
+                value: Some(response::Value::Echo(ResponseEcho::default())),
+            };
+            stream
+                .write_all(&response.encode_length_delimited_to_vec())
+                .unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+        });
+
+        let mut client = ClientBuilder::default().connect(addr).unwrap();
+        let echo = Request {
+            value: Some(request::Value::Echo(RequestEcho::default())),
+        };
+
+        let result = client.perform_with_timeout(echo, Duration::from_millis(50));
+        assert!(result.is_err());
+
+        // Without a timeout anymore, the next request waits for the late response
+        client.echo(RequestEcho::default()).unwrap();
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn echo_bytes_round_trips_invalid_utf8() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Echo back the raw bytes of the request, as the response has the same layout
+        // other than the tag of its `echo` variant
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut frame = vec![0; 8];
+            stream.read_exact(&mut frame).unwrap();
+            assert_eq!(frame, [7, 0x0a, 5, 0x0a, 3, 0xff, 0x00, 0xfe]);
+
+            frame[1] = 0x12;
+            stream.write_all(&frame).unwrap();
+        });
+
+        let mut client = ClientBuilder::default().connect(addr).unwrap();
+        let echoed = client.echo_bytes(vec![0xff, 0x00, 0xfe]).unwrap();
+        assert_eq!(echoed, [0xff, 0x00, 0xfe]);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn encoded_request_matches_wire_bytes() {
+        let echo = RequestEcho {
+            message: "audited".to_string(),
+        };
+        let expected = Client::encode_request(&Request {
+            value: Some(request::Value::Echo(echo.clone())),
+        })
+        .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let len = expected.len();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = vec![0; len];
+            stream.read_exact(&mut received).unwrap();
+
+            let response = Response {
+                value: Some(response::Value::Echo(ResponseEcho {
+                    message: "audited".to_string(),
+                })),
+            };
+            stream
+                .write_all(&response.encode_length_delimited_to_vec())
+                .unwrap();
+
+            received
+        });
+
+        let mut client = ClientBuilder::default().connect(addr).unwrap();
+        client.echo(echo).unwrap();
+
+        assert_eq!(server.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn decode_response_requires_exactly_one_frame() {
+        let response = Response {
+            value: Some(response::Value::Flush(ResponseFlush {})),
+        };
+        let bytes = response.encode_length_delimited_to_vec();
+
+        assert_eq!(Client::decode_response(&bytes).unwrap(), response);
+        assert!(Client::decode_response(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Client::decode_response(&[bytes.clone(), bytes].concat()).is_err());
+    }
+
+    #[test]
+    fn bind_sets_the_local_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Pick a free local port to bind the client to
+        let local = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (_stream, peer) = listener.accept().unwrap();
+            peer
+        });
+
+        let client = ClientBuilder::default().bind(local).connect(addr).unwrap();
+        assert!(client.is_connected());
+        assert_eq!(server.join().unwrap(), local);
+
+        // Binding to an address which is not local fails to connect
+        let result = ClientBuilder::default()
+            .bind("192.0.2.1:0".parse().unwrap())
+            .connect_timeout(&addr, Duration::from_secs(1));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn configure_socket_runs_on_the_connected_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let _first = listener.accept().unwrap();
+            let _second = listener.accept().unwrap();
+        });
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let client = ClientBuilder::default()
+            .configure_socket(move |socket| {
+                socket.set_nodelay(true)?;
+                tx.send((socket.peer_addr()?.as_socket(), socket.nodelay()?))
+                    .unwrap();
+                Ok(())
+            })
+            .connect(addr)
+            .unwrap();
+        assert!(client.is_connected());
+        assert_eq!(rx.recv().unwrap(), (Some(addr), true));
+
+        // Errors of the closure fail the connection
+        let result = ClientBuilder::default()
+            .configure_socket(|_| Err(io::Error::other("unsupported option")))
+            .connect(addr);
+        match result.map_err(|e| e.into_detail()) {
+            Err(ErrorDetail::Io(e)) => assert_eq!(e.source.to_string(), "unsupported option"),
+            other => panic!("expected an I/O error, got {:?}", other.err()),
+        }
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn socket_buffer_sizes_are_set_on_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let _conn = listener.accept().unwrap();
+        });
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let requested = 256 * 1024;
+        let _client = ClientBuilder::default()
+            .recv_buffer_size(requested)
+            .send_buffer_size(requested)
+            .configure_socket(move |socket| {
+                tx.send((socket.recv_buffer_size()?, socket.send_buffer_size()?))
+                    .unwrap();
+                Ok(())
+            })
+            .connect(addr)
+            .unwrap();
+
+        // The OS may round the sizes up, eg. Linux doubles them for its own bookkeeping
+        let (recv, send) = rx.recv().unwrap();
+        assert!(recv >= requested, "receive buffer of {recv} bytes");
+        assert!(send >= requested, "send buffer of {send} bytes");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn reconnect_detects_a_replaced_application() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let info = |app_version, last_block_height| ResponseInfo {
+            data: "kvstore".to_string(),
+            version: "1.0.0".to_string(),
+            app_version,
+            last_block_height,
+            ..Default::default()
+        };
+
+        // Answer the handshake of each connection, the application being replaced
+        // before the third one
+        let responses = [info(1, 10), info(1, 11), info(2, 0)];
+        let server = std::thread::spawn(move || {
+            let mut streams = Vec::new();
+            for info in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let response = Response {
+                    value: Some(response::Value::Info(info)),
+                };
+                stream
+                    .write_all(&response.encode_length_delimited_to_vec())
+                    .unwrap();
+                streams.push(stream);
+            }
+            // Keep the connections open until the client is done
+            std::thread::sleep(Duration::from_millis(200));
+        });
+
+        let mut client = ClientBuilder::default()
+            .handshake_on_connect(true)
+            .connect(addr)
+            .unwrap();
+        assert_eq!(client.server_info(), Some(&info(1, 10)));
+
+        // The same application is refreshed
+        client.reconnect().unwrap();
+        assert_eq!(client.server_info(), Some(&info(1, 11)));
+
+        match client.reconnect().map_err(|e| e.into_detail()) {
+            Err(ErrorDetail::ServerIdentityChanged(e)) => {
+                assert_eq!(e.old, info(1, 11));
+                assert_eq!(e.new, info(2, 0));
+            },
+            other => panic!("expected the server identity to change, got {other:?}"),
+        }
+        assert_eq!(client.server_info(), Some(&info(1, 11)));
+
+        server.join().unwrap();
+    }
+
+    #[derive(Clone)]
+    struct VersionedApp;
+
+    impl Application for VersionedApp {
+        fn info(&self, _request: RequestInfo) -> ResponseInfo {
+            ResponseInfo {
+                app_version: 3,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[test]
+    fn handshake_checks_the_app_version() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", VersionedApp)
+            .unwrap();
+        let addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+
+        let connect = |supported| {
+            ClientBuilder::default()
+                .handshake_on_connect(true)
+                .require_app_version(supported)
+                .connect(&addr)
+        };
+
+        let client = connect(2..=3).unwrap();
+        assert_eq!(client.server_info().unwrap().app_version, 3);
+
+        match connect(1..=2).map_err(|e| e.into_detail()) {
+            Err(ErrorDetail::UnsupportedAppVersion(e)) => {
+                assert_eq!(e.got, 3);
+                assert_eq!(e.supported, 1..=2);
+            },
+            other => panic!("expected an unsupported app version, got {:?}", other.err()),
+        }
+
+        // Without the handshake, the app version is unknown and not checked
+        ClientBuilder::default()
+            .require_app_version(1..=2)
+            .connect(&addr)
+            .unwrap();
+    }
+
+    /// Answers every request with the default implementation, eg. echoes messages back.
+    #[derive(Clone)]
+    struct PlainApp;
+
+    impl Application for PlainApp {}
+
+    #[test]
+    fn max_frame_seen_survives_reconnections() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", PlainApp)
+            .unwrap();
+        let addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+
+        let mut client = ClientBuilder::new(64).connect(addr).unwrap();
+        assert_eq!(client.max_frame_seen(), 0);
+
+        let message = "x".repeat(1000);
+        let frame = Response {
+            value: Some(response::Value::Echo(ResponseEcho {
+                message: message.clone(),
+            })),
+        }
+        .encode_length_delimited_to_vec();
+
+        client.echo(RequestEcho { message }).unwrap();
+        assert_eq!(client.max_frame_seen(), frame.len());
+
+        // Smaller frames on a new connection do not lower it
+        client.reconnect().unwrap();
+        client.echo(RequestEcho::default()).unwrap();
+        assert_eq!(client.max_frame_seen(), frame.len());
+    }
+
+    #[test]
+    fn frames_split_across_reads_are_reassembled() {
+        let (client_stream, mut server_stream) = channel_pair();
+        let mut client = ClientBuilder::default()
+            .connect_stream(client_stream)
+            .unwrap();
+
+        // Longer than the read buffer, and with a length prefix of several bytes
+        let message = "x".repeat(3000);
+        let response = Response {
+            value: Some(response::Value::Echo(ResponseEcho {
+                message: message.clone(),
+            })),
+        };
+
+        // Each write of a channel stream is received by a separate read
+        for byte in response.encode_length_delimited_to_vec() {
+            server_stream.write_all(&[byte]).unwrap();
+        }
+
+        let echoed = client.echo(RequestEcho::default()).unwrap();
+        assert_eq!(echoed.message, message);
+    }
+
+    #[test]
+    fn truncated_frames_are_told_apart_from_a_clean_end_of_stream() {
+        let response = Response {
+            value: Some(response::Value::Echo(ResponseEcho {
+                message: "truncated".to_string(),
+            })),
+        }
+        .encode_length_delimited_to_vec();
+
+        // Answer the request with the given bytes, and hang up
+        let echo = |reply: Vec<u8>| {
+            let (client_stream, mut server_stream) = channel_pair();
+            let mut client = ClientBuilder::default()
+                .connect_stream(client_stream)
+                .unwrap();
+
+            let server = std::thread::spawn(move || {
+                let mut request = [0; 64];
+                let _ = server_stream.read(&mut request).unwrap();
+                server_stream.write_all(&reply).unwrap();
+            });
+
+            let result = client.echo(RequestEcho::default());
+            server.join().unwrap();
+            result.map_err(|e| e.into_detail())
+        };
+
+        match echo(response[..response.len() / 2].to_vec()) {
+            Err(ErrorDetail::UnexpectedEof(e)) => assert_eq!(e.buffered, response.len() / 2),
+            other => panic!("expected an unexpected end of stream, got {other:?}"),
+        }
+
+        assert!(matches!(
+            echo(Vec::new()),
+            Err(ErrorDetail::ServerConnectionTerminated(_))
+        ));
+    }
+
+    /// Reports the same last block height on every `Info` request.
+    #[derive(Clone)]
+    struct TipApp;
+
+    impl Application for TipApp {
+        fn info(&self, _request: RequestInfo) -> ResponseInfo {
+            ResponseInfo {
+                last_block_height: 7,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[test]
+    fn tip_is_the_last_block_height() {
+        let (client_stream, server_stream) = channel_pair();
+        let _server = ServerBuilder::default().serve_stream(server_stream, TipApp);
+        let mut client = ClientBuilder::default()
+            .connect_stream(client_stream)
+            .unwrap();
+
+        assert_eq!(client.latest_height().unwrap(), 7);
+        assert!(client.is_tip(7).unwrap());
+        assert!(!client.is_tip(6).unwrap());
+        assert!(!client.is_tip(8).unwrap());
+    }
+
+    #[test]
+    fn stream_clients_keep_their_stream_on_reconnect() {
+        let (client_stream, server_stream) = channel_pair();
+        let _server = ServerBuilder::default().serve_stream(server_stream, TipApp);
+        let mut client = ClientBuilder::default()
+            .connect_stream(client_stream)
+            .unwrap();
+
+        assert!(matches!(
+            client.reconnect().map_err(|e| e.into_detail()),
+            Err(ErrorDetail::NotReconnectable(_))
+        ));
+        assert_eq!(client.latest_height().unwrap(), 7);
+    }
+
+    /// Reports a higher last block height on every `Info` request.
+    #[derive(Clone, Default)]
+    struct GrowingApp(Arc<AtomicI64>);
+
+    impl Application for GrowingApp {
+        fn info(&self, _request: RequestInfo) -> ResponseInfo {
+            ResponseInfo {
+                last_block_height: self.0.fetch_add(1, Ordering::SeqCst) + 1,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[test]
+    fn health_poll_reports_the_latest_info() {
+        let (client_stream, server_stream) = channel_pair();
+        let app = GrowingApp::default();
+        let server = ServerBuilder::default().serve_stream(server_stream, app.clone());
+
+        let client = ClientBuilder::default()
+            .connect_stream(client_stream)
+            .unwrap();
+        let health = client.start_health_poll(Duration::from_millis(10));
+
+        // Wait for a few polls
+        let mut height = 0;
+        for _ in 0..200 {
+            if let Some(status) = health.latest() {
+                height = status.info.unwrap().last_block_height;
+                if height >= 3 {
+                    break;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(height >= 3, "expected at least 3 polls, got {height}");
+
+        // The client is handed back, still connected
+        let mut client = health.stop();
+        let polls = app.0.load(Ordering::SeqCst);
+        let info = client.info(RequestInfo::default()).unwrap();
+        assert_eq!(info.last_block_height, polls + 1);
+
+        drop(client);
+        server.join().unwrap();
+    }
+}
@@ -2,8 +2,10 @@
 
 #[cfg(all(feature = "client", feature = "echo-app"))]
 mod echo_app_integration {
-    use tendermint_abci::{ClientBuilder, EchoApp, ServerBuilder};
-    use tendermint_proto::v0_38::abci::RequestEcho;
+    use tendermint_abci::{channel_pair, ClientBuilder, EchoApp, ServerBuilder};
+    use tendermint_proto::v0_38::abci::{
+        request, response, CommitInfo, Request, RequestEcho, RequestFinalizeBlock,
+    };
 
     #[test]
     fn echo() {
@@ -21,4 +23,108 @@ mod echo_app_integration {
             .unwrap();
         assert_eq!(response.message, "Hello ABCI!");
     }
+
+    #[test]
+    fn lazy_client_connects_on_first_request() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", EchoApp)
+            .unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default()
+            .lazy(true)
+            .connect(server_addr)
+            .unwrap();
+
+        assert!(!client.is_connected());
+
+        let response = client
+            .echo(RequestEcho {
+                message: "Hello ABCI!".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.message, "Hello ABCI!");
+        assert!(client.is_connected());
+    }
+
+    #[test]
+    fn echo_over_channel() {
+        let (client_stream, server_stream) = channel_pair();
+        let server = ServerBuilder::default().serve_stream(server_stream, EchoApp);
+        let mut client = ClientBuilder::default()
+            .connect_stream(client_stream)
+            .unwrap();
+
+        let response = client
+            .echo(RequestEcho {
+                message: "Hello ABCI!".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.message, "Hello ABCI!");
+
+        // Dropping the client terminates the server handler
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn finalize_block_summary_pairs_request_and_response() {
+        let (client_stream, server_stream) = channel_pair();
+        let server = ServerBuilder::default().serve_stream(server_stream, EchoApp);
+        let mut client = ClientBuilder::default()
+            .connect_stream(client_stream)
+            .unwrap();
+
+        let summary = client
+            .finalize_block_summary(RequestFinalizeBlock {
+                height: 42,
+                hash: vec![1; 32].into(),
+                proposer_address: vec![2; 20].into(),
+                decided_last_commit: Some(CommitInfo {
+                    round: 3,
+                    votes: vec![],
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(summary.height, 42);
+        assert_eq!(summary.hash.as_ref(), [1; 32]);
+        assert_eq!(summary.proposer_address.as_ref(), [2; 20]);
+        assert_eq!(summary.last_commit_round, Some(3));
+        assert_eq!(summary.response, Default::default());
+
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn large_batch_does_not_deadlock() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", EchoApp)
+            .unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default()
+            .max_in_flight(4)
+            .connect(server_addr)
+            .unwrap();
+
+        // Far more data than fits in the socket buffers of both ends
+        let message = "x".repeat(64 * 1024);
+        let requests = (0..256).map(|_| Request {
+            value: Some(request::Value::Echo(RequestEcho {
+                message: message.clone(),
+            })),
+        });
+
+        let responses = client.send_batch(requests).unwrap();
+        assert_eq!(responses.len(), 256);
+        for res in responses {
+            match res.value {
+                Some(response::Value::Echo(echo)) => assert_eq!(echo.message, message),
+                other => panic!("unexpected response: {other:?}"),
+            }
+        }
+    }
 }
@@ -4,7 +4,7 @@
 mod kvstore_app_integration {
     use std::thread;
 
-    use tendermint_abci::{ClientBuilder, KeyValueStoreApp, ServerBuilder};
+    use tendermint_abci::{Client, ClientBuilder, KeyValueStoreApp, ServerBuilder};
     use tendermint_proto::v0_38::abci::{RequestEcho, RequestFinalizeBlock, RequestQuery};
 
     #[test]
@@ -41,4 +41,141 @@ mod kvstore_app_integration {
             .unwrap();
         assert_eq!(res.value, "test-value".as_bytes());
     }
+
+    #[test]
+    fn query_builder_queries_the_latest_height() {
+        let (app, driver) = KeyValueStoreApp::new();
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        thread::spawn(move || driver.run());
+        thread::spawn(move || server.listen());
+
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+        client
+            .finalize_block(RequestFinalizeBlock {
+                txs: vec!["test-key=test-value".into()],
+                ..Default::default()
+            })
+            .unwrap();
+        client.commit().unwrap();
+
+        let res = client
+            .query(Client::query_builder().data("test-key"))
+            .unwrap();
+        assert_eq!(res.value, "test-value".as_bytes());
+        assert_eq!(res.height, 1);
+    }
+
+    #[cfg(feature = "test-helpers")]
+    #[test]
+    fn simulate_tx_checks_and_finalizes() {
+        let (app, driver) = KeyValueStoreApp::new();
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        thread::spawn(move || driver.run());
+        thread::spawn(move || server.listen());
+
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+        let result = client
+            .simulate_tx(b"sim-key=sim-value".to_vec(), 1)
+            .unwrap();
+        assert_eq!(result.check_tx.code, 0);
+
+        let finalize_block = result.finalize_block.unwrap();
+        let attrs = &finalize_block.events[0].attributes;
+        assert_eq!(attrs[0].key, "key");
+        assert_eq!(attrs[0].value, "sim-key");
+
+        client.commit().unwrap();
+        let res = client
+            .query(Client::query_builder().data("sim-key"))
+            .unwrap();
+        assert_eq!(res.value, "sim-value".as_bytes());
+    }
+
+    #[test]
+    fn implicit_latest_queries_can_be_forbidden() {
+        let (app, driver) = KeyValueStoreApp::new();
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        thread::spawn(move || driver.run());
+        thread::spawn(move || server.listen());
+
+        let mut client = ClientBuilder::default()
+            .forbid_implicit_latest_query(true)
+            .connect(server_addr)
+            .unwrap();
+
+        client
+            .finalize_block(RequestFinalizeBlock {
+                txs: vec!["test-key=test-value".into()],
+                ..Default::default()
+            })
+            .unwrap();
+        client.commit().unwrap();
+
+        let err = client
+            .query(Client::query_builder().data("test-key"))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("implicitly queries the latest height"));
+
+        let res = client
+            .query(Client::query_builder().data("test-key").height(1))
+            .unwrap();
+        assert_eq!(res.value, "test-value".as_bytes());
+
+        let res = client
+            .query_latest(Client::query_builder().data("test-key"))
+            .unwrap();
+        assert_eq!(res.value, "test-value".as_bytes());
+    }
+
+    #[test]
+    fn query_builder_defaults_to_latest_without_proof() {
+        let req = Client::query_builder().path("/store").build();
+        assert_eq!(
+            req,
+            RequestQuery {
+                data: Default::default(),
+                path: "/store".to_string(),
+                height: 0,
+                prove: false,
+            }
+        );
+
+        let req = Client::query_builder()
+            .height(5)
+            .prove(true)
+            .latest()
+            .build();
+        assert_eq!(req.height, 0);
+        assert!(req.prove);
+    }
+
+    #[test]
+    fn handshake_fetches_server_info() {
+        let (app, driver) = KeyValueStoreApp::new();
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        thread::spawn(move || driver.run());
+        thread::spawn(move || server.listen());
+
+        let client = ClientBuilder::default().connect(&server_addr).unwrap();
+        assert!(client.server_info().is_none());
+
+        let mut client = ClientBuilder::default()
+            .handshake_on_connect(true)
+            .lazy(true)
+            .connect(server_addr)
+            .unwrap();
+        assert!(client.server_info().is_none());
+
+        client.flush().unwrap();
+        let info = client.server_info().unwrap();
+        assert_eq!(info.data, "kvstore-rs");
+        assert_eq!(info.app_version, 1);
+        assert_eq!(info.last_block_height, 0);
+    }
 }
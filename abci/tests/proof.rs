@@ -0,0 +1,211 @@
+//! Verification of query proofs against simple Merkle trees built here.
+
+use prost::Message;
+use sha2::{Digest, Sha256};
+use tendermint_abci::{
+    error::ErrorDetail, verify_query_proof, AbsenceOp, Neighbour, ABSENCE_OP_TYPE, VALUE_OP_TYPE,
+};
+use tendermint_proto::v0_38::crypto::{Proof, ProofOp, ProofOps, ValueOp};
+
+/// A simple Merkle tree of key-value pairs, sorted by key.
+struct Tree {
+    leaves: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Tree {
+    fn new(pairs: &[(&str, &str)]) -> Self {
+        let mut leaves: Vec<_> = pairs
+            .iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), Sha256::digest(v).to_vec()))
+            .collect();
+        leaves.sort();
+        Self { leaves }
+    }
+
+    fn leaf_hashes(&self) -> Vec<Vec<u8>> {
+        self.leaves
+            .iter()
+            .map(|(key, value_hash)| {
+                let mut leaf = vec![0x00];
+                for bytes in [key, value_hash] {
+                    prost::encoding::encode_varint(bytes.len() as u64, &mut leaf);
+                    leaf.extend_from_slice(bytes);
+                }
+                Sha256::digest(leaf).to_vec()
+            })
+            .collect()
+    }
+
+    fn root(&self) -> Vec<u8> {
+        root_and_aunts(&self.leaf_hashes(), 0).0
+    }
+
+    fn proof(&self, index: usize) -> Proof {
+        let leaf_hashes = self.leaf_hashes();
+        Proof {
+            total: leaf_hashes.len() as i64,
+            index: index as i64,
+            leaf_hash: leaf_hashes[index].clone(),
+            aunts: root_and_aunts(&leaf_hashes, index).1,
+        }
+    }
+
+    fn value_op(&self, index: usize) -> ProofOp {
+        let key = self.leaves[index].0.clone();
+        ProofOp {
+            r#type: VALUE_OP_TYPE.to_string(),
+            key: key.clone(),
+            data: ValueOp {
+                key,
+                proof: Some(self.proof(index)),
+            }
+            .encode_to_vec(),
+        }
+    }
+
+    fn neighbour(&self, index: usize) -> Neighbour {
+        Neighbour {
+            key: self.leaves[index].0.clone(),
+            value_hash: self.leaves[index].1.clone(),
+            proof: Some(self.proof(index)),
+        }
+    }
+
+    fn absence_op(&self, key: &str, left: Option<usize>, right: Option<usize>) -> ProofOp {
+        ProofOp {
+            r#type: ABSENCE_OP_TYPE.to_string(),
+            key: key.as_bytes().to_vec(),
+            data: AbsenceOp {
+                left: left.map(|i| self.neighbour(i)),
+                right: right.map(|i| self.neighbour(i)),
+            }
+            .encode_to_vec(),
+        }
+    }
+}
+
+/// The root of the tree of the given leaf hashes, and the aunts of the given leaf.
+fn root_and_aunts(leaf_hashes: &[Vec<u8>], index: usize) -> (Vec<u8>, Vec<Vec<u8>>) {
+    if leaf_hashes.len() == 1 {
+        return (leaf_hashes[0].clone(), Vec::new());
+    }
+
+    let split = leaf_hashes.len().next_power_of_two() / 2;
+    let (left, right) = leaf_hashes.split_at(split);
+    let (left_root, left_aunts) = root_and_aunts(left, index);
+    let (right_root, right_aunts) = root_and_aunts(right, index.saturating_sub(split));
+    let root = Sha256::new()
+        .chain_update([0x01])
+        .chain_update(&left_root)
+        .chain_update(&right_root)
+        .finalize()
+        .to_vec();
+
+    let aunts = if index < split {
+        [left_aunts, vec![right_root]].concat()
+    } else {
+        [right_aunts, vec![left_root]].concat()
+    };
+
+    (root, aunts)
+}
+
+fn ops(ops: Vec<ProofOp>) -> ProofOps {
+    ProofOps { ops }
+}
+
+fn kvstore() -> Tree {
+    Tree::new(&[("a", "1"), ("c", "2"), ("e", "3"), ("g", "4"), ("i", "5")])
+}
+
+#[test]
+fn values_are_verified_against_the_app_hash() {
+    let tree = kvstore();
+
+    for (index, (key, value)) in ["a", "c", "e", "g", "i"].into_iter().zip(1..).enumerate() {
+        let value = value.to_string();
+        let proof = ops(vec![tree.value_op(index)]);
+        let valid =
+            verify_query_proof(&proof, &tree.root(), key.as_bytes(), Some(value.as_bytes()));
+        assert!(valid.unwrap(), "key {key} should verify");
+    }
+
+    let proof = ops(vec![tree.value_op(1)]);
+    assert!(!verify_query_proof(&proof, &tree.root(), b"c", Some(b"3")).unwrap());
+    assert!(!verify_query_proof(&proof, &[0; 32], b"c", Some(b"2")).unwrap());
+    assert!(!verify_query_proof(&proof, &tree.root(), b"c", None).unwrap());
+}
+
+#[test]
+fn roots_are_chained_through_outer_trees() {
+    let store = kvstore();
+    let multistore = Tree {
+        leaves: vec![
+            (b"bank".to_vec(), Sha256::digest(b"other").to_vec()),
+            (b"kv".to_vec(), Sha256::digest(store.root()).to_vec()),
+        ],
+    };
+
+    let proof = ops(vec![store.value_op(2), multistore.value_op(1)]);
+    assert!(verify_query_proof(&proof, &multistore.root(), b"e", Some(b"3")).unwrap());
+    assert!(!verify_query_proof(&proof, &store.root(), b"e", Some(b"3")).unwrap());
+}
+
+#[test]
+fn absent_keys_are_verified_against_their_neighbours() {
+    let tree = kvstore();
+    let root = tree.root();
+
+    let between = ops(vec![tree.absence_op("d", Some(1), Some(2))]);
+    assert!(verify_query_proof(&between, &root, b"d", None).unwrap());
+    assert!(!verify_query_proof(&between, &root, b"d", Some(b"4")).unwrap());
+
+    let below = ops(vec![tree.absence_op("0", None, Some(0))]);
+    assert!(verify_query_proof(&below, &root, b"0", None).unwrap());
+
+    let above = ops(vec![tree.absence_op("z", Some(4), None)]);
+    assert!(verify_query_proof(&above, &root, b"z", None).unwrap());
+
+    // Neighbours skipping over a leaf, which may hold the key
+    let skipping = ops(vec![tree.absence_op("e", Some(1), Some(3))]);
+    assert!(!verify_query_proof(&skipping, &root, b"e", None).unwrap());
+
+    // Neighbours not surrounding the key
+    let outside = ops(vec![tree.absence_op("b", Some(1), Some(2))]);
+    assert!(!verify_query_proof(&outside, &root, b"b", None).unwrap());
+}
+
+#[test]
+fn malformed_proofs_are_rejected() {
+    let tree = kvstore();
+    let root = tree.root();
+
+    assert!(matches!(
+        verify_query_proof(&ops(Vec::new()), &root, b"a", Some(b"1")).map_err(|e| e.into_detail()),
+        Err(ErrorDetail::MalformedProof(_))
+    ));
+
+    let mut unsupported = tree.value_op(0);
+    unsupported.r#type = "ics23:iavl".to_string();
+    match verify_query_proof(&ops(vec![unsupported]), &root, b"a", Some(b"1"))
+        .map_err(|e| e.into_detail())
+    {
+        Err(ErrorDetail::UnsupportedProofOp(e)) => assert_eq!(e.op_type, "ics23:iavl"),
+        other => panic!("expected an unsupported operation, got {other:?}"),
+    }
+
+    let mut out_of_bounds = tree.proof(0);
+    out_of_bounds.index = out_of_bounds.total;
+    let op = ProofOp {
+        data: ValueOp {
+            key: b"a".to_vec(),
+            proof: Some(out_of_bounds),
+        }
+        .encode_to_vec(),
+        ..tree.value_op(0)
+    };
+    assert!(matches!(
+        verify_query_proof(&ops(vec![op]), &root, b"a", Some(b"1")).map_err(|e| e.into_detail()),
+        Err(ErrorDetail::MalformedProof(_))
+    ));
+}
@@ -0,0 +1,362 @@
+//! Integration tests for the ABCI client's snapshot restoration.
+
+#[cfg(feature = "client")]
+mod snapshot_integration {
+    use std::{
+        future::{self, Future},
+        pin::{pin, Pin},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        task::{Context, Poll, Wake, Waker},
+        time::{Duration, Instant},
+    };
+
+    use bytes::Bytes;
+    use tendermint_abci::{
+        error::ErrorDetail, Application, ClientBuilder, ServerBuilder, SnapshotChunk,
+    };
+    use tendermint_proto::v0_38::abci::{
+        response_apply_snapshot_chunk, response_offer_snapshot, RequestApplySnapshotChunk,
+        RequestEcho, RequestOfferSnapshot, ResponseApplySnapshotChunk, ResponseListSnapshots,
+        ResponseOfferSnapshot, Snapshot,
+    };
+
+    /// Accepts any snapshot, asks for the first chunk to be retried once
+    /// and rejects any chunk coming from the "bad" peer.
+    #[derive(Clone, Default)]
+    struct SnapshotApp {
+        applied: Arc<Mutex<Vec<(u32, String)>>>,
+        delay: Duration,
+    }
+
+    impl Application for SnapshotApp {
+        fn list_snapshots(&self) -> ResponseListSnapshots {
+            let snapshot = |height, format| Snapshot {
+                height,
+                format,
+                ..Default::default()
+            };
+
+            ResponseListSnapshots {
+                snapshots: vec![
+                    snapshot(20, 1),
+                    snapshot(30, 1),
+                    snapshot(10, 2),
+                    snapshot(30, 2),
+                ],
+            }
+        }
+
+        fn offer_snapshot(&self, _request: RequestOfferSnapshot) -> ResponseOfferSnapshot {
+            ResponseOfferSnapshot {
+                result: response_offer_snapshot::Result::Accept as i32,
+            }
+        }
+
+        fn apply_snapshot_chunk(
+            &self,
+            request: RequestApplySnapshotChunk,
+        ) -> ResponseApplySnapshotChunk {
+            std::thread::sleep(self.delay);
+
+            let mut applied = self.applied.lock().unwrap();
+            applied.push((request.index, request.sender.clone()));
+
+            let mut response = ResponseApplySnapshotChunk {
+                result: response_apply_snapshot_chunk::Result::Accept as i32,
+                ..Default::default()
+            };
+
+            if request.sender == "bad" {
+                response.refetch_chunks = vec![request.index];
+                response.reject_senders = vec![request.sender];
+            } else if request.index == 0 && applied.len() == 1 {
+                response.result = response_apply_snapshot_chunk::Result::Retry as i32;
+            }
+
+            response
+        }
+    }
+
+    fn snapshot(chunks: u32) -> Snapshot {
+        Snapshot {
+            height: 1,
+            format: 1,
+            chunks,
+            ..Default::default()
+        }
+    }
+
+    fn fetch_chunk(
+        index: u32,
+        rejected: &[String],
+    ) -> Result<SnapshotChunk, tendermint_abci::Error> {
+        let sender = if index == 1 && rejected.is_empty() {
+            "bad"
+        } else {
+            "good"
+        };
+
+        Ok(SnapshotChunk {
+            chunk: Bytes::from(vec![index as u8]),
+            sender: sender.to_string(),
+        })
+    }
+
+    #[test]
+    fn apply_snapshot_follows_application_results() {
+        let app = SnapshotApp::default();
+        let applied = app.applied.clone();
+
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        client
+            .apply_snapshot(
+                snapshot(3),
+                Bytes::new(),
+                Duration::from_secs(5),
+                fetch_chunk,
+            )
+            .unwrap();
+
+        let applied = applied.lock().unwrap();
+        assert_eq!(
+            *applied,
+            vec![
+                (0, "good".to_string()),
+                (0, "good".to_string()),
+                (1, "bad".to_string()),
+                (1, "good".to_string()),
+                (2, "good".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshots_are_listed_newest_first() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", SnapshotApp::default())
+            .unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        let listed: Vec<_> = client
+            .list_snapshots_sorted()
+            .unwrap()
+            .iter()
+            .map(|snapshot| (snapshot.height, snapshot.format))
+            .collect();
+        assert_eq!(listed, [(30, 2), (30, 1), (20, 1), (10, 2)]);
+
+        let best = client.best_snapshot(30).unwrap().unwrap();
+        assert_eq!((best.height, best.format), (30, 2));
+        assert_eq!(client.best_snapshot(31).unwrap(), None);
+    }
+
+    #[test]
+    fn apply_snapshot_fails_when_over_budget() {
+        let app = SnapshotApp {
+            delay: Duration::from_millis(200),
+            ..Default::default()
+        };
+
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        let budget = Duration::from_millis(300);
+        let err = client
+            .apply_snapshot(snapshot(3), Bytes::new(), budget, fetch_chunk)
+            .unwrap_err();
+
+        match err.detail() {
+            ErrorDetail::SnapshotTimeout(e) => assert_eq!(e.budget, budget),
+            e => panic!("expected a snapshot timeout, got {e:?}"),
+        }
+    }
+
+    /// Accepts any snapshot, then slowly asks for every chunk to be retried.
+    #[derive(Clone)]
+    struct RetryingApp;
+
+    impl Application for RetryingApp {
+        fn offer_snapshot(&self, _request: RequestOfferSnapshot) -> ResponseOfferSnapshot {
+            ResponseOfferSnapshot {
+                result: response_offer_snapshot::Result::Accept as i32,
+            }
+        }
+
+        fn apply_snapshot_chunk(
+            &self,
+            _request: RequestApplySnapshotChunk,
+        ) -> ResponseApplySnapshotChunk {
+            std::thread::sleep(Duration::from_millis(100));
+
+            ResponseApplySnapshotChunk {
+                result: response_apply_snapshot_chunk::Result::Retry as i32,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[test]
+    fn apply_snapshot_retries_are_bounded_by_the_budget() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", RetryingApp)
+            .unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        let budget = Duration::from_millis(300);
+        let started = Instant::now();
+        let err = client
+            .apply_snapshot(snapshot(1), Bytes::new(), budget, fetch_chunk)
+            .unwrap_err();
+
+        match err.detail() {
+            ErrorDetail::SnapshotTimeout(e) => assert_eq!(e.budget, budget),
+            e => panic!("expected a snapshot timeout, got {e:?}"),
+        }
+        assert!(started.elapsed() < budget * 3);
+    }
+
+    /// Accepts snapshots of format 1, aborts on format 2 and rejects any other format.
+    #[derive(Clone)]
+    struct PickyApp;
+
+    impl Application for PickyApp {
+        fn offer_snapshot(&self, request: RequestOfferSnapshot) -> ResponseOfferSnapshot {
+            let result = match request.snapshot.unwrap().format {
+                1 => response_offer_snapshot::Result::Accept,
+                2 => response_offer_snapshot::Result::Abort,
+                _ => response_offer_snapshot::Result::RejectFormat,
+            };
+
+            ResponseOfferSnapshot {
+                result: result as i32,
+            }
+        }
+    }
+
+    #[test]
+    fn checked_offers_fail_unless_accepted() {
+        let server = ServerBuilder::default()
+            .bind("127.0.0.1:0", PickyApp)
+            .unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        let mut offer = |format| {
+            client
+                .offer_snapshot_checked(RequestOfferSnapshot {
+                    snapshot: Some(Snapshot {
+                        format,
+                        ..snapshot(1)
+                    }),
+                    app_hash: Bytes::new(),
+                })
+                .map_err(|e| e.into_detail())
+        };
+
+        offer(1).unwrap();
+        assert!(matches!(offer(2), Err(ErrorDetail::SnapshotAborted(_))));
+        match offer(3) {
+            Err(ErrorDetail::SnapshotRejected(e)) => assert_eq!(e.result, "REJECT_FORMAT"),
+            other => panic!("expected a rejected snapshot, got {other:?}"),
+        }
+    }
+
+    /// Wakes the thread blocked in [`block_on`].
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Run the given future to completion on the current thread.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    type ChunkFuture = Pin<Box<dyn Future<Output = Result<SnapshotChunk, tendermint_abci::Error>>>>;
+
+    #[test]
+    fn cancellable_snapshot_follows_application_results() {
+        let app = SnapshotApp::default();
+        let applied = app.applied.clone();
+
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        block_on(client.apply_snapshot_cancellable(
+            snapshot(3),
+            Bytes::new(),
+            |index, rejected| future::ready(fetch_chunk(index, rejected)),
+            future::pending(),
+        ))
+        .unwrap();
+
+        assert_eq!(applied.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn cancelled_snapshot_stops_fetching_chunks() {
+        let app = SnapshotApp::default();
+        let applied = app.applied.clone();
+
+        let server = ServerBuilder::default().bind("127.0.0.1:0", app).unwrap();
+        let server_addr = server.local_addr();
+        let _ = std::thread::spawn(move || server.listen());
+        let mut client = ClientBuilder::default().connect(server_addr).unwrap();
+
+        // Cancel while fetching the last chunk, which never arrives
+        let cancelled = AtomicBool::new(false);
+        let fetch = |index, rejected: &[String]| -> ChunkFuture {
+            if index == 2 {
+                cancelled.store(true, Ordering::SeqCst);
+                Box::pin(future::pending())
+            } else {
+                Box::pin(future::ready(fetch_chunk(index, rejected)))
+            }
+        };
+        let cancel = future::poll_fn(|_| {
+            if cancelled.load(Ordering::SeqCst) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        });
+
+        let result =
+            block_on(client.apply_snapshot_cancellable(snapshot(3), Bytes::new(), fetch, cancel));
+        assert!(matches!(
+            result.map_err(|e| e.into_detail()),
+            Err(ErrorDetail::Cancelled(_))
+        ));
+
+        // The last chunk was never applied, and the connection is still usable
+        assert!(applied.lock().unwrap().iter().all(|(index, _)| *index < 2));
+        client.echo(RequestEcho::default()).unwrap();
+    }
+}
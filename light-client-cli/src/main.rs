@@ -1,6 +1,6 @@
 #![allow(unused)]
 
-use std::{convert::Infallible, str::FromStr, time::Duration};
+use std::{convert::Infallible, str::FromStr, sync::Arc, time::Duration};
 
 use clap::Parser;
 use color_eyre::{
@@ -8,17 +8,20 @@ use color_eyre::{
     Report,
 };
 use futures::future::join_all;
-use tendermint::{crypto::default::Sha256, evidence::Evidence, Time};
+use tendermint::{crypto::default::Sha256, evidence::Evidence};
 use tendermint_light_client::{
     builder::LightClientBuilder,
+    components::{block_cache::BlockCache, io::LatencyStats},
     instance::Instance,
     light_client::Options,
+    peer_labeler::Labeled,
     store::memory::MemoryStore,
     types::{Hash, Height, LightBlock, TrustThreshold},
+    verifier::options::DEFAULT_FORK_DETECTION_QUORUM,
 };
 use tendermint_light_client_detector::{
-    compare_new_header_with_witness, detect_divergence, gather_evidence_from_conflicting_headers,
-    CompareError, Error, ErrorDetail, Provider, Trace,
+    compare_new_header_with_witness, detect_divergence, detect_forks,
+    gather_evidence_from_conflicting_headers, CompareError, Error, ErrorDetail, Provider, Trace,
 };
 use tendermint_rpc::{Client, HttpClient, HttpClientUrl, Url};
 use tracing::{debug, error, info, metadata::LevelFilter, warn};
@@ -108,6 +111,20 @@ struct Cli {
     #[clap(long, default_value = "5")]
     max_block_lag: u64,
 
+    /// Proceed without fork detection if no witness is reachable
+    #[clap(long)]
+    allow_primary_only: bool,
+
+    /// Number of witnesses which must respond for fork detection to complete
+    /// (default: all of them)
+    #[clap(long)]
+    fork_detection_quorum: Option<usize>,
+
+    /// Number of witnesses which may disagree with the primary, and are then evicted,
+    /// before a fork is declared
+    #[clap(long, default_value = "0")]
+    max_faulty_witnesses: usize,
+
     /// Increase verbosity
     #[clap(flatten)]
     verbose: Verbosity,
@@ -130,17 +147,34 @@ async fn main() -> Result<()> {
         .init();
 
     let options = Options {
-        trust_threshold: args.trust_threshold,
-        trusting_period: Duration::from_secs(args.trusting_period),
-        clock_drift: Duration::from_secs(args.max_clock_drift),
+        allow_primary_only: args.allow_primary_only,
+        fork_detection_quorum: args
+            .fork_detection_quorum
+            .unwrap_or(DEFAULT_FORK_DETECTION_QUORUM),
+        max_faulty_witnesses: args.max_faulty_witnesses,
+        ..Options::new(
+            args.trust_threshold,
+            Duration::from_secs(args.trusting_period),
+            Duration::from_secs(args.max_clock_drift),
+        )
     };
 
+    // Share the latency statistics of all peers, to report them from the primary
+    let latency = LatencyStats::new();
+
+    // Share the block cache of all peers, so that the blocks of the primary are reused
+    // by the witnesses for as long as the scope is open
+    let block_cache = Arc::new(BlockCache::new());
+    let block_cache_scope = block_cache.scope();
+
     let mut primary = make_provider(
         &args.chain_id,
         args.primary,
         args.trusted_height,
         args.trusted_hash,
         options,
+        latency.clone(),
+        block_cache.clone(),
     )
     .await?;
 
@@ -166,94 +200,51 @@ async fn main() -> Result<()> {
             trusted_block.height(),
             trusted_block.signed_header.header.hash(),
             options,
+            latency.clone(),
+            block_cache.clone(),
         )
     }))
     .await;
 
-    let mut witnesses = witnesses.into_iter().collect::<Result<Vec<_>>>()?;
+    let mut witnesses = if options.allow_primary_only {
+        // Drop the unreachable witnesses instead of bailing out,
+        // `detect_forks` will skip fork detection if none is left.
+        witnesses
+            .into_iter()
+            .filter_map(|witness| {
+                witness
+                    .map_err(|e| error!("failed to connect to witness: {e}"))
+                    .ok()
+            })
+            .collect::<Vec<_>>()
+    } else {
+        witnesses.into_iter().collect::<Result<Vec<_>>>()?
+    };
 
     let max_clock_drift = Duration::from_secs(args.max_clock_drift);
     let max_block_lag = Duration::from_secs(args.max_block_lag);
-    let now = Time::now();
 
-    run_detector(
-        &mut primary,
-        witnesses.as_mut_slice(),
+    let primary = Arc::new(primary);
+    let fork_detection = detect_forks::<Sha256>(
+        &primary,
+        &mut witnesses,
         primary_trace,
         max_clock_drift,
         max_block_lag,
-        now,
+        &options,
     )
     .await?;
 
-    Ok(())
-}
-
-async fn run_detector(
-    primary: &mut Provider,
-    witnesses: &mut [Provider],
-    primary_trace: Vec<LightBlock>,
-    max_clock_drift: Duration,
-    max_block_lag: Duration,
-    now: Time,
-) -> Result<(), Report> {
-    if witnesses.is_empty() {
-        return Err(Error::no_witnesses().into());
-    }
-
     info!(
-        "Running misbehavior detection against {} witnesses...",
-        witnesses.len()
+        "Verified block at height {} ({fork_detection})",
+        primary_block.height()
     );
 
-    let primary_trace = Trace::new(primary_trace)?;
+    drop(block_cache_scope);
+    debug!("block cache hits: {}", block_cache.hits());
 
-    let last_verified_block = primary_trace.last();
-    let last_verified_header = &last_verified_block.signed_header;
-
-    for witness in witnesses {
-        let divergence = detect_divergence::<Sha256>(
-            Some(primary),
-            witness,
-            primary_trace.clone().into_vec(),
-            max_clock_drift,
-            max_block_lag,
-        )
-        .await;
-
-        let evidence = match divergence {
-            Ok(Some(divergence)) => divergence.evidence,
-            Ok(None) => {
-                info!(
-                    "no divergence found between primary and witness {}",
-                    witness.peer_id()
-                );
-
-                continue;
-            },
-            Err(e) => {
-                error!(
-                    "failed to run attack detector against witness {}: {e}",
-                    witness.peer_id()
-                );
-
-                continue;
-            },
-        };
-
-        // Report the evidence to the witness
-        witness
-            .report_evidence(Evidence::from(evidence.against_primary))
-            .await
-            .map_err(|e| eyre!("failed to report evidence to witness: {}", e))?;
-
-        if let Some(against_witness) = evidence.against_witness {
-            // Report the evidence to the primary
-            primary
-                .report_evidence(Evidence::from(against_witness))
-                .await
-                .map_err(|e| eyre!("failed to report evidence to primary: {}", e))?;
-        }
+    for (peer, latency) in primary.witness_latencies() {
+        debug!(peer = %Labeled(primary.peer_labeler(), &peer), "latency: {latency:?}");
     }
 
     Ok(())
@@ -265,6 +256,8 @@ async fn make_provider(
     trusted_height: Height,
     trusted_hash: Hash,
     options: Options,
+    latency: LatencyStats,
+    block_cache: Arc<BlockCache>,
 ) -> Result<Provider> {
     use tendermint_rpc::client::CompatMode;
 
@@ -275,10 +268,17 @@ async fn make_provider(
     let node_id = rpc_client.status().await?.node_info.id;
     let light_store = Box::new(MemoryStore::new());
 
-    let instance =
-        LightClientBuilder::prod(node_id, rpc_client.clone(), light_store, options, None)
-            .trust_primary_at(trusted_height, trusted_hash)?
-            .build();
+    let instance = LightClientBuilder::prod_with_latency_stats(
+        node_id,
+        rpc_client.clone(),
+        light_store,
+        options,
+        None,
+        latency,
+    )
+    .block_cache(block_cache)
+    .trust_primary_at(trusted_height, trusted_hash)?
+    .build();
 
     Ok(Provider::new(chain_id.to_string(), instance, rpc_client))
 }
@@ -0,0 +1,135 @@
+//! Weighing the two branches of a fork against each other.
+
+use tendermint_light_client::{
+    errors::Error as LightClientError,
+    peer_labeler::PeerLabeler,
+    verifier::{
+        operations::{ProdVotingPowerCalculator, VotingPowerCalculator, VotingPowerTally},
+        types::{LightBlock, TrustThreshold},
+    },
+};
+
+use crate::error::Error;
+
+/// One of the two branches of a fork.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Branch {
+    /// The branch of the verified block, ie. the primary's.
+    Trusted,
+    /// The branch of the conflicting block returned by the witness.
+    Conflicting,
+}
+
+/// The voting power which signed the conflicting blocks at the tip of each branch of a fork,
+/// out of the validator set of the last block common to both branches.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BranchWeights {
+    /// Voting power of the common validators which signed the verified block.
+    pub trusted: VotingPowerTally,
+    /// Voting power of the common validators which signed the conflicting block.
+    pub conflicting: VotingPowerTally,
+}
+
+impl BranchWeights {
+    /// Tally the voting power which signed each of the two conflicting blocks.
+    ///
+    /// Both commits are checked against the validator set of the `common` block, so that
+    /// a branch cannot outweigh the other by inflating the voting power of its own
+    /// validators. The signatures of validators outside of that set are not counted,
+    /// and the whole set is tallied rather than stopping once a threshold is met. A block
+    /// whose commit cannot be tallied is reported with its provider, labeled by `labeler`.
+    pub fn tally(
+        labeler: &dyn PeerLabeler,
+        common: &LightBlock,
+        trusted: &LightBlock,
+        conflicting: &LightBlock,
+    ) -> Result<Self, Error> {
+        let calculator = ProdVotingPowerCalculator::default();
+
+        // No voting power is ever strictly greater than the whole of it,
+        // so this threshold has the calculator tally every signature.
+        let whole = TrustThreshold::new(1, 1).expect("1/1 is a valid trust threshold");
+
+        let tally = |block: &LightBlock| {
+            calculator
+                .voting_power_in(&block.signed_header, &common.validators, whole)
+                .map_err(|e| {
+                    Error::light_client(LightClientError::invalid_light_block(
+                        block.provider,
+                        labeler.label(&block.provider),
+                        e.into_detail(),
+                    ))
+                })
+        };
+
+        Ok(Self {
+            trusted: tally(trusted)?,
+            conflicting: tally(conflicting)?,
+        })
+    }
+
+    /// The branch whose block was signed by the most voting power.
+    ///
+    /// Branches are compared on the voting power of the common validators which signed
+    /// their block. Ties go to the trusted branch.
+    pub fn heavier(&self) -> Branch {
+        if self.conflicting.tallied > self.trusted.tallied {
+            Branch::Conflicting
+        } else {
+            Branch::Trusted
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint_testgen::{
+        light_block::TmLightBlock, Generator, LightBlock as TestgenLightBlock, LightChain,
+        Validator,
+    };
+
+    use tendermint_light_client::peer_labeler::DefaultPeerLabeler;
+
+    use super::*;
+
+    fn testgen_to_lb(tm_lb: TmLightBlock) -> LightBlock {
+        LightBlock {
+            signed_header: tm_lb.signed_header,
+            validators: tm_lb.validators,
+            next_validators: tm_lb.next_validators,
+            provider: tm_lb.provider,
+        }
+    }
+
+    #[test]
+    fn inflated_validators_do_not_outweigh_the_common_ones() {
+        let chain = LightChain::default_with_length(2).light_blocks;
+
+        // Sign a conflicting block with one of the common validators,
+        // along with new ones, all claiming a huge voting power
+        let inflated = [
+            Validator::new("1").voting_power(1000),
+            Validator::new("3").voting_power(1000),
+            Validator::new("4").voting_power(1000),
+        ];
+        let header = chain[0]
+            .header
+            .as_ref()
+            .unwrap()
+            .next()
+            .validators(&inflated)
+            .next_validators(&inflated);
+        let conflicting = TestgenLightBlock::new_default_with_header(header);
+
+        let [common, trusted, conflicting] =
+            [&chain[0], &chain[1], &conflicting].map(|lb| testgen_to_lb(lb.generate().unwrap()));
+
+        let weights =
+            BranchWeights::tally(&DefaultPeerLabeler, &common, &trusted, &conflicting).unwrap();
+
+        assert_eq!(weights.trusted.total, 100);
+        assert_eq!(weights.trusted.tallied, 100);
+        assert_eq!(weights.conflicting.tallied, 50);
+        assert_eq!(weights.heavier(), Branch::Trusted);
+    }
+}
@@ -3,8 +3,12 @@ use tendermint_light_client::verifier::types::LightBlock;
 use tracing::{error, error_span, warn};
 
 use super::{
-    error::Error, evidence::make_evidence, examine::examine_conflicting_header_against_trace,
-    provider::Provider, trace::Trace,
+    error::Error,
+    eviction::{evict, EvictionReason},
+    evidence::make_evidence,
+    examine::examine_conflicting_header_against_trace,
+    provider::Provider,
+    trace::Trace,
 };
 
 #[derive(Clone, Debug)]
@@ -26,17 +30,32 @@ pub async fn gather_evidence_from_conflicting_headers<H>(
     primary_trace: &Trace,
     challenging_block: &LightBlock,
 ) -> Result<GatheredEvidence, Error>
+where
+    H: Sha256 + MerkleHash + Default,
+{
+    gather_evidence::<H>(primary, witness, primary_trace, challenging_block)
+}
+
+/// Blocking version of [`gather_evidence_from_conflicting_headers`], which does not need
+/// an async context and can thus be run on its own thread.
+pub(crate) fn gather_evidence<H>(
+    primary: Option<&Provider>,
+    witness: &Provider,
+    primary_trace: &Trace,
+    challenging_block: &LightBlock,
+) -> Result<GatheredEvidence, Error>
 where
     H: Sha256 + MerkleHash + Default,
 {
     let _span =
-        error_span!("gather_evidence_from_conflicting_headers", witness = %witness.peer_id())
+        error_span!("gather_evidence_from_conflicting_headers", witness = %witness.labeled())
             .entered();
 
     let (witness_trace, primary_block) =
         examine_conflicting_header_against_trace::<H>(primary_trace, challenging_block, witness)
             .map_err(|e| {
                 error!("Error validating witness's divergent header: {e}");
+                evict(witness, EvictionReason::Fork);
                 e
             })?;
 
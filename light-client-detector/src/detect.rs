@@ -1,18 +1,26 @@
-use std::{thread, time::Duration};
+use std::{fmt, sync::Arc, thread, time::Duration};
 
-use tracing::{debug, warn};
+use futures::{channel::mpsc, StreamExt};
 
-use tendermint::{block::signed_header::SignedHeader, crypto::Sha256, merkle::MerkleHash};
+use tracing::{debug, error, info, warn};
+
+use tendermint::{
+    block::signed_header::SignedHeader, crypto::Sha256, evidence::Evidence, merkle::MerkleHash,
+};
+use tendermint_light_client::errors::ErrorDetail;
 use tendermint_light_client::light_client::TargetOrLatest;
 use tendermint_light_client::verifier::errors::ErrorExt;
+use tendermint_light_client::verifier::options::Options;
 use tendermint_light_client::verifier::types::LightBlock;
 
-use crate::conflict::GatheredEvidence;
-
-use super::{
-    error::Error, gather_evidence_from_conflicting_headers, provider::Provider, trace::Trace,
+use crate::{
+    branch::BranchWeights,
+    conflict::{gather_evidence, GatheredEvidence},
+    eviction::{evict, EvictionReason},
 };
 
+use super::{error::Error, provider::Provider, trace::Trace};
+
 /// A divergence between the primary and a witness that has been detected in [`detect_divergence`].
 #[derive(Clone, Debug)]
 pub struct Divergence {
@@ -20,6 +28,9 @@ pub struct Divergence {
     pub evidence: GatheredEvidence,
     /// The conflicting light block that was returned by the witness
     pub challenging_block: LightBlock,
+    /// The voting power behind the verified block and behind the conflicting one,
+    /// see [`BranchWeights::heavier`] to select the canonical branch.
+    pub branch_weights: BranchWeights,
 }
 
 /// Given a primary trace and a witness, detect any divergence between the two,
@@ -41,6 +52,27 @@ pub async fn detect_divergence<H>(
     max_clock_drift: Duration,
     max_block_lag: Duration,
 ) -> Result<Option<Divergence>, Error>
+where
+    H: Sha256 + MerkleHash + Default,
+{
+    find_divergence::<H>(
+        primary,
+        witness,
+        primary_trace,
+        max_clock_drift,
+        max_block_lag,
+    )
+}
+
+/// Blocking version of [`detect_divergence`], which does not need an async context
+/// and can thus be run on its own thread, see [`detect_forks`].
+fn find_divergence<H>(
+    primary: Option<&Provider>,
+    witness: &mut Provider,
+    primary_trace: Vec<LightBlock>,
+    max_clock_drift: Duration,
+    max_block_lag: Duration,
+) -> Result<Option<Divergence>, Error>
 where
     H: Sha256 + MerkleHash + Default,
 {
@@ -75,42 +107,322 @@ where
         // which captures the bifurcation point and if successful provides the information to create valid evidence.
         Err(CompareError::ConflictingHeaders(challenging_block)) => {
             warn!(
-                witness = %witness.peer_id(),
+                witness = %witness.labeled(),
                 height  = %challenging_block.height(),
                 "Found conflicting headers between primary and witness"
             );
 
             // Gather the evidence to report from the conflicting headers
-            let evidence = gather_evidence_from_conflicting_headers::<H>(
-                primary,
-                witness,
-                &primary_trace,
+            let evidence =
+                gather_evidence::<H>(primary, witness, &primary_trace, &challenging_block)?;
+
+            let branch_weights = BranchWeights::tally(
+                witness.peer_labeler(),
+                evidence.witness_trace.first(),
+                last_verified_block,
                 &challenging_block,
-            )
-            .await?;
+            )?;
+
+            warn!(
+                trusted_power = branch_weights.trusted.tallied,
+                conflicting_power = branch_weights.conflicting.tallied,
+                heavier = ?branch_weights.heavier(),
+                "Weighed both branches of the fork"
+            );
 
             Ok(Some(Divergence {
                 evidence,
                 challenging_block: *challenging_block,
+                branch_weights,
             }))
         },
 
-        Err(CompareError::BadWitness) => {
+        Err(CompareError::BadWitness(reason)) => {
             // These are all melevolent errors and should result in removing the witness
-            debug!(witness = %witness.peer_id(), "witness returned an error during header comparison, removing...");
+            debug!(witness = %witness.labeled(), "witness returned an error during header comparison, removing...");
+            evict(witness, reason);
 
             Err(Error::bad_witness())
         },
 
         Err(CompareError::Other(e)) => {
             // Benign errors which can be ignored
-            debug!(witness = %witness.peer_id(), "error in light block request to witness: {e}");
+            debug!(witness = %witness.labeled(), "error in light block request to witness: {e}");
 
             Err(Error::light_client(e))
         },
     }
 }
 
+/// Whether fork detection was performed on a verified block, as reported by [`detect_forks`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ForkDetection {
+    /// The primary trace was checked against the witnesses.
+    Performed,
+    /// No witness was available and [`Options::allow_primary_only`] is set,
+    /// so the block was verified against the primary alone.
+    ///
+    /// [`Options::allow_primary_only`]: tendermint_light_client::verifier::options::Options::allow_primary_only
+    Skipped,
+}
+
+impl fmt::Display for ForkDetection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Performed => write!(f, "fork detection performed"),
+            Self::Skipped => write!(f, "fork detection SKIPPED"),
+        }
+    }
+}
+
+/// Check the given primary trace against every witness with [`detect_divergence`],
+/// then decide whether the witnesses disagreeing with the primary amount to a fork
+/// with [`check_disagreeing_witnesses`]. Only once a fork is declared is the evidence
+/// of the attack reported to the primary and to the disagreeing witnesses. A report
+/// which fails does not stop the others, and its error is recorded in the fork error.
+///
+/// The witnesses are all checked at once, each on its own thread, and detection completes
+/// as soon as [`Options::fork_detection_quorum`] of them have responded. The witnesses
+/// which are still being checked by then are evicted, with [`EvictionReason::Timeout`],
+/// and dropped from `witnesses` along with the other evicted ones, while the others are
+/// given back. The block cache of the primary is kept
+/// for the whole run, see [`Provider::block_cache`].
+///
+/// Without any witness, this fails with [`Error::no_witnesses`], unless
+/// [`Options::allow_primary_only`] is set, in which case fork detection is skipped
+/// and [`ForkDetection::Skipped`] is returned, so that the caller knows the verified
+/// block is only as trustworthy as the primary itself.
+///
+/// [`Options::fork_detection_quorum`]: tendermint_light_client::verifier::options::Options::fork_detection_quorum
+/// [`Options::allow_primary_only`]: tendermint_light_client::verifier::options::Options::allow_primary_only
+pub async fn detect_forks<H>(
+    primary: &Arc<Provider>,
+    witnesses: &mut Vec<Provider>,
+    primary_trace: Vec<LightBlock>,
+    max_clock_drift: Duration,
+    max_block_lag: Duration,
+    options: &Options,
+) -> Result<ForkDetection, Error>
+where
+    H: Sha256 + MerkleHash + Default + 'static,
+{
+    if witnesses.is_empty() {
+        if !options.allow_primary_only {
+            return Err(Error::no_witnesses());
+        }
+
+        warn!(
+            "No witnesses available, SKIPPING FORK DETECTION: \
+             the verified block is only as trustworthy as the primary itself"
+        );
+
+        return Ok(ForkDetection::Skipped);
+    }
+
+    info!(
+        "Running misbehavior detection against {} witnesses...",
+        witnesses.len()
+    );
+
+    // Keep the blocks fetched by any provider in their block cache for the whole run,
+    // so that those sharing it reuse each other's validator sets
+    let _scope = primary.block_cache().scope();
+
+    let needed = options.fork_detection_quorum.min(witnesses.len());
+    let (sender, mut receiver) = mpsc::unbounded();
+
+    // Keep what is needed to evict the witnesses still being checked once the quorum responded
+    let mut running: Vec<_> = witnesses
+        .iter()
+        .map(|witness| Some(witness.evictee()))
+        .collect();
+
+    for (index, mut witness) in witnesses.drain(..).enumerate() {
+        let primary = Arc::clone(primary);
+        let primary_trace = primary_trace.clone();
+        let sender = sender.clone();
+
+        thread::spawn(move || {
+            let divergence = find_divergence::<H>(
+                Some(&primary),
+                &mut witness,
+                primary_trace,
+                max_clock_drift,
+                max_block_lag,
+            );
+
+            // Nobody is listening anymore if the quorum already responded
+            let _ = sender.unbounded_send((index, witness, divergence));
+        });
+    }
+
+    drop(sender);
+
+    // Wait for the quorum to respond, whether they agree with the primary or not,
+    // then also take the witnesses which finished in the meantime
+    let mut results = Vec::with_capacity(running.len());
+    let mut responded = 0;
+
+    while responded < needed {
+        let Some((index, witness, divergence)) = receiver.next().await else {
+            break;
+        };
+
+        if divergence.is_ok() {
+            responded += 1;
+        }
+
+        running[index] = None;
+        results.push((witness, divergence));
+    }
+
+    while let Ok((index, witness, divergence)) = receiver.try_recv() {
+        running[index] = None;
+        results.push((witness, divergence));
+    }
+
+    let responded = results
+        .iter()
+        .filter(|(_, divergence)| divergence.is_ok())
+        .count();
+
+    // The witnesses still being checked did not respond in time
+    for evictee in running.into_iter().flatten() {
+        evictee.evict(EvictionReason::Timeout);
+    }
+
+    if responded < needed {
+        let results = results.into_iter().map(|(witness, _)| witness);
+        witnesses.extend(results.filter(|witness| !witness.is_evicted()));
+        return Err(Error::insufficient_witness_responses(responded, needed));
+    }
+
+    let mut disagreeing = Vec::new();
+
+    for (witness, divergence) in results {
+        match divergence {
+            Ok(Some(divergence)) => {
+                warn!(
+                    "fork detected with witness {}, heavier branch: {:?}",
+                    witness.labeled(),
+                    divergence.branch_weights.heavier()
+                );
+
+                disagreeing.push((witness, divergence));
+            },
+            Ok(None) => {
+                info!(
+                    "no divergence found between primary and witness {}",
+                    witness.labeled()
+                );
+
+                witnesses.push(witness);
+            },
+            Err(e) => {
+                error!(
+                    "failed to run attack detector against witness {}: {e}",
+                    witness.labeled()
+                );
+
+                // Drop the witness if it was evicted
+                if !witness.is_evicted() {
+                    witnesses.push(witness);
+                }
+            },
+        }
+    }
+
+    // Decide whether the disagreeing witnesses amount to a fork before reporting
+    // anything, as those which are tolerated are faulty, and evicted and dropped instead
+    let faulty: Vec<_> = disagreeing
+        .iter()
+        .map(|(witness, divergence)| (witness, divergence.branch_weights))
+        .collect();
+    if check_disagreeing_witnesses(&faulty, options.max_faulty_witnesses).is_ok() {
+        return Ok(ForkDetection::Performed);
+    }
+
+    // Report the evidence to every disagreeing witness, and to the primary, even if some
+    // of the reports fail, and record the failures in the fork error
+    let mut report_errors = Vec::new();
+
+    for (witness, Divergence { evidence, .. }) in &disagreeing {
+        // Report the evidence to the witness
+        if let Err(e) = witness
+            .report_evidence(Evidence::from(evidence.against_primary.clone()))
+            .await
+        {
+            error!(
+                "failed to report evidence to witness {}: {e}",
+                witness.labeled()
+            );
+            report_errors.push(Error::report_evidence(*witness.peer_id(), e));
+        }
+
+        if let Some(against_witness) = &evidence.against_witness {
+            // Report the evidence to the primary
+            if let Err(e) = primary
+                .report_evidence(Evidence::from(against_witness.clone()))
+                .await
+            {
+                error!(
+                    "failed to report evidence to primary {}: {e}",
+                    primary.labeled()
+                );
+                report_errors.push(Error::report_evidence(*primary.peer_id(), e));
+            }
+        }
+    }
+
+    let fork = fork_detected(&faulty, report_errors);
+    witnesses.extend(disagreeing.into_iter().map(|(witness, _)| witness));
+
+    Err(fork)
+}
+
+/// Given the witnesses which were found to disagree with the primary by [`detect_divergence`],
+/// decide whether they amount to a fork, see [`Options::max_faulty_witnesses`].
+///
+/// Each witness comes with the weights of the two branches of its fork, see
+/// [`Divergence::branch_weights`].
+///
+/// If more than `max_faulty_witnesses` witnesses disagree with the primary, this fails with
+/// a `ForkDetected` error listing them, along with the weights of their branches to select
+/// the canonical one. Otherwise, each of them is evicted as faulty, with
+/// [`EvictionReason::Fork`], and the primary is trusted.
+///
+/// [`Options::max_faulty_witnesses`]: tendermint_light_client::verifier::options::Options::max_faulty_witnesses
+pub fn check_disagreeing_witnesses(
+    disagreeing: &[(&Provider, BranchWeights)],
+    max_faulty_witnesses: usize,
+) -> Result<(), Error> {
+    if disagreeing.len() > max_faulty_witnesses {
+        return Err(fork_detected(disagreeing, Vec::new()));
+    }
+
+    for (witness, _) in disagreeing {
+        evict(witness, EvictionReason::Fork);
+    }
+
+    Ok(())
+}
+
+/// The `ForkDetected` error for the given disagreeing witnesses, along with the errors
+/// of the evidence reports which failed, if any.
+fn fork_detected(disagreeing: &[(&Provider, BranchWeights)], report_errors: Vec<Error>) -> Error {
+    let peers = disagreeing
+        .iter()
+        .map(|(witness, _)| *witness.peer_id())
+        .collect();
+    let labels = disagreeing
+        .iter()
+        .map(|(witness, _)| witness.labeled().to_string())
+        .collect();
+    let branch_weights = disagreeing.iter().map(|(_, weights)| *weights).collect();
+
+    Error::fork_detected(peers, labels, branch_weights, report_errors)
+}
+
 /// An error that arised when comparing a header from the primary with a header from a witness
 /// with [`compare_new_header_with_witness`].
 #[derive(Debug)]
@@ -118,7 +430,7 @@ pub enum CompareError {
     /// There may have been an attack on this light client
     ConflictingHeaders(Box<LightBlock>),
     /// The witness has either not responded, doesn't have the header or has given us an invalid one
-    BadWitness,
+    BadWitness(EvictionReason),
     /// Some other error has occurred, this is likely a benign error
     Other(tendermint_light_client::errors::Error),
 }
@@ -155,7 +467,7 @@ fn check_against_witness(
     max_block_lag: Duration,
 ) -> Result<LightBlock, CompareError> {
     let _span =
-        tracing::debug_span!("check_against_witness", witness = %witness.peer_id()).entered();
+        tracing::debug_span!("check_against_witness", witness = %witness.labeled()).entered();
 
     let light_block = witness.fetch_light_block(sh.header.height);
 
@@ -168,7 +480,16 @@ fn check_against_witness(
         Err(e) if e.detail().is_io() => {
             debug!("The witness hasn't been helpful in comparing headers");
 
-            Err(CompareError::BadWitness)
+            let timed_out = match e.detail() {
+                ErrorDetail::Io(e) => e.source.is_timeout().is_some(),
+                _ => false,
+            };
+
+            if timed_out {
+                Err(CompareError::BadWitness(EvictionReason::Timeout))
+            } else {
+                Err(CompareError::BadWitness(EvictionReason::RepeatedFailure))
+            }
         },
 
         // The witness' head of the blockchain is lower than the height of the primary.
@@ -180,7 +501,7 @@ fn check_against_witness(
 
             let light_block = witness
                 .get_target_block_or_latest(sh.header.height)
-                .map_err(|_| CompareError::BadWitness)?;
+                .map_err(|_| CompareError::BadWitness(EvictionReason::RepeatedFailure))?;
 
             let light_block = match light_block {
                 // If the witness caught up and has returned a block of the target height then we can
@@ -210,7 +531,7 @@ fn check_against_witness(
 
             let light_block = witness
                 .get_target_block_or_latest(sh.header.height)
-                .map_err(|_| CompareError::BadWitness)?;
+                .map_err(|_| CompareError::BadWitness(EvictionReason::RepeatedFailure))?;
 
             let light_block = match light_block {
                 // If the witness caught up and has returned a block of the target height then we can
@@ -234,9 +555,146 @@ fn check_against_witness(
             // NOTE: If the clock drift / lag has been miscalibrated it is feasible that the light client has
             // drifted too far ahead for any witness to be able provide a comparable block and thus may allow
             // for a malicious primary to attack it
-            Err(CompareError::BadWitness)
+            Err(CompareError::BadWitness(EvictionReason::RepeatedFailure))
         },
 
         Err(other) => Err(CompareError::Other(other)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tendermint::crypto::default::Sha256 as DefaultSha256;
+    use tendermint_light_client::verifier::{
+        operations::VotingPowerTally,
+        types::{PeerId, TrustThreshold},
+    };
+
+    use super::*;
+    use crate::{
+        branch::Branch,
+        eviction::EvictionListener,
+        tests::{block_on, light_blocks, provider, slow_provider},
+    };
+
+    #[test]
+    fn fork_detection_completes_once_the_quorum_responded() {
+        let blocks = light_blocks();
+        let primary = Arc::new(provider(
+            "0000000000000000000000000000000000000000",
+            blocks.clone(),
+        ));
+        let mut witnesses = (1..=3)
+            .map(|i| provider(&format!("{i:040}"), blocks.clone()))
+            .collect();
+
+        let options = Options {
+            fork_detection_quorum: 2,
+            ..Options::new(
+                TrustThreshold::TWO_THIRDS,
+                Duration::from_secs(60),
+                Duration::from_secs(1),
+            )
+        };
+
+        let detection = block_on(detect_forks::<DefaultSha256>(
+            &primary,
+            &mut witnesses,
+            blocks,
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            &options,
+        ));
+
+        assert_eq!(detection.unwrap(), ForkDetection::Performed);
+        assert!((2..=3).contains(&witnesses.len()));
+    }
+
+    #[test]
+    fn tolerated_disagreeing_witnesses_are_evicted() {
+        let blocks = light_blocks();
+        let witnesses: Vec<_> = (1..=2)
+            .map(|i| provider(&format!("{i:040}"), blocks.clone()))
+            .collect();
+
+        let weights = |trusted, conflicting| {
+            let tally = |tallied| VotingPowerTally {
+                total: 100,
+                tallied,
+                trust_threshold: TrustThreshold::TWO_THIRDS,
+            };
+
+            BranchWeights {
+                trusted: tally(trusted),
+                conflicting: tally(conflicting),
+            }
+        };
+        let disagreeing = [
+            (&witnesses[0], weights(70, 30)),
+            (&witnesses[1], weights(30, 70)),
+        ];
+
+        let fork = check_disagreeing_witnesses(&disagreeing, 1).unwrap_err();
+        match fork.detail() {
+            crate::ErrorDetail::ForkDetected(e) => {
+                assert_eq!(e.peers, [*witnesses[0].peer_id(), *witnesses[1].peer_id()]);
+                let heavier: Vec<_> = e.branch_weights.iter().map(|w| w.heavier()).collect();
+                assert_eq!(heavier, [Branch::Trusted, Branch::Conflicting]);
+            },
+            e => panic!("expected a fork, got {e:?}"),
+        }
+        assert!(witnesses.iter().all(|witness| !witness.is_evicted()));
+
+        check_disagreeing_witnesses(&disagreeing, 2).unwrap();
+        assert!(witnesses.iter().all(|witness| witness.is_evicted()));
+    }
+
+    #[test]
+    fn witnesses_still_running_once_the_quorum_responded_are_evicted() {
+        let blocks = light_blocks();
+        let primary = Arc::new(provider(
+            "0000000000000000000000000000000000000000",
+            blocks.clone(),
+        ));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let listener: Arc<dyn EvictionListener> =
+            Arc::new(move |peer: &PeerId, reason| sender.send((*peer, reason)).unwrap());
+        let slow = slow_provider(
+            "0000000000000000000000000000000000000002",
+            blocks.clone(),
+            Duration::from_secs(1),
+        )
+        .with_eviction_listener(listener);
+        let slow_peer = *slow.peer_id();
+        let mut witnesses = vec![
+            provider("0000000000000000000000000000000000000001", blocks.clone()),
+            slow,
+        ];
+
+        let options = Options {
+            fork_detection_quorum: 1,
+            ..Options::new(
+                TrustThreshold::TWO_THIRDS,
+                Duration::from_secs(60),
+                Duration::from_secs(1),
+            )
+        };
+
+        let detection = block_on(detect_forks::<DefaultSha256>(
+            &primary,
+            &mut witnesses,
+            blocks,
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            &options,
+        ));
+
+        assert_eq!(detection.unwrap(), ForkDetection::Performed);
+        assert_eq!(witnesses.len(), 1);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            (slow_peer, EvictionReason::Timeout)
+        );
+    }
+}
@@ -1,9 +1,10 @@
 use tendermint::{block::Height, Hash, Time};
 use tendermint_light_client::components::io::IoError;
 use tendermint_light_client::errors::Error as LightClientError;
-use tendermint_light_client::verifier::types::LightBlock;
+use tendermint_light_client::verifier::types::{LightBlock, PeerId};
+use tendermint_rpc::Error as RpcError;
 
-use crate::conflict::GatheredEvidence;
+use crate::branch::BranchWeights;
 
 flex_error::define_error! {
     /// Error type for the light client detector. See [`ErrorDetail`] for all the possible error variants.
@@ -22,12 +23,27 @@ flex_error::define_error! {
         NoDivergence
             |_| { "expected divergence between conflicting headers but none found" },
 
-        Divergence
+        ForkDetected
             {
-                evidence: GatheredEvidence,
-                challenging_block: LightBlock,
+                peers: Vec<PeerId>,
+                labels: Vec<String>,
+                branch_weights: Vec<BranchWeights>,
+                report_errors: Vec<Error>,
             }
-            |e| { format_args!("divergence detected, found evidence: {:#?}", e.evidence) },
+            |e| {
+                format_args!("fork detected peers=[{}], heavier branches=[{}], failed reports=[{}]",
+                    e.labels.join(", "),
+                    e.branch_weights
+                        .iter()
+                        .map(|weights| format!("{:?}", weights.heavier()))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    e.report_errors
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+            },
 
         NoWitnesses
             |_| { "no witnesses provided" },
@@ -35,6 +51,23 @@ flex_error::define_error! {
         BadWitness
             |_| { "bad witness" },
 
+        InsufficientWitnessResponses
+            {
+                got: usize,
+                needed: usize,
+            }
+            |e| {
+                format_args!(
+                    "only {} witnesses responded, fork detection needs {}",
+                    e.got, e.needed
+                )
+            },
+
+        ReportEvidence
+            { peer: PeerId }
+            [ RpcError ]
+            |e| { format_args!("failed to report evidence to peer {}", e.peer) },
+
         TargetBlockLowerThanTrusted
             {
                 target_height: Height,
@@ -0,0 +1,115 @@
+//! Notifications of the witnesses evicted by the detector.
+//!
+//! A witness which cannot be compared against the primary is dropped by
+//! [`detect_divergence`], which fails with [`Error::bad_witness`], and so is a witness
+//! disagreeing with the primary when [`check_disagreeing_witnesses`] tolerates it as
+//! faulty. [`detect_forks`] also evicts the witnesses which have not responded yet once
+//! its quorum did, and then drops the evicted witnesses from its witness set.
+//! Operators can give each witness an [`EvictionListener`] with
+//! [`Provider::with_eviction_listener`] to be told at once, eg. to raise an alert before
+//! the witness set is exhausted.
+//!
+//! [`detect_divergence`]: crate::detect_divergence
+//! [`detect_forks`]: crate::detect_forks
+//! [`check_disagreeing_witnesses`]: crate::check_disagreeing_witnesses
+//! [`Error::bad_witness`]: crate::Error::bad_witness
+//! [`Provider::with_eviction_listener`]: crate::Provider::with_eviction_listener
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use derive_more::Display;
+use tendermint_light_client::verifier::types::PeerId;
+use tracing::warn;
+
+use crate::provider::Provider;
+
+/// Why a witness was evicted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display)]
+pub enum EvictionReason {
+    /// The witness did not answer in time.
+    #[display(fmt = "timeout")]
+    Timeout,
+    /// The witness served a header conflicting with the primary's, which it could not back up.
+    #[display(fmt = "fork")]
+    Fork,
+    /// The witness kept failing to serve the requested header, eg. as it lags too far behind.
+    #[display(fmt = "repeated failure")]
+    RepeatedFailure,
+}
+
+/// Notified of every witness evicted by the detector.
+pub trait EvictionListener: Send + Sync {
+    /// The given witness was evicted for the given reason.
+    fn witness_evicted(&self, witness: &PeerId, reason: EvictionReason);
+}
+
+impl<F> EvictionListener for F
+where
+    F: Fn(&PeerId, EvictionReason) + Send + Sync,
+{
+    fn witness_evicted(&self, witness: &PeerId, reason: EvictionReason) {
+        self(witness, reason)
+    }
+}
+
+/// Log the eviction of the given witness, and notify its listener, if any.
+pub(crate) fn evict(witness: &Provider, reason: EvictionReason) {
+    witness.evictee().evict(reason);
+}
+
+/// What it takes to evict a witness without holding on to it, eg. while it is being
+/// checked on another thread, see [`Provider::evictee`].
+pub(crate) struct Evictee {
+    pub(crate) peer_id: PeerId,
+    pub(crate) label: String,
+    pub(crate) listener: Option<Arc<dyn EvictionListener>>,
+    pub(crate) evicted: Arc<AtomicBool>,
+}
+
+impl Evictee {
+    /// Log the eviction of the witness, and notify its listener, if any.
+    pub(crate) fn evict(&self, reason: EvictionReason) {
+        warn!(witness = %self.label, %reason, "Evicting witness");
+        self.evicted.store(true, Ordering::Release);
+
+        if let Some(listener) = &self.listener {
+            listener.witness_evicted(&self.peer_id, reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::tests::{light_blocks, provider};
+
+    fn witness(peer_id: &str) -> Provider {
+        provider(peer_id, light_blocks())
+    }
+
+    #[test]
+    fn evicted_witnesses_notify_their_own_listener() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let listener = {
+            let evicted = evicted.clone();
+            move |witness: &PeerId, reason| evicted.lock().unwrap().push((*witness, reason))
+        };
+
+        let listened = witness("BADFADAD0BEFEEDC0C0ADEADBEEFC0FFEEFACADE")
+            .with_eviction_listener(Arc::new(listener));
+        let unlistened = witness("CEFEEDBADFADAD0C0CEEFACADE0ADEADBEEFC0FF");
+
+        evict(&listened, EvictionReason::Timeout);
+        evict(&unlistened, EvictionReason::Fork);
+
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            [(*listened.peer_id(), EvictionReason::Timeout)]
+        );
+    }
+}
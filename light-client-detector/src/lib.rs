@@ -1,18 +1,29 @@
 //! The detector component of the light client detects and handles attacks on the light client.
 //!
-//! See [`detect_divergence`] for the main entry point.
+//! See [`detect_forks`] for the main entry point, and [`detect_divergence`] to check
+//! a single witness.
 
+mod branch;
 mod conflict;
 mod detect;
 mod error;
+mod eviction;
 mod evidence;
 mod examine;
 mod provider;
 mod trace;
 
+#[cfg(test)]
+mod tests;
+
+pub use branch::{Branch, BranchWeights};
 pub use conflict::gather_evidence_from_conflicting_headers;
-pub use detect::{compare_new_header_with_witness, detect_divergence, CompareError, Divergence};
+pub use detect::{
+    check_disagreeing_witnesses, compare_new_header_with_witness, detect_divergence, detect_forks,
+    CompareError, Divergence, ForkDetection,
+};
 pub use error::{Error, ErrorDetail};
+pub use eviction::{EvictionListener, EvictionReason};
 pub use provider::Provider;
 pub use tendermint::evidence::{Evidence, LightClientAttackEvidence};
 pub use trace::Trace;
@@ -1,20 +1,46 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 use tendermint::block::Height;
 use tendermint::evidence::Evidence;
 use tendermint::hash::Hash;
+use tendermint_light_client::components::block_cache::BlockCache;
 use tendermint_light_client::errors::Error;
 use tendermint_light_client::instance::Instance;
 use tendermint_light_client::light_client::TargetOrLatest;
+use tendermint_light_client::peer_labeler::{Labeled, PeerLabeler};
 use tendermint_light_client::state::State;
 use tendermint_light_client::store::memory::MemoryStore;
-use tendermint_light_client::verifier::types::LightBlock;
+use tendermint_light_client::verifier::types::{LightBlock, PeerId};
 use tendermint_rpc::{Client, Error as RpcError, HttpClient};
 
+use crate::eviction::{Evictee, EvictionListener};
+
 /// A interface over a light client instance and its RPC client.
-#[derive(Debug)]
 pub struct Provider {
     chain_id: String,
     instance: Instance,
     rpc_client: HttpClient,
+    eviction_listener: Option<Arc<dyn EvictionListener>>,
+    evicted: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Provider")
+            .field("chain_id", &self.chain_id)
+            .field("instance", &self.instance)
+            .field("rpc_client", &self.rpc_client)
+            .field("evicted", &self.evicted)
+            .finish()
+    }
 }
 
 impl Provider {
@@ -23,6 +49,35 @@ impl Provider {
             chain_id,
             instance,
             rpc_client,
+            eviction_listener: None,
+            evicted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Notify the given listener when the detector evicts this provider as a witness.
+    ///
+    /// The same listener can be shared by all the witnesses.
+    pub fn with_eviction_listener(mut self, listener: Arc<dyn EvictionListener>) -> Self {
+        self.eviction_listener = Some(listener);
+        self
+    }
+
+    pub fn eviction_listener(&self) -> Option<&dyn EvictionListener> {
+        self.eviction_listener.as_deref()
+    }
+
+    /// Whether the detector evicted this provider as a witness, which should then be dropped.
+    pub fn is_evicted(&self) -> bool {
+        self.evicted.load(Ordering::Acquire)
+    }
+
+    /// What it takes to evict this provider as a witness, without holding on to it.
+    pub(crate) fn evictee(&self) -> Evictee {
+        Evictee {
+            peer_id: *self.peer_id(),
+            label: self.labeled().to_string(),
+            listener: self.eviction_listener.clone(),
+            evicted: Arc::clone(&self.evicted),
         }
     }
 
@@ -34,6 +89,28 @@ impl Provider {
         self.instance.peer_id()
     }
 
+    /// The labeler showing peers in the errors and log lines of this provider's light client.
+    pub fn peer_labeler(&self) -> &dyn PeerLabeler {
+        self.instance.light_client.peer_labeler()
+    }
+
+    /// Displays the peer of this provider with its labeler.
+    pub fn labeled(&self) -> Labeled<'_> {
+        Labeled(self.peer_labeler(), self.peer_id())
+    }
+
+    /// The block cache of this provider's light client, which may be shared with the
+    /// other providers, see [`LightClient::with_block_cache`].
+    ///
+    /// [`LightClient::with_block_cache`]: tendermint_light_client::light_client::LightClient::with_block_cache
+    pub fn block_cache(&self) -> &Arc<BlockCache> {
+        self.instance.light_client.block_cache()
+    }
+
+    pub fn witness_latencies(&self) -> BTreeMap<PeerId, Duration> {
+        self.instance.witness_latencies()
+    }
+
     pub async fn report_evidence(&self, evidence: Evidence) -> Result<Hash, RpcError> {
         self.rpc_client
             .broadcast_evidence(evidence)
@@ -70,6 +147,16 @@ impl Provider {
         self.instance.light_client.verify_to_target(height, state)
     }
 
+    pub fn verify_prefetched(
+        &mut self,
+        height: Height,
+        light_block: LightBlock,
+    ) -> Result<LightBlock, Error> {
+        self.instance
+            .light_client
+            .verify_prefetched(height, light_block, &mut self.instance.state)
+    }
+
     pub fn get_target_block_or_latest(&mut self, height: Height) -> Result<TargetOrLatest, Error> {
         self.instance
             .light_client
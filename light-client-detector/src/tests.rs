@@ -0,0 +1,108 @@
+//! Helpers shared by the unit tests of the detector.
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use tendermint_light_client::{
+    components::{io::Io, scheduler},
+    instance::Instance,
+    light_client::{LightClient, Options},
+    state::State,
+    store::memory::MemoryStore,
+    tests::{MockClock, MockIo},
+    verifier::{
+        types::{LightBlock, Time, TrustThreshold},
+        ProdVerifier,
+    },
+};
+use tendermint_rpc::HttpClient;
+use tendermint_testgen::{Generator, LightBlock as TestgenLightBlock};
+
+use crate::provider::Provider;
+
+/// The light blocks at heights 1 and 2 of a default testgen chain.
+pub(crate) fn light_blocks() -> Vec<LightBlock> {
+    let first = TestgenLightBlock::new_default(1);
+    let second = first.next();
+
+    [first, second]
+        .into_iter()
+        .map(|lb| {
+            let lb = lb.generate().unwrap();
+            LightBlock::new(
+                lb.signed_header,
+                lb.validators,
+                lb.next_validators,
+                lb.provider,
+            )
+        })
+        .collect()
+}
+
+/// A provider serving the given blocks as the given peer.
+pub(crate) fn provider(peer_id: &str, blocks: Vec<LightBlock>) -> Provider {
+    slow_provider(peer_id, blocks, Duration::ZERO)
+}
+
+/// A provider serving the given blocks as the given peer, each after the given delay.
+pub(crate) fn slow_provider(
+    peer_id: &str,
+    mut blocks: Vec<LightBlock>,
+    delay: Duration,
+) -> Provider {
+    let peer_id = peer_id.parse().unwrap();
+    for block in &mut blocks {
+        block.provider = peer_id;
+    }
+
+    let io = MockIo::new(blocks);
+    let light_client = LightClient::new(
+        peer_id,
+        Options::new(
+            TrustThreshold::TWO_THIRDS,
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+        ),
+        MockClock {
+            now: Time::unix_epoch(),
+        },
+        scheduler::basic_bisecting_schedule,
+        ProdVerifier::default(),
+        move |height| {
+            thread::sleep(delay);
+            io.fetch_light_block(height)
+        },
+    );
+    let instance = Instance::new(light_client, State::new(MemoryStore::new()));
+    let rpc_client = HttpClient::new("http://127.0.0.1:26657").unwrap();
+
+    Provider::new("test-chain".to_string(), instance, rpc_client)
+}
+
+/// Wakes the thread blocked in [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run the given future to completion on the current thread.
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
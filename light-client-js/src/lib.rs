@@ -95,10 +95,10 @@ pub struct JsOptions {
 impl From<JsOptions> for Options {
     fn from(o: JsOptions) -> Self {
         let (num, den) = o.trust_threshold;
-        Self {
-            trust_threshold: TrustThreshold::new(num, den).unwrap(),
-            trusting_period: Duration::from_secs(o.trusting_period),
-            clock_drift: Duration::from_secs(o.clock_drift),
-        }
+        Self::new(
+            TrustThreshold::new(num, den).unwrap(),
+            Duration::from_secs(o.trusting_period),
+            Duration::from_secs(o.clock_drift),
+        )
     }
 }
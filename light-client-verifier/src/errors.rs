@@ -19,6 +19,25 @@ define_error! {
             [ TendermintError ]
             | _ | { "tendermint error" },
 
+        TimeArithmetic
+            {
+                time: Time,
+                duration: Duration,
+            }
+            [ TendermintError ]
+            | e | {
+                format_args!("cannot subtract {0:?} from time {1}: out of range",
+                    e.duration, e.time)
+            },
+
+        InvalidOptions
+            {
+                reason: String,
+            }
+            | e | {
+                format_args!("invalid options: {0}", e.reason)
+            },
+
         HeaderFromTheFuture
             {
                 header_time: Time,
@@ -93,6 +112,16 @@ define_error! {
                     e.header_next_validators_hash, e.next_validators_hash)
             },
 
+        ValidatorCountMismatch
+            {
+                expected: usize,
+                got: usize,
+            }
+            | e | {
+                format_args!("validator count mismatch: the commit has {0} signatures but the validator set has {1} validators",
+                    e.expected, e.got)
+            },
+
         InvalidValidatorSet
             {
                 header_validators_hash: Hash,
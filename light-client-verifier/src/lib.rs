@@ -1,6 +1,8 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 mod prelude;
 
@@ -11,7 +13,7 @@ pub mod predicates;
 pub mod types;
 mod verifier;
 
-pub use verifier::{PredicateVerifier, Verdict, Verifier};
+pub use verifier::{bisection_midpoint, PredicateVerifier, Verdict, Verifier};
 
 #[cfg(feature = "rust-crypto")]
 pub use verifier::ProdVerifier;
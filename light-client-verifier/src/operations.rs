@@ -5,3 +5,8 @@ pub use self::voting_power::*;
 
 pub mod commit_validator;
 pub use self::commit_validator::*;
+
+#[cfg(feature = "rust-crypto")]
+pub mod verify_commit;
+#[cfg(feature = "rust-crypto")]
+pub use self::verify_commit::*;
@@ -0,0 +1,98 @@
+//! Provides a standalone function verifying a commit against a validator set
+
+use crate::{
+    errors::{VerificationError, VerificationErrorDetail},
+    operations::{
+        CommitValidator, ProdCommitValidator, ProdVotingPowerCalculator, VotingPowerCalculator,
+        VotingPowerTally,
+    },
+    predicates::{ProdPredicates, VerificationPredicates},
+    types::{SignedHeader, TrustThreshold, ValidatorSet},
+};
+
+/// Verify that the commit of `signed_header` is a valid commit of its header by
+/// `validators`, signed by more than `threshold` of their voting power.
+///
+/// This is the innermost check of light client verification, without any of the
+/// checks against a trusted state: trusting period, adjacency, clock drift, etc.
+/// It checks that:
+///
+/// - `validators` hashes to the header's `validators_hash`,
+/// - the commit is for the header, ie. its block id is the hash of the header,
+/// - the commit has one signature per validator, all from validators of the set,
+/// - the valid signatures add up to more than `threshold` of the total voting power.
+///
+/// On success, returns the tally of the voting power which signed the commit.
+/// As with [`VotingPowerCalculator::voting_power_in`], the tally may stop short of
+/// the actual voting power once the threshold is reached.
+pub fn verify_commit_against(
+    signed_header: &SignedHeader,
+    validators: &ValidatorSet,
+    threshold: TrustThreshold,
+) -> Result<VotingPowerTally, VerificationErrorDetail> {
+    verify(signed_header, validators, threshold).map_err(VerificationError::into_detail)
+}
+
+fn verify(
+    signed_header: &SignedHeader,
+    validators: &ValidatorSet,
+    threshold: TrustThreshold,
+) -> Result<VotingPowerTally, VerificationError> {
+    let predicates = ProdPredicates;
+    let commit_validator = ProdCommitValidator;
+
+    predicates.validator_sets_match(validators, signed_header.header.validators_hash)?;
+    predicates.header_matches_commit(&signed_header.header, signed_header.commit.block_id.hash)?;
+
+    commit_validator.validate(signed_header, validators)?;
+    commit_validator.validate_full(signed_header, validators)?;
+
+    let tally = ProdVotingPowerCalculator::default().voting_power_in(
+        signed_header,
+        validators,
+        threshold,
+    )?;
+    tally
+        .check()
+        .map_err(VerificationError::insufficient_signers_overlap)?;
+
+    Ok(tally)
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint_testgen::{Generator, LightBlock as TestgenLightBlock};
+
+    use super::*;
+    use crate::types::LightBlock;
+
+    #[test]
+    fn commit_is_verified_against_its_validators() {
+        let light_block: LightBlock = TestgenLightBlock::new_default(3).generate().unwrap().into();
+
+        let tally = verify_commit_against(
+            &light_block.signed_header,
+            &light_block.validators,
+            TrustThreshold::TWO_THIRDS,
+        )
+        .unwrap();
+        assert_eq!(
+            tally.total,
+            light_block.validators.total_voting_power().value()
+        );
+        assert!(tally.tallied * 3 > tally.total * 2);
+
+        let mut tampered = light_block.signed_header.clone();
+        tampered.commit.signatures.truncate(1);
+
+        let result = verify_commit_against(
+            &tampered,
+            &light_block.validators,
+            TrustThreshold::TWO_THIRDS,
+        );
+        assert!(matches!(
+            result,
+            Err(VerificationErrorDetail::MismatchPreCommitLength(_))
+        ));
+    }
+}
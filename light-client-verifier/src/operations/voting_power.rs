@@ -1,7 +1,11 @@
 //! Provides an interface and default implementation for the `VotingPower` operation
 
 use alloc::vec::Vec;
-use core::{fmt, marker::PhantomData};
+use core::{
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
 
 use serde::{Deserialize, Serialize};
 use tendermint::{
@@ -47,7 +51,7 @@ impl VotingPowerTally {
     }
 
     /// Checks whether tallied amount meets trust threshold.
-    fn check(&self) -> Result<(), Self> {
+    pub(crate) fn check(&self) -> Result<(), Self> {
         if self
             .trust_threshold
             .is_enough_power(self.tallied, self.total)
@@ -181,22 +185,199 @@ pub trait VotingPowerCalculator: Send + Sync {
         first_set: (&ValidatorSet, TrustThreshold),
         second_set: (&ValidatorSet, TrustThreshold),
     ) -> Result<(VotingPowerTally, VotingPowerTally), VerificationError>;
+
+    /// Same as [`Self::voting_power_in`], but the signatures may be verified
+    /// across the given number of threads.
+    ///
+    /// Implementations must return the same result as [`Self::voting_power_in`],
+    /// whatever the number of threads. The default implementation ignores it.
+    fn voting_power_in_with_threads(
+        &self,
+        signed_header: &SignedHeader,
+        validator_set: &ValidatorSet,
+        trust_threshold: TrustThreshold,
+        threads: usize,
+    ) -> Result<VotingPowerTally, VerificationError> {
+        let _ = threads;
+        self.voting_power_in(signed_header, validator_set, trust_threshold)
+    }
+
+    /// Same as [`Self::voting_power_in_sets`], but the signatures may be verified
+    /// across the given number of threads.
+    ///
+    /// Implementations must return the same result as [`Self::voting_power_in_sets`],
+    /// whatever the number of threads. The default implementation ignores it.
+    fn voting_power_in_sets_with_threads(
+        &self,
+        signed_header: &SignedHeader,
+        first_set: (&ValidatorSet, TrustThreshold),
+        second_set: (&ValidatorSet, TrustThreshold),
+        threads: usize,
+    ) -> Result<(VotingPowerTally, VotingPowerTally), VerificationError> {
+        let _ = threads;
+        self.voting_power_in_sets(signed_header, first_set, second_set)
+    }
+}
+
+/// Calculator which verifies signatures across a given number of threads,
+/// by calling the `*_with_threads` methods of the wrapped calculator.
+pub(crate) struct WithThreads<'a, C> {
+    pub(crate) calculator: &'a C,
+    pub(crate) threads: usize,
+}
+
+impl<C: VotingPowerCalculator> VotingPowerCalculator for WithThreads<'_, C> {
+    fn total_power_of(&self, validator_set: &ValidatorSet) -> u64 {
+        self.calculator.total_power_of(validator_set)
+    }
+
+    fn voting_power_in(
+        &self,
+        signed_header: &SignedHeader,
+        validator_set: &ValidatorSet,
+        trust_threshold: TrustThreshold,
+    ) -> Result<VotingPowerTally, VerificationError> {
+        self.calculator.voting_power_in_with_threads(
+            signed_header,
+            validator_set,
+            trust_threshold,
+            self.threads,
+        )
+    }
+
+    fn voting_power_in_sets(
+        &self,
+        signed_header: &SignedHeader,
+        first_set: (&ValidatorSet, TrustThreshold),
+        second_set: (&ValidatorSet, TrustThreshold),
+    ) -> Result<(VotingPowerTally, VotingPowerTally), VerificationError> {
+        self.calculator.voting_power_in_sets_with_threads(
+            signed_header,
+            first_set,
+            second_set,
+            self.threads,
+        )
+    }
+}
+
+/// Calculator which remembers the last tally computed with a ⅔ threshold,
+/// ie. the voting power of the untrusted validators which signed a header.
+pub(crate) struct RecordSigners<'a> {
+    calculator: &'a dyn VotingPowerCalculator,
+    recorded: AtomicBool,
+    tallied: AtomicU64,
+    total: AtomicU64,
+}
+
+impl<'a> RecordSigners<'a> {
+    pub(crate) fn new(calculator: &'a dyn VotingPowerCalculator) -> Self {
+        Self {
+            calculator,
+            recorded: AtomicBool::new(false),
+            tallied: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+        }
+    }
+
+    /// The last tally computed with a ⅔ threshold, if any.
+    pub(crate) fn tally(&self) -> Option<VotingPowerTally> {
+        self.recorded
+            .load(Ordering::Relaxed)
+            .then(|| VotingPowerTally {
+                total: self.total.load(Ordering::Relaxed),
+                tallied: self.tallied.load(Ordering::Relaxed),
+                trust_threshold: TrustThreshold::TWO_THIRDS,
+            })
+    }
+
+    fn record(&self, tally: &VotingPowerTally) {
+        if tally.trust_threshold == TrustThreshold::TWO_THIRDS {
+            self.total.store(tally.total, Ordering::Relaxed);
+            self.tallied.store(tally.tallied, Ordering::Relaxed);
+            self.recorded.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl VotingPowerCalculator for RecordSigners<'_> {
+    fn total_power_of(&self, validator_set: &ValidatorSet) -> u64 {
+        self.calculator.total_power_of(validator_set)
+    }
+
+    fn voting_power_in(
+        &self,
+        signed_header: &SignedHeader,
+        validator_set: &ValidatorSet,
+        trust_threshold: TrustThreshold,
+    ) -> Result<VotingPowerTally, VerificationError> {
+        let tally =
+            self.calculator
+                .voting_power_in(signed_header, validator_set, trust_threshold)?;
+        self.record(&tally);
+        Ok(tally)
+    }
+
+    fn voting_power_in_sets(
+        &self,
+        signed_header: &SignedHeader,
+        first_set: (&ValidatorSet, TrustThreshold),
+        second_set: (&ValidatorSet, TrustThreshold),
+    ) -> Result<(VotingPowerTally, VotingPowerTally), VerificationError> {
+        let (first, second) =
+            self.calculator
+                .voting_power_in_sets(signed_header, first_set, second_set)?;
+        self.record(&second);
+        Ok((first, second))
+    }
+}
+
+/// Computes the voting power held by a validator, for the purpose of tallying
+/// votes, see [`ProvidedVotingPowerCalculator::with_weight`].
+///
+/// This lets chains which weight voting power non-linearly, eg. quadratically,
+/// reuse the signature verification of the default calculator. The trust
+/// threshold is then checked against the sum of the weights of the signers,
+/// out of the sum of the weights of the whole validator set.
+pub trait PowerWeight: Send + Sync {
+    /// The voting power of the given validator.
+    fn weight(&self, validator: &validator::Info) -> u64;
+}
+
+/// Weights each validator with its voting power, as Tendermint does.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinearPower;
+
+impl PowerWeight for LinearPower {
+    fn weight(&self, validator: &validator::Info) -> u64 {
+        validator.power.value()
+    }
 }
 
 /// Default implementation of a `VotingPowerCalculator`, parameterized with
-/// the signature verification trait.
+/// the signature verification trait, and with the weight of each validator,
+/// which defaults to its voting power.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct ProvidedVotingPowerCalculator<V> {
+pub struct ProvidedVotingPowerCalculator<V, W = LinearPower> {
+    weight: W,
     _verifier: PhantomData<V>,
 }
 
-// Safety: the only member is phantom data
-unsafe impl<V> Send for ProvidedVotingPowerCalculator<V> {}
-unsafe impl<V> Sync for ProvidedVotingPowerCalculator<V> {}
+// Safety: the only other member is phantom data
+unsafe impl<V, W: Send> Send for ProvidedVotingPowerCalculator<V, W> {}
+unsafe impl<V, W: Sync> Sync for ProvidedVotingPowerCalculator<V, W> {}
 
-impl<V> Default for ProvidedVotingPowerCalculator<V> {
+impl<V, W: Default> Default for ProvidedVotingPowerCalculator<V, W> {
     fn default() -> Self {
+        Self::with_weight(W::default())
+    }
+}
+
+impl<V, W> ProvidedVotingPowerCalculator<V, W> {
+    /// A calculator tallying the given weight of each validator, rather than
+    /// its voting power.
+    pub fn with_weight(weight: W) -> Self {
         Self {
+            weight,
             _verifier: PhantomData,
         }
     }
@@ -358,6 +539,71 @@ impl NonAbsentCommitVotes {
         vote.verified = true;
         Ok(true)
     }
+
+    /// Verify, across the given number of threads, the signatures of the votes cast
+    /// by the given validators which have not been verified yet.
+    ///
+    /// Votes with a valid signature are flagged as verified. Those with an invalid
+    /// signature are left as is, so that [`Self::has_voted`] reports the error if and
+    /// when it gets to them, exactly as it would have without this call.
+    #[cfg(feature = "std")]
+    fn verify_in_parallel<V: signature::Verifier>(
+        &mut self,
+        validators: &[validator::Info],
+        threads: usize,
+    ) {
+        let pending: Vec<(usize, &validator::Info)> = validators
+            .iter()
+            .filter_map(|validator| {
+                self.votes
+                    .binary_search_by_key(&validator.address, NonAbsentCommitVote::validator_id)
+                    .ok()
+                    .filter(|idx| !self.votes[*idx].verified)
+                    .map(|idx| (idx, validator))
+            })
+            .collect();
+
+        if pending.is_empty() {
+            return;
+        }
+
+        let votes = &self.votes;
+        let chunk_size = pending.len().div_ceil(threads);
+
+        let verified: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = pending
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut sign_bytes = Vec::with_capacity(Self::SIGN_BYTES_INITIAL_CAPACITY);
+                        chunk
+                            .iter()
+                            .filter(|(idx, validator)| {
+                                let signed_vote = &votes[*idx].signed_vote;
+                                sign_bytes.truncate(0);
+                                signed_vote
+                                    .sign_bytes_into(&mut sign_bytes)
+                                    .expect("buffer is resized if needed and encoding never fails");
+                                validator
+                                    .verify_signature::<V>(&sign_bytes, signed_vote.signature())
+                                    .is_ok()
+                            })
+                            .map(|(idx, _)| *idx)
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("signature verification panicked"))
+                .collect()
+        });
+
+        for idx in verified {
+            self.votes[idx].verified = true;
+        }
+    }
 }
 
 /// Default implementation of a `VotingPowerCalculator`.
@@ -365,7 +611,16 @@ impl NonAbsentCommitVotes {
 pub type ProdVotingPowerCalculator =
     ProvidedVotingPowerCalculator<tendermint::crypto::default::signature::Verifier>;
 
-impl<V: signature::Verifier> VotingPowerCalculator for ProvidedVotingPowerCalculator<V> {
+impl<V: signature::Verifier, W: PowerWeight> VotingPowerCalculator
+    for ProvidedVotingPowerCalculator<V, W>
+{
+    fn total_power_of(&self, validator_set: &ValidatorSet) -> u64 {
+        validator_set
+            .validators()
+            .iter()
+            .fold(0u64, |total, val_info| total + self.weight.weight(val_info))
+    }
+
     fn voting_power_in(
         &self,
         signed_header: &SignedHeader,
@@ -374,6 +629,7 @@ impl<V: signature::Verifier> VotingPowerCalculator for ProvidedVotingPowerCalcul
     ) -> Result<VotingPowerTally, VerificationError> {
         let mut votes = NonAbsentCommitVotes::new(signed_header)?;
         voting_power_in_impl::<V>(
+            &self.weight,
             &mut votes,
             validator_set,
             trust_threshold,
@@ -389,22 +645,120 @@ impl<V: signature::Verifier> VotingPowerCalculator for ProvidedVotingPowerCalcul
     ) -> Result<(VotingPowerTally, VotingPowerTally), VerificationError> {
         let mut votes = NonAbsentCommitVotes::new(signed_header)?;
         let first_tally = voting_power_in_impl::<V>(
+            &self.weight,
             &mut votes,
             first_set.0,
             first_set.1,
             self.total_power_of(first_set.0),
         )?;
         let second_tally = voting_power_in_impl::<V>(
+            &self.weight,
+            &mut votes,
+            second_set.0,
+            second_set.1,
+            self.total_power_of(second_set.0),
+        )?;
+        Ok((first_tally, second_tally))
+    }
+
+    fn voting_power_in_with_threads(
+        &self,
+        signed_header: &SignedHeader,
+        validator_set: &ValidatorSet,
+        trust_threshold: TrustThreshold,
+        threads: usize,
+    ) -> Result<VotingPowerTally, VerificationError> {
+        let mut votes = NonAbsentCommitVotes::new(signed_header)?;
+        voting_power_in_threads_impl::<V>(
+            &self.weight,
+            &mut votes,
+            validator_set,
+            trust_threshold,
+            self.total_power_of(validator_set),
+            threads,
+        )
+    }
+
+    fn voting_power_in_sets_with_threads(
+        &self,
+        signed_header: &SignedHeader,
+        first_set: (&ValidatorSet, TrustThreshold),
+        second_set: (&ValidatorSet, TrustThreshold),
+        threads: usize,
+    ) -> Result<(VotingPowerTally, VotingPowerTally), VerificationError> {
+        let mut votes = NonAbsentCommitVotes::new(signed_header)?;
+        let first_tally = voting_power_in_threads_impl::<V>(
+            &self.weight,
+            &mut votes,
+            first_set.0,
+            first_set.1,
+            self.total_power_of(first_set.0),
+            threads,
+        )?;
+        let second_tally = voting_power_in_threads_impl::<V>(
+            &self.weight,
             &mut votes,
             second_set.0,
             second_set.1,
             self.total_power_of(second_set.0),
+            threads,
         )?;
         Ok((first_tally, second_tally))
     }
 }
 
+/// Number of signatures verified by each thread before checking
+/// whether the voting power threshold is met.
+#[cfg(feature = "std")]
+const SIGNATURES_PER_THREAD: usize = 8;
+
+/// Same as [`voting_power_in_impl`], verifying signatures across the given number of threads.
+///
+/// The validators are processed in batches, in the order of the validator set. The
+/// signatures of a batch are verified in parallel, then the batch is tallied serially,
+/// stopping as soon as the threshold is met. The tally, and the error reported if any,
+/// are therefore the same as with serial verification.
+fn voting_power_in_threads_impl<V: signature::Verifier>(
+    weight: &impl PowerWeight,
+    votes: &mut NonAbsentCommitVotes,
+    validator_set: &ValidatorSet,
+    trust_threshold: TrustThreshold,
+    total_voting_power: u64,
+    threads: usize,
+) -> Result<VotingPowerTally, VerificationError> {
+    #[cfg(feature = "std")]
+    if threads > 1 {
+        let mut power = VotingPowerTally::new(total_voting_power, trust_threshold);
+        for batch in validator_set
+            .validators()
+            .chunks(threads * SIGNATURES_PER_THREAD)
+        {
+            votes.verify_in_parallel::<V>(batch, threads);
+
+            for validator in batch {
+                if votes.has_voted::<V>(validator)? {
+                    power.tally(weight.weight(validator));
+                    if power.check().is_ok() {
+                        return Ok(power);
+                    }
+                }
+            }
+        }
+        return Ok(power);
+    }
+
+    let _ = threads;
+    voting_power_in_impl::<V>(
+        weight,
+        votes,
+        validator_set,
+        trust_threshold,
+        total_voting_power,
+    )
+}
+
 fn voting_power_in_impl<V: signature::Verifier>(
+    weight: &impl PowerWeight,
     votes: &mut NonAbsentCommitVotes,
     validator_set: &ValidatorSet,
     trust_threshold: TrustThreshold,
@@ -413,7 +767,7 @@ fn voting_power_in_impl<V: signature::Verifier>(
     let mut power = VotingPowerTally::new(total_voting_power, trust_threshold);
     for validator in validator_set.validators() {
         if votes.has_voted::<V>(validator)? {
-            power.tally(validator.power());
+            power.tally(weight.weight(validator));
             // Break out of the loop when we have enough voting power.
             if power.check().is_ok() {
                 break;
@@ -429,10 +783,11 @@ fn voting_power_in_impl<V: signature::Verifier>(
 // TODO: We plan to add Lightweight MBT for `voting_power_in` in the near future
 #[cfg(test)]
 mod tests {
-    use tendermint::trust_threshold::TrustThresholdFraction;
+    use tendermint::{trust_threshold::TrustThresholdFraction, Time};
     use tendermint_testgen::{
         light_block::generate_signed_header, Commit, Generator, Header,
-        LightBlock as TestgenLightBlock, ValidatorSet, Vote as TestgenVote,
+        LightBlock as TestgenLightBlock, Validator as TestgenValidator, ValidatorSet,
+        Vote as TestgenVote,
     };
 
     use super::*;
@@ -581,4 +936,101 @@ mod tests {
         // ensure the result matches the expected result
         assert_eq!(result_ok.unwrap(), EXPECTED_RESULT);
     }
+
+    /// Weights each validator with the square of its voting power.
+    struct QuadraticPower;
+
+    impl PowerWeight for QuadraticPower {
+        fn weight(&self, validator: &validator::Info) -> u64 {
+            validator.power.value().pow(2)
+        }
+    }
+
+    #[test]
+    fn test_custom_power_weight() {
+        let vals: Vec<_> = [("a", 1), ("b", 2), ("c", 3)]
+            .into_iter()
+            .map(|(name, power)| TestgenValidator::new(name).voting_power(power))
+            .collect();
+        let valset = ValidatorSet {
+            validators: Some(vals.clone()),
+        }
+        .generate()
+        .unwrap();
+        let header = Header::new(&vals).time(Time::from_unix_timestamp(1, 0).unwrap());
+
+        // Only the validators holding 1 and 2 sign
+        let votes = vals[..2]
+            .iter()
+            .map(|val| TestgenVote::new(val.clone(), header.clone()))
+            .collect();
+        let commit = Commit::new_with_votes(header.clone(), 1, votes);
+        let signed_header = generate_signed_header(&header, &commit).unwrap();
+
+        let tally = |vp_calculator: &dyn VotingPowerCalculator| {
+            let tally = vp_calculator
+                .voting_power_in(&signed_header, &valset, TrustThreshold::TWO_THIRDS)
+                .unwrap();
+            (tally.tallied, tally.total)
+        };
+
+        assert_eq!(tally(&ProdVotingPowerCalculator::default()), (3, 6));
+        assert_eq!(
+            tally(&ProvidedVotingPowerCalculator::<
+                tendermint::crypto::default::signature::Verifier,
+                _,
+            >::with_weight(QuadraticPower)),
+            (5, 14)
+        );
+    }
+
+    #[test]
+    fn test_threads_match_serial_verification() {
+        let vp_calculator = ProdVotingPowerCalculator::default();
+
+        let names: Vec<String> = (0..20).map(|i| alloc::format!("val-{i:02}")).collect();
+        let validator_set = ValidatorSet::new(names.iter().map(String::as_str).collect());
+        let vals = validator_set.clone().validators.unwrap();
+        let valset = validator_set.generate().unwrap();
+        let header = Header::new(&vals);
+
+        // Corrupt the signature of a validator near the front, in the middle or at the
+        // back of the set, or none at all, so that the invalid signature is found
+        // before or after the point where the threshold is met.
+        for invalid in [Some(0), Some(10), Some(19), None] {
+            let votes = vals
+                .iter()
+                .enumerate()
+                .map(|(i, val)| {
+                    let vote = TestgenVote::new(val.clone(), header.clone());
+                    if Some(i) == invalid {
+                        vote.header(header.clone().chain_id("bad-chain"))
+                    } else {
+                        vote
+                    }
+                })
+                .collect();
+            let commit = Commit::new_with_votes(header.clone(), 1, votes);
+            let signed_header = generate_signed_header(&header, &commit).unwrap();
+
+            for trust_threshold in [TrustThreshold::ONE_THIRD, TrustThreshold::TWO_THIRDS] {
+                let serial = vp_calculator
+                    .voting_power_in(&signed_header, &valset, trust_threshold)
+                    .map_err(VerificationError::into_detail);
+
+                for threads in [1, 2, 3, 8] {
+                    let parallel = vp_calculator
+                        .voting_power_in_with_threads(
+                            &signed_header,
+                            &valset,
+                            trust_threshold,
+                            threads,
+                        )
+                        .map_err(VerificationError::into_detail);
+
+                    assert_eq!(parallel, serial, "invalid={invalid:?} threads={threads}");
+                }
+            }
+        }
+    }
 }
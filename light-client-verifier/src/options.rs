@@ -7,7 +7,39 @@ use core::time::Duration;
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
-use crate::types::TrustThreshold;
+use crate::{
+    errors::VerificationError,
+    prelude::*,
+    types::{Height, Time, TrustThreshold},
+};
+
+/// Default value of [`Options::max_bisection_steps`], high enough not to be
+/// reached by bisection in normal operation.
+pub const DEFAULT_MAX_BISECTION_STEPS: usize = 1_000;
+
+fn default_max_bisection_steps() -> usize {
+    DEFAULT_MAX_BISECTION_STEPS
+}
+
+/// Default value of [`Options::max_stalled_iterations`]. Bisection can legitimately
+/// fail to raise the trusted height for a few consecutive steps while it halves the
+/// interval towards the trusted block, but never more than about 63 times in a row.
+pub const DEFAULT_MAX_STALLED_ITERATIONS: usize = 100;
+
+fn default_max_stalled_iterations() -> usize {
+    DEFAULT_MAX_STALLED_ITERATIONS
+}
+
+/// Default value of [`Options::fork_detection_quorum`]: every witness must respond.
+pub const DEFAULT_FORK_DETECTION_QUORUM: usize = usize::MAX;
+
+fn default_fork_detection_quorum() -> usize {
+    DEFAULT_FORK_DETECTION_QUORUM
+}
+
+fn default_signature_verification_threads() -> usize {
+    1
+}
 
 /// Verification parameters
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Display, Serialize, Deserialize)]
@@ -27,4 +59,271 @@ pub struct Options {
     /// is the maximum amount that the local clock may drift behind a timestamp from the
     /// blockchain.
     pub clock_drift: Duration,
+
+    /// Whether to carry on with verification against the primary alone when no
+    /// witness is available, instead of failing with `NoWitnesses`.
+    ///
+    /// Fork detection is skipped entirely in that case, which means a faulty
+    /// primary cannot be caught, and the fork detector reports it as skipped
+    /// for the verified block. Only enable this if a degraded client is
+    /// preferable to no client at all.
+    #[serde(default)]
+    pub allow_primary_only: bool,
+
+    /// If set, the light client uses this instant as the current time for the
+    /// trusting period and clock drift checks, instead of querying its clock.
+    ///
+    /// This is meant for replaying a past verification, eg. when debugging,
+    /// so that the checks reflect what they were at the time. Leave it to `None`
+    /// to use the real clock.
+    #[serde(default)]
+    pub now_override: Option<Time>,
+
+    /// Whether to tolerate a header at height 1 without a `last_block_id`,
+    /// instead of failing with `MissingLastBlockId`.
+    ///
+    /// The condition is exactly `header.height == 1`: some chains omit the
+    /// `last_block_id` of their first block, as there is no block before it.
+    /// A missing `last_block_id` at any other height is always an error.
+    #[serde(default)]
+    pub allow_missing_last_block_id_at_genesis: bool,
+
+    /// Maximum number of intermediate verifications attempted when bisecting
+    /// towards a target block, after which verification fails with
+    /// `BisectionStepLimitExceeded`. Defaults to [`DEFAULT_MAX_BISECTION_STEPS`].
+    #[serde(default = "default_max_bisection_steps")]
+    pub max_bisection_steps: usize,
+
+    /// Number of threads across which the commit signatures of a header are verified.
+    /// Defaults to 1, ie. signatures are verified serially on the calling thread.
+    ///
+    /// Whatever the number of threads, verification yields the same outcome and
+    /// voting power tally as serial verification. This requires the `std` feature,
+    /// without which signatures are always verified serially.
+    #[serde(default = "default_signature_verification_threads")]
+    pub signature_verification_threads: usize,
+
+    /// Maximum number of consecutive verification steps which do not raise the
+    /// highest trusted height, after which verification fails with `NoProgress`.
+    /// Defaults to [`DEFAULT_MAX_STALLED_ITERATIONS`].
+    #[serde(default = "default_max_stalled_iterations")]
+    pub max_stalled_iterations: usize,
+
+    /// Whether to verify the target block as an adjacent block when it is right above the
+    /// highest verified or trusted block below it, eg. for clients following a full node
+    /// and storing every block. The target block must then link to the one below it through
+    /// its `last_block_id`, and be signed by more than two thirds of the validators announced
+    /// by it, without bisecting or checking the trust threshold. Unverified blocks in the
+    /// light store do not count.
+    ///
+    /// Verification falls back to bisection otherwise, and always does in a light client
+    /// built without the `rust-crypto` feature, which cannot hash headers. Disabled by default.
+    #[serde(default)]
+    pub adjacent_only: bool,
+
+    /// Whether to verify the block right above the trusted state next, rather than
+    /// bisecting, when a block cannot be trusted yet and less than the trust threshold
+    /// of the trusted validators' voting power is still held by its validators.
+    ///
+    /// Across such a validator churn, bisection may take many steps before finding
+    /// enough overlap, whereas adjacent blocks can always be verified. Disabled by default.
+    #[serde(default)]
+    pub step_through_validator_churn: bool,
+
+    /// Whether to store successfully verified blocks as `Trusted` directly, rather than as
+    /// `Verified` until they are promoted, eg. after fork detection.
+    ///
+    /// This skips the promotion step entirely: a block served by a faulty primary, which
+    /// fork detection would have caught, is then trusted and used as a trusted state for
+    /// further verifications. Only enable this if nothing else checks verified blocks
+    /// before promoting them, eg. with a single writer and no witnesses. Disabled by default.
+    #[serde(default)]
+    pub auto_trust_verified: bool,
+
+    /// Number of witnesses which must respond for fork detection to complete. Once that
+    /// many witnesses have been compared against the primary, the others are not queried,
+    /// and fork detection fails with `InsufficientWitnessResponses` if fewer respond.
+    ///
+    /// This is capped by the number of witnesses, so that the default of
+    /// [`DEFAULT_FORK_DETECTION_QUORUM`] waits for all of them.
+    #[serde(default = "default_fork_detection_quorum")]
+    pub fork_detection_quorum: usize,
+
+    /// If set, the light client refuses to verify any block below this height, failing
+    /// with `BelowMinHeight` before fetching anything.
+    ///
+    /// This is a policy floor guarding against accidental deep re-verifications, which are
+    /// expensive for the archive nodes serving them, regardless of the trusted state.
+    /// Defaults to `None`, ie. no floor.
+    #[serde(default)]
+    pub min_verifiable_height: Option<Height>,
+
+    /// Whether to record why each block failed verification, alongside the block stored
+    /// with the `Failed` status, eg. for post-incident review. The reasons can then be
+    /// read back with `LightStore::failures`.
+    ///
+    /// Disabled by default, so as not to grow the light store with the reasons.
+    #[serde(default)]
+    pub retain_failed: bool,
+
+    /// Number of witnesses which may disagree with the primary without a fork being
+    /// declared, eg. to put up with the occasional flaky node of a large witness set.
+    ///
+    /// A fork is only declared when more witnesses than this disagree with the primary;
+    /// fewer disagreeing witnesses are evicted instead. Defaults to zero, ie. a single
+    /// disagreeing witness is enough to declare a fork.
+    #[serde(default)]
+    pub max_faulty_witnesses: usize,
+}
+
+impl Options {
+    /// Options with the given trust parameters, and every other option set to its default.
+    ///
+    /// Unlike [`OptionsBuilder::build`], this does not check that the trust parameters
+    /// make sense together.
+    pub fn new(
+        trust_threshold: TrustThreshold,
+        trusting_period: Duration,
+        clock_drift: Duration,
+    ) -> Self {
+        Self {
+            trust_threshold,
+            trusting_period,
+            clock_drift,
+            allow_primary_only: false,
+            now_override: None,
+            allow_missing_last_block_id_at_genesis: false,
+            max_bisection_steps: DEFAULT_MAX_BISECTION_STEPS,
+            signature_verification_threads: default_signature_verification_threads(),
+            max_stalled_iterations: DEFAULT_MAX_STALLED_ITERATIONS,
+            adjacent_only: false,
+            step_through_validator_churn: false,
+            auto_trust_verified: false,
+            fork_detection_quorum: DEFAULT_FORK_DETECTION_QUORUM,
+            min_verifiable_height: None,
+            retain_failed: false,
+            max_faulty_witnesses: 0,
+        }
+    }
+
+    /// The parameters which decide whether a block can be trusted, for logging.
+    pub fn snapshot(&self) -> OptionsSnapshot {
+        OptionsSnapshot {
+            trust_threshold: self.trust_threshold,
+            trusting_period: self.trusting_period,
+            clock_drift: self.clock_drift,
+        }
+    }
+}
+
+/// Compact summary of the [`Options`] in effect, as carried by errors, so that a log line
+/// reporting a failed verification tells under which parameters it failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[display(
+    fmt = "trust threshold {trust_threshold}, trusting period {trusting_period:?}, clock drift {clock_drift:?}"
+)]
+pub struct OptionsSnapshot {
+    /// See [`Options::trust_threshold`].
+    pub trust_threshold: TrustThreshold,
+    /// See [`Options::trusting_period`].
+    pub trusting_period: Duration,
+    /// See [`Options::clock_drift`].
+    pub clock_drift: Duration,
+}
+
+/// Builds [`Options`], checking that the trust parameters make sense together.
+///
+/// The other options are set to their defaults, and can be changed on the built value.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use tendermint_light_client_verifier::options::OptionsBuilder;
+/// let options = OptionsBuilder::new()
+///     .trusting_period(Duration::from_secs(60 * 60 * 24 * 14))
+///     .clock_drift(Duration::from_secs(5))
+///     .build()
+///     .unwrap();
+///
+/// // The trusting period must be set, and be longer than the clock drift
+/// assert!(OptionsBuilder::new().build().is_err());
+/// assert!(OptionsBuilder::new()
+///     .trusting_period(Duration::from_secs(5))
+///     .build()
+///     .is_err());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OptionsBuilder {
+    trust_threshold: TrustThreshold,
+    trusting_period: Duration,
+    clock_drift: Duration,
+}
+
+impl OptionsBuilder {
+    /// Start with a trust threshold of 1/3 and a clock drift of 10 seconds.
+    ///
+    /// The trusting period has no sensible default, as it depends on the unbonding period
+    /// of the chain, and must be set.
+    pub fn new() -> Self {
+        Self {
+            trust_threshold: TrustThreshold::ONE_THIRD,
+            trusting_period: Duration::ZERO,
+            clock_drift: Duration::from_secs(10),
+        }
+    }
+
+    /// See [`Options::trust_threshold`].
+    pub fn trust_threshold(mut self, trust_threshold: TrustThreshold) -> Self {
+        self.trust_threshold = trust_threshold;
+        self
+    }
+
+    /// See [`Options::trusting_period`].
+    pub fn trusting_period(mut self, trusting_period: Duration) -> Self {
+        self.trusting_period = trusting_period;
+        self
+    }
+
+    /// See [`Options::clock_drift`].
+    pub fn clock_drift(mut self, clock_drift: Duration) -> Self {
+        self.clock_drift = clock_drift;
+        self
+    }
+
+    /// Build the options, or fail with `InvalidOptions` if the trust threshold is not
+    /// within `[1/3, 1]`, if the trusting period is zero, or if the clock drift is not
+    /// shorter than the trusting period.
+    pub fn build(self) -> Result<Options, VerificationError> {
+        let threshold = self.trust_threshold;
+        if TrustThreshold::new(threshold.numerator(), threshold.denominator()).is_err() {
+            return Err(VerificationError::invalid_options(alloc::format!(
+                "trust threshold {threshold} is not within [1/3, 1]"
+            )));
+        }
+
+        if self.trusting_period.is_zero() {
+            return Err(VerificationError::invalid_options(
+                "trusting period is zero".to_string(),
+            ));
+        }
+
+        if self.clock_drift >= self.trusting_period {
+            return Err(VerificationError::invalid_options(alloc::format!(
+                "clock drift {:?} is not shorter than trusting period {:?}",
+                self.clock_drift,
+                self.trusting_period
+            )));
+        }
+
+        Ok(Options::new(
+            self.trust_threshold,
+            self.trusting_period,
+            self.clock_drift,
+        ))
+    }
+}
+
+impl Default for OptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -34,6 +34,24 @@ pub trait VerificationPredicates: Send + Sync {
     /// The implementation of SHA256 digest
     type Sha256: MerkleHash + Sha256 + Default;
 
+    /// Check that the validator set has as many validators as the commit has signatures,
+    /// before hashing it, so that a truncated or padded validator set is told apart from
+    /// one whose contents differ.
+    fn validator_count_matches(
+        &self,
+        validators: &ValidatorSet,
+        signed_header: &SignedHeader,
+    ) -> Result<(), VerificationError> {
+        let expected = signed_header.commit.signatures.len();
+        let got = validators.validators().len();
+
+        if expected == got {
+            Ok(())
+        } else {
+            Err(VerificationError::validator_count_mismatch(expected, got))
+        }
+    }
+
     /// Compare the provided validator_set_hash against the hash produced from hashing the validator
     /// set.
     fn validator_sets_match(
@@ -473,6 +491,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validator_count_matches() {
+        let mut light_block: LightBlock =
+            TestgenLightBlock::new_default(1).generate().unwrap().into();
+
+        let vp = ProdPredicates;
+
+        let result_ok =
+            vp.validator_count_matches(&light_block.validators, &light_block.signed_header);
+        assert!(result_ok.is_ok());
+
+        let expected = light_block.signed_header.commit.signatures.len();
+        light_block.validators = ValidatorSet::new(vec!["1", "2", "3", "4", "5"])
+            .generate()
+            .unwrap();
+
+        let result_err =
+            vp.validator_count_matches(&light_block.validators, &light_block.signed_header);
+
+        match result_err {
+            Err(VerificationError(VerificationErrorDetail::ValidatorCountMismatch(e), _)) => {
+                assert_eq!(e.expected, expected);
+                assert_eq!(e.got, 5);
+            },
+            _ => panic!("expected ValidatorCountMismatch error"),
+        }
+    }
+
     #[test]
     fn test_header_matches_commit() {
         let mut signed_header = TestgenLightBlock::new_default(1)
@@ -1,5 +1,7 @@
 //! Defines or just re-exports the main datatypes used by the light client.
 
+use core::time::Duration;
+
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use tendermint::{
@@ -14,7 +16,7 @@ use tendermint::{
 };
 pub use tendermint::{block::Height, hash::Hash, time::Time};
 
-use crate::prelude::*;
+use crate::{errors::VerificationError, prelude::*};
 
 /// Peer ID (public key) of a full node
 pub type PeerId = tendermint::node::Id;
@@ -53,6 +55,10 @@ pub type TrustedState = LightBlock;
 pub enum Status {
     /// The light block has failed verification.
     Failed,
+    /// The light block used to be trusted, until another block was made the trusted anchor.
+    ///
+    /// Archived blocks are kept for audit purposes only, and are never used for verification.
+    Archived,
     /// The light has not been verified yet.
     Unverified,
     /// The light block has been successfully verified.
@@ -65,13 +71,13 @@ impl Status {
     /// Return a slice of all the possible values for this enum.
     pub fn iter() -> &'static [Self] {
         use Status::*;
-        static ALL: &[Status] = &[Unverified, Verified, Trusted, Failed];
+        static ALL: &[Status] = &[Unverified, Verified, Trusted, Failed, Archived];
         ALL
     }
 
     /// Returns the most trusted status between the two given one.
     ///
-    /// From least to most trusted: `Failed`, `Unverified`, `Verified`, `Trusted`.
+    /// From least to most trusted: `Failed`, `Archived`, `Unverified`, `Verified`, `Trusted`.
     pub fn most_trusted(a: Self, b: Self) -> Self {
         core::cmp::max(a, b)
     }
@@ -98,6 +104,12 @@ impl<'a> UntrustedBlockState<'a> {
     pub fn height(&self) -> Height {
         self.signed_header.header.height
     }
+
+    /// Whether this block immediately follows the given trusted block,
+    /// in which case it can be verified sequentially rather than by skipping.
+    pub fn is_adjacent_to(&self, trusted: &TrustedBlockState<'_>) -> bool {
+        self.height() == trusted.height.increment()
+    }
 }
 
 /// A light block is the core data structure used by the light client.
@@ -154,8 +166,8 @@ impl LightBlock {
     pub fn as_trusted_state(&self) -> TrustedBlockState<'_> {
         TrustedBlockState {
             chain_id: &self.signed_header.header.chain_id,
-            header_time: self.signed_header.header.time,
-            height: self.signed_header.header.height,
+            header_time: self.time(),
+            height: self.height(),
             next_validators: &self.next_validators,
             next_validators_hash: self.signed_header.header.next_validators_hash,
         }
@@ -172,6 +184,12 @@ impl LightBlock {
     }
 }
 
+/// Subtract the given duration from the given time, eg. to find when a trusting period
+/// started, failing with `TimeArithmetic` if the result is not a valid time.
+pub fn checked_sub(time: Time, duration: Duration) -> Result<Time, VerificationError> {
+    (time - duration).map_err(|e| VerificationError::time_arithmetic(time, duration, e))
+}
+
 /// Contains the local status information, like the latest height, latest block and valset hashes,
 /// list of of connected full nodes (primary and witnesses).
 #[derive(Clone, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
@@ -219,7 +237,8 @@ mod tests {
         fn ord_impl() {
             assert!(Trusted > Verified);
             assert!(Verified > Unverified);
-            assert!(Unverified > Failed);
+            assert!(Unverified > Archived);
+            assert!(Archived > Failed);
         }
 
         #[test]
@@ -243,4 +262,29 @@ mod tests {
                 .collect()
         }
     }
+
+    mod checked_sub {
+        use core::time::Duration;
+
+        use crate::{
+            errors::VerificationErrorDetail,
+            types::{checked_sub, Time},
+        };
+
+        #[test]
+        fn underflow_is_a_time_arithmetic_error() {
+            let time = Time::from_unix_timestamp(100, 0).unwrap();
+
+            assert_eq!(
+                checked_sub(time, Duration::from_secs(40)).unwrap(),
+                Time::from_unix_timestamp(60, 0).unwrap()
+            );
+
+            let error = checked_sub(time, Duration::from_secs(u64::MAX)).unwrap_err();
+            assert!(matches!(
+                error.detail(),
+                VerificationErrorDetail::TimeArithmetic(_)
+            ));
+        }
+    }
 }
@@ -4,10 +4,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     errors::{ErrorExt, VerificationError, VerificationErrorDetail},
-    operations::{voting_power::VotingPowerTally, CommitValidator, VotingPowerCalculator},
+    operations::{
+        voting_power::{RecordSigners, VotingPowerTally, WithThreads},
+        CommitValidator, VotingPowerCalculator,
+    },
     options::Options,
     predicates::VerificationPredicates,
-    types::{Time, TrustedBlockState, UntrustedBlockState},
+    types::{Height, LightBlock, Time, TrustedBlockState, UntrustedBlockState, ValidatorSet},
 };
 
 #[cfg(feature = "rust-crypto")]
@@ -41,6 +44,32 @@ impl From<Result<(), VerificationError>> for Verdict {
     }
 }
 
+impl From<Result<(), VerificationErrorDetail>> for Verdict {
+    fn from(result: Result<(), VerificationErrorDetail>) -> Self {
+        match result {
+            Ok(()) => Self::Success,
+            Err(e) => match e.not_enough_trust() {
+                Some(tally) => Self::NotEnoughTrust(tally),
+                _ => Self::Invalid(e),
+            },
+        }
+    }
+}
+
+impl Verdict {
+    /// Convert this verdict into a `Result`, where not reaching the voting power
+    /// threshold is reported as a `NotEnoughTrust` error.
+    pub fn into_result(self) -> Result<(), VerificationErrorDetail> {
+        match self {
+            Self::Success => Ok(()),
+            Self::NotEnoughTrust(tally) => {
+                Err(VerificationError::not_enough_trust(tally).into_detail())
+            },
+            Self::Invalid(e) => Err(e),
+        }
+    }
+}
+
 /// The verifier checks:
 ///
 /// a) whether a given untrusted light block is valid, and
@@ -60,6 +89,23 @@ pub trait Verifier: Send + Sync {
         now: Time,
     ) -> Verdict;
 
+    /// Same as [`Self::verify_update_header`], but additionally returns, on success,
+    /// the voting power of the untrusted validators which signed the untrusted header.
+    ///
+    /// The default implementation does not report any tally.
+    fn verify_update_header_with_tally(
+        &self,
+        untrusted: UntrustedBlockState<'_>,
+        trusted: TrustedBlockState<'_>,
+        options: &Options,
+        now: Time,
+    ) -> (Verdict, Option<VotingPowerTally>) {
+        (
+            self.verify_update_header(untrusted, trusted, options, now),
+            None,
+        )
+    }
+
     /// Verify a header received in `MsgSubmitMisbehaviour`.
     /// The verification for these headers is a bit more relaxed in order to catch FLA attacks.
     /// In particular the "header in the future" check for the header should be skipped
@@ -71,6 +117,15 @@ pub trait Verifier: Send + Sync {
         options: &Options,
         now: Time,
     ) -> Verdict;
+
+    /// Verify the checks of a header which do not depend on any trusted state, ie. that
+    /// its validator sets match the hashes of the header and that more than 2/3 of its
+    /// own validators correctly committed it, eg. for a block used as a root of trust.
+    ///
+    /// The default implementation performs no check.
+    fn verify_standalone_header(&self, _untrusted: UntrustedBlockState<'_>) -> Verdict {
+        Verdict::Success
+    }
 }
 
 macro_rules! verdict {
@@ -117,6 +172,11 @@ where
 
     /// Validates an `UntrustedBlockState`.
     pub fn verify_validator_sets(&self, untrusted: &UntrustedBlockState<'_>) -> Verdict {
+        // Ensure the validator set is neither truncated nor padded, before hashing it
+        verdict!(self
+            .predicates
+            .validator_count_matches(untrusted.validators, untrusted.signed_header));
+
         // Ensure the header validator hashes match the given validators
         verdict!(self.predicates.validator_sets_match(
             untrusted.validators,
@@ -173,9 +233,7 @@ where
             .predicates
             .is_matching_chain_id(&untrusted.signed_header.header.chain_id, trusted.chain_id));
 
-        let trusted_next_height = trusted.height.increment();
-
-        if untrusted.height() == trusted_next_height {
+        if untrusted.is_adjacent_to(trusted) {
             // If the untrusted block is the very next block after the trusted block,
             // check that their (next) validator sets hashes match.
             verdict!(self.predicates.valid_next_validator_set(
@@ -223,6 +281,22 @@ where
         Verdict::Success
     }
 
+    /// Perform the checks of [`Verifier::verify_update_header`] which come before
+    /// verifying the commit.
+    fn validate_update_header(
+        &self,
+        untrusted: &UntrustedBlockState<'_>,
+        trusted: &TrustedBlockState<'_>,
+        options: &Options,
+        now: Time,
+    ) -> Verdict {
+        ensure_verdict_success!(self.verify_validator_sets(untrusted));
+        ensure_verdict_success!(self.validate_against_trusted(untrusted, trusted, options, now));
+        ensure_verdict_success!(self.check_header_is_from_past(untrusted, options, now));
+
+        Verdict::Success
+    }
+
     /// Verify that a) there is enough overlap between the validator sets of the
     /// trusted and untrusted blocks and b) more than 2/3 of the validators
     /// correctly committed the block.
@@ -232,11 +306,33 @@ where
         trusted: &TrustedBlockState<'_>,
         options: &Options,
     ) -> Verdict {
+        self.commit_tally_against_trusted(untrusted, trusted, options)
+            .0
+    }
+
+    /// Same as [`Self::verify_commit_against_trusted`], but additionally returns, on
+    /// success, the voting power of the untrusted validators which signed the commit.
+    fn commit_tally_against_trusted(
+        &self,
+        untrusted: &UntrustedBlockState<'_>,
+        trusted: &TrustedBlockState<'_>,
+        options: &Options,
+    ) -> (Verdict, Option<VotingPowerTally>) {
         // If the trusted validator set has changed we need to check if there’s
         // overlap between the old trusted set and the new untrested header in
         // addition to checking if the new set correctly signed the header.
-        let trusted_next_height = trusted.height.increment();
-        let need_both = untrusted.height() != trusted_next_height;
+        let need_both = !untrusted.is_adjacent_to(trusted);
+
+        let with_threads = WithThreads {
+            calculator: &self.voting_power_calculator,
+            threads: options.signature_verification_threads,
+        };
+        let calculator: &dyn VotingPowerCalculator = if with_threads.threads > 1 {
+            &with_threads
+        } else {
+            &self.voting_power_calculator
+        };
+        let calculator = RecordSigners::new(calculator);
 
         let result = if need_both {
             self.predicates
@@ -245,17 +341,73 @@ where
                     trusted.next_validators,
                     &options.trust_threshold,
                     untrusted.validators,
-                    &self.voting_power_calculator,
+                    &calculator,
                 )
         } else {
             self.predicates.has_sufficient_signers_overlap(
                 untrusted.signed_header,
                 untrusted.validators,
-                &self.voting_power_calculator,
+                &calculator,
             )
         };
-        verdict!(result);
-        Verdict::Success
+
+        match result {
+            Ok(()) => (Verdict::Success, calculator.tally()),
+            Err(e) => (Verdict::from(Err(e)), None),
+        }
+    }
+}
+
+impl<P, C, V> PredicateVerifier<P, C, V>
+where
+    P: VerificationPredicates,
+    C: VotingPowerCalculator,
+    V: CommitValidator,
+{
+    /// Verify the `untrusted` light block against the `trusted` one, taking the given
+    /// validator set as the next validators of the trusted block rather than fetching
+    /// it, eg. when it is already available on disk.
+    ///
+    /// This performs the same checks as [`Verifier::verify_update_header`], after
+    /// ensuring that the given validator set hashes to the trusted header's
+    /// `next_validators_hash`. It does not perform any I/O.
+    ///
+    /// Fails with `NotEnoughTrust` if the voting power threshold is not reached.
+    /// See [`Self::verdict_with_next_validators`] to tell that case apart without
+    /// matching on the error.
+    pub fn verify_with_next_validators(
+        &self,
+        untrusted: &LightBlock,
+        trusted: &LightBlock,
+        trusted_next_validators: &ValidatorSet,
+        options: &Options,
+        now: Time,
+    ) -> Result<(), VerificationErrorDetail> {
+        self.verdict_with_next_validators(untrusted, trusted, trusted_next_validators, options, now)
+            .into_result()
+    }
+
+    /// Same as [`Self::verify_with_next_validators`], but returns a [`Verdict`] telling
+    /// apart a block which is invalid from one which cannot be trusted yet.
+    pub fn verdict_with_next_validators(
+        &self,
+        untrusted: &LightBlock,
+        trusted: &LightBlock,
+        trusted_next_validators: &ValidatorSet,
+        options: &Options,
+        now: Time,
+    ) -> Verdict {
+        verdict!(self.predicates.next_validators_match(
+            trusted_next_validators,
+            trusted.signed_header.header.next_validators_hash,
+        ));
+
+        let trusted = TrustedBlockState {
+            next_validators: trusted_next_validators,
+            ..trusted.as_trusted_state()
+        };
+
+        self.verify_update_header(untrusted.as_untrusted_state(), trusted, options, now)
     }
 }
 
@@ -299,12 +451,21 @@ where
         options: &Options,
         now: Time,
     ) -> Verdict {
-        ensure_verdict_success!(self.verify_validator_sets(&untrusted));
-        ensure_verdict_success!(self.validate_against_trusted(&untrusted, &trusted, options, now));
-        ensure_verdict_success!(self.check_header_is_from_past(&untrusted, options, now));
-        ensure_verdict_success!(self.verify_commit_against_trusted(&untrusted, &trusted, options));
+        self.verify_update_header_with_tally(untrusted, trusted, options, now)
+            .0
+    }
 
-        Verdict::Success
+    fn verify_update_header_with_tally(
+        &self,
+        untrusted: UntrustedBlockState<'_>,
+        trusted: TrustedBlockState<'_>,
+        options: &Options,
+        now: Time,
+    ) -> (Verdict, Option<VotingPowerTally>) {
+        match self.validate_update_header(&untrusted, &trusted, options, now) {
+            Verdict::Success => self.commit_tally_against_trusted(&untrusted, &trusted, options),
+            verdict => (verdict, None),
+        }
     }
 
     /// Verify a header received in `MsgSubmitMisbehaviour`.
@@ -322,6 +483,27 @@ where
         ensure_verdict_success!(self.verify_commit_against_trusted(&untrusted, &trusted, options));
         Verdict::Success
     }
+
+    fn verify_standalone_header(&self, untrusted: UntrustedBlockState<'_>) -> Verdict {
+        ensure_verdict_success!(self.verify_validator_sets(&untrusted));
+        self.verify_commit(&untrusted)
+    }
+}
+
+/// The height the light client tries next when it bisects between a trusted height and
+/// a target height it could not verify from it, rounded up, so that it always moves past
+/// the trusted height if the target is right above it.
+///
+/// If the target height is not above the trusted height, it is returned as is.
+pub fn bisection_midpoint(trusted_height: Height, target_height: Height) -> Height {
+    if target_height <= trusted_height {
+        return target_height;
+    }
+
+    let (low, high) = (trusted_height.value(), target_height.value());
+    (low + (high + 1 - low) / 2)
+        .try_into()
+        .expect("the midpoint is not above the target height")
 }
 
 #[cfg(feature = "rust-crypto")]
@@ -331,15 +513,19 @@ pub type ProdVerifier =
 
 #[cfg(test)]
 mod tests {
-    use alloc::{borrow::ToOwned, string::ToString};
+    use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
     use core::{ops::Sub, time::Duration};
 
     use tendermint::Time;
     use tendermint_testgen::{light_block::LightBlock as TestgenLightBlock, Generator};
 
     use crate::{
-        errors::VerificationErrorDetail, options::Options, types::LightBlock, ProdVerifier,
-        Verdict, Verifier,
+        bisection_midpoint,
+        errors::VerificationErrorDetail,
+        operations::VotingPowerTally,
+        options::Options,
+        types::{LightBlock, ValidatorSet},
+        ProdVerifier, Verdict, Verifier,
     };
 
     #[allow(dead_code)]
@@ -349,6 +535,102 @@ mod tests {
         verifier: ProdVerifier,
     }
 
+    #[cfg(feature = "rust-crypto")]
+    #[test]
+    fn test_verify_with_next_validators() {
+        let now = Time::now();
+
+        let light_block_1: LightBlock = TestgenLightBlock::new_default_with_time_and_chain_id(
+            "chain-1".to_owned(),
+            now.sub(Duration::from_secs(20)).unwrap(),
+            1u64,
+        )
+        .generate()
+        .unwrap()
+        .into();
+
+        let light_block_2: LightBlock = TestgenLightBlock::new_default_with_time_and_chain_id(
+            "chain-1".to_owned(),
+            now.sub(Duration::from_secs(10)).unwrap(),
+            2u64,
+        )
+        .generate()
+        .unwrap()
+        .into();
+
+        let vp = ProdVerifier::default();
+        let opt = Options::new(
+            Default::default(),
+            Duration::from_secs(60),
+            Default::default(),
+        );
+
+        let result = vp.verify_with_next_validators(
+            &light_block_2,
+            &light_block_1,
+            &light_block_1.next_validators,
+            &opt,
+            now,
+        );
+        assert_eq!(result, Ok(()));
+
+        let other_validators = ValidatorSet::without_proposer(Vec::new());
+        let result = vp.verify_with_next_validators(
+            &light_block_2,
+            &light_block_1,
+            &other_validators,
+            &opt,
+            now,
+        );
+        assert!(matches!(
+            result,
+            Err(VerificationErrorDetail::InvalidNextValidatorSet(_))
+        ));
+
+        let verdict = vp.verdict_with_next_validators(
+            &light_block_2,
+            &light_block_1,
+            &other_validators,
+            &opt,
+            now,
+        );
+        assert!(matches!(
+            verdict,
+            Verdict::Invalid(VerificationErrorDetail::InvalidNextValidatorSet(_))
+        ));
+    }
+
+    #[test]
+    fn test_verdict_result_round_trip() {
+        let tally = VotingPowerTally {
+            total: 100,
+            tallied: 10,
+            trust_threshold: Default::default(),
+        };
+
+        assert_eq!(
+            Verdict::from(Verdict::Success.into_result()),
+            Verdict::Success
+        );
+        assert_eq!(
+            Verdict::from(Verdict::NotEnoughTrust(tally).into_result()),
+            Verdict::NotEnoughTrust(tally)
+        );
+    }
+
+    #[test]
+    fn test_bisection_midpoint() {
+        let midpoint = |trusted: u32, target: u32| -> u64 {
+            bisection_midpoint(trusted.into(), target.into()).value()
+        };
+
+        assert_eq!(midpoint(1, 10), 6);
+        assert_eq!(midpoint(1, 11), 6);
+        assert_eq!(midpoint(4, 5), 5);
+        assert_eq!(midpoint(5, 5), 5);
+        assert_eq!(midpoint(7, 3), 3);
+    }
+
     #[test]
     fn test_verification_failure_on_chain_id_mismatch() {
         let now = Time::now();
@@ -376,11 +658,11 @@ mod tests {
         .into();
 
         let vp = ProdVerifier::default();
-        let opt = Options {
-            trust_threshold: Default::default(),
-            trusting_period: Duration::from_secs(60),
-            clock_drift: Default::default(),
-        };
+        let opt = Options::new(
+            Default::default(),
+            Duration::from_secs(60),
+            Default::default(),
+        );
 
         let verdict = vp.verify_update_header(
             light_block_2.as_untrusted_state(),
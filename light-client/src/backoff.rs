@@ -0,0 +1,175 @@
+//! Exponential backoff schedule for retrying requests to peers.
+//!
+//! A [`Backoff`] describes the policy, and iterating over it yields the delays to wait
+//! for before each retry. This crate does not retry on its own: the policy is meant
+//! for user code retrying requests to peers, e.g. on I/O errors or timeouts.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// Exponential backoff policy: the delay before the `n`-th retry is `base * 2^n`,
+/// capped at `max`, minus a random fraction of up to `jitter` of it.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use tendermint_light_client::backoff::Backoff;
+/// let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1));
+/// let delays: Vec<_> = backoff.into_iter().take(5).collect();
+///
+/// assert_eq!(delays, [100, 200, 400, 800, 1000].map(Duration::from_millis));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+    seed: Option<u64>,
+}
+
+impl Backoff {
+    /// A policy starting at `base` and doubling up to `max`, without jitter.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            jitter: 0.0,
+            seed: None,
+        }
+    }
+
+    /// Shorten each delay by a random fraction of up to `jitter` of it, so that peers
+    /// retrying at the same time spread out. The fraction is clamped to `[0, 1]`,
+    /// and a NaN fraction disables the jitter.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = if jitter.is_nan() {
+            0.0
+        } else {
+            jitter.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    /// Seed the jitter, so that iterating over this policy always yields the same delays.
+    ///
+    /// By default, each iteration is seeded randomly.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The delay before the first retry.
+    pub fn base(&self) -> Duration {
+        self.base
+    }
+
+    /// The maximum delay between two retries.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The maximum fraction of each delay removed at random.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+}
+
+impl Default for Backoff {
+    /// Start at 100 ms, double up to 10 s, with a jitter of a half.
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(10)).with_jitter(0.5)
+    }
+}
+
+impl IntoIterator for Backoff {
+    type Item = Duration;
+    type IntoIter = BackoffIter;
+
+    fn into_iter(self) -> BackoffIter {
+        let seed = self
+            .seed
+            .unwrap_or_else(|| RandomState::new().build_hasher().finish());
+
+        BackoffIter {
+            backoff: self,
+            next: self.base.min(self.max),
+            // Xorshift gets stuck on zero
+            rng: seed | 1,
+        }
+    }
+}
+
+/// The endless sequence of delays of a [`Backoff`] policy.
+#[derive(Clone, Debug)]
+pub struct BackoffIter {
+    backoff: Backoff,
+    next: Duration,
+    rng: u64,
+}
+
+impl BackoffIter {
+    /// A random number in `[0, 1)`, by xorshift.
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        (self.rng >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+impl Iterator for BackoffIter {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.next;
+        self.next = delay.saturating_mul(2).min(self.backoff.max);
+
+        if self.backoff.jitter == 0.0 {
+            return Some(delay);
+        }
+
+        let jitter = self.backoff.jitter * self.random();
+        Some(delay.mul_f64(1.0 - jitter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jittered_delays_stay_within_bounds() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1))
+            .with_jitter(0.5)
+            .with_seed(42);
+
+        let delays: Vec<_> = backoff.into_iter().take(8).collect();
+        let unjittered = [100, 200, 400, 800, 1000, 1000, 1000, 1000].map(Duration::from_millis);
+
+        for (delay, max) in delays.iter().zip(unjittered) {
+            assert!(*delay <= max && *delay >= max / 2, "{delay:?} vs {max:?}");
+        }
+
+        // The same seed yields the same delays
+        assert_eq!(backoff.into_iter().take(8).collect::<Vec<_>>(), delays);
+    }
+
+    #[test]
+    fn nan_jitter_is_disabled() {
+        let backoff =
+            Backoff::new(Duration::from_millis(100), Duration::from_secs(1)).with_jitter(f64::NAN);
+        assert_eq!(backoff.jitter(), 0.0);
+
+        let delays: Vec<_> = backoff.into_iter().take(3).collect();
+        assert_eq!(delays, [100, 200, 400].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn base_above_max_is_capped() {
+        let backoff = Backoff::new(Duration::from_secs(5), Duration::from_secs(1));
+        assert_eq!(backoff.into_iter().next(), Some(Duration::from_secs(1)));
+    }
+}
@@ -1,11 +1,15 @@
 //! DSL for building a light client [`Instance`]
 
+use std::sync::Arc;
+
 use tendermint::{block::Height, crypto::Sha256, merkle::MerkleHash, Hash};
 
+#[cfg(feature = "rust-crypto")]
+use crate::components::block_cache::BlockCache;
 #[cfg(feature = "rpc-client")]
 use {
     crate::components::clock::SystemClock,
-    crate::components::io::ProdIo,
+    crate::components::io::{LatencyStats, ProdIo},
     crate::components::scheduler,
     crate::verifier::{predicates::ProdPredicates, ProdVerifier},
     core::time::Duration,
@@ -21,6 +25,7 @@ use crate::{
     },
     instance::Instance,
     light_client::LightClient,
+    peer_labeler::{DefaultPeerLabeler, PeerLabeler},
     state::{State, VerificationTrace},
     store::LightStore,
     verifier::{
@@ -48,6 +53,9 @@ pub struct LightClientBuilder<State, H: MerkleHash + Sha256 + Default> {
     scheduler: Box<dyn Scheduler>,
     predicates: Box<dyn VerificationPredicates<Sha256 = H>>,
     light_store: Box<dyn LightStore>,
+    peer_labeler: Arc<dyn PeerLabeler>,
+    #[cfg(feature = "rust-crypto")]
+    block_cache: Option<Arc<BlockCache>>,
 
     #[allow(dead_code)]
     state: State,
@@ -68,9 +76,28 @@ where
             scheduler: self.scheduler,
             predicates: self.predicates,
             light_store: self.light_store,
+            peer_labeler: self.peer_labeler,
+            #[cfg(feature = "rust-crypto")]
+            block_cache: self.block_cache,
             state,
         }
     }
+
+    /// Show peers with the given labeler in the errors and log lines of the light client,
+    /// see [`LightClient::with_peer_labeler`].
+    pub fn peer_labeler(mut self, labeler: Arc<dyn PeerLabeler>) -> Self {
+        self.peer_labeler = labeler;
+        self
+    }
+
+    /// Share the given block cache with other light clients,
+    /// see [`LightClient::with_block_cache`].
+    #[cfg(feature = "rust-crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+    pub fn block_cache(mut self, cache: Arc<BlockCache>) -> Self {
+        self.block_cache = Some(cache);
+        self
+    }
 }
 
 #[cfg(feature = "rpc-client")]
@@ -83,12 +110,37 @@ impl LightClientBuilder<NoTrustedState, tendermint::crypto::default::Sha256> {
         options: Options,
         timeout: Option<Duration>,
     ) -> Self {
+        Self::prod_with_latency_stats(
+            peer_id,
+            rpc_client,
+            light_store,
+            options,
+            timeout,
+            LatencyStats::new(),
+        )
+    }
+
+    /// Same as [`LightClientBuilder::prod`], but records the latency of the peer into
+    /// the given statistics.
+    ///
+    /// Building the light clients of the primary and of the witnesses with the same
+    /// statistics lets [`LightClient::witness_latencies`] report all of them.
+    pub fn prod_with_latency_stats(
+        peer_id: PeerId,
+        rpc_client: rpc::HttpClient,
+        light_store: Box<dyn LightStore>,
+        options: Options,
+        timeout: Option<Duration>,
+        latency: LatencyStats,
+    ) -> Self {
+        let io = ProdIo::new(peer_id, rpc_client, timeout).with_latency_stats(latency);
+
         #[allow(clippy::box_default)]
         Self::custom(
             peer_id,
             options,
             light_store,
-            Box::new(ProdIo::new(peer_id, rpc_client, timeout)),
+            Box::new(io),
             Box::new(SystemClock),
             Box::new(ProdVerifier::default()),
             Box::new(scheduler::basic_bisecting_schedule),
@@ -123,10 +175,22 @@ where
             scheduler,
             options,
             predicates,
+            peer_labeler: Arc::new(DefaultPeerLabeler),
+            #[cfg(feature = "rust-crypto")]
+            block_cache: None,
             state: NoTrustedState,
         }
     }
 
+    /// Replace the clock the light client gets the current time from, eg. the system
+    /// clock of `LightClientBuilder::prod` on targets without one.
+    ///
+    /// This must be set before the trusted state, which is checked against the clock.
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Set the given light block as the initial trusted state.
     pub fn trust_light_block(
         mut self,
@@ -177,15 +241,17 @@ where
     }
 
     fn validate(&self, light_block: &LightBlock) -> Result<(), Error> {
-        let header = &light_block.signed_header.header;
-        let now = self.clock.now();
+        let now = self
+            .options
+            .now_override
+            .unwrap_or_else(|| self.clock.now());
 
         self.predicates
-            .is_within_trust_period(header.time, self.options.trusting_period, now)
+            .is_within_trust_period(light_block.time(), self.options.trusting_period, now)
             .map_err(Error::invalid_light_block)?;
 
         self.predicates
-            .is_header_from_past(header.time, self.options.clock_drift, now)
+            .is_header_from_past(light_block.time(), self.options.clock_drift, now)
             .map_err(Error::invalid_light_block)?;
 
         self.predicates
@@ -225,7 +291,14 @@ where
             self.scheduler,
             self.verifier,
             self.io,
-        );
+        )
+        .with_peer_labeler(self.peer_labeler);
+
+        #[cfg(feature = "rust-crypto")]
+        let light_client = match self.block_cache {
+            Some(cache) => light_client.with_block_cache(cache),
+            None => light_client,
+        };
 
         Instance::new(light_client, state)
     }
@@ -1,8 +1,12 @@
 //! Components used by the Light Client.
 
+#[cfg(feature = "rust-crypto")]
+pub mod block_cache;
 pub mod clock;
 pub mod io;
 pub mod scheduler;
+#[cfg(feature = "rust-crypto")]
+pub mod verdict_cache;
 
 // Re-export for backward compatibility
 pub use tendermint_light_client_verifier as verifier;
@@ -0,0 +1,197 @@
+//! Cache of fetched light blocks, keyed by header hash.
+//!
+//! When the same header is fetched more than once, eg. from every witness during fork
+//! detection, its validator sets are bound to it by the `validators_hash` and
+//! `next_validators_hash` of the header, so they only need to be fetched and decoded once.
+//! Light clients hold an `Arc<BlockCache>`, which can be shared between the primary and
+//! the witnesses with [`LightClient::with_block_cache`], and hand it to
+//! [`Io::fetch_light_block_cached`], so that only the signed header is fetched again.
+//!
+//! The cache holds its blocks for as long as a [`BlockCacheScope`] is alive. Every
+//! verification call opens one, and scopes nest: the cache is only cleared once the
+//! outermost one ends, eg. at the end of a whole fork detection run.
+//!
+//! The cache relies on header hashes being unique: it is keyed by [`BlockCacheKey`], the
+//! SHA-256 hash of the header, which commits to its validator sets. Blocks are only
+//! cached once their validator sets are checked against those hashes, so that two blocks
+//! sharing a key share their validator sets, short of a collision of the hash function.
+//! Their commit and provider may differ though, and are not to be taken from the cache.
+//!
+//! [`LightClient::with_block_cache`]: crate::light_client::LightClient::with_block_cache
+//! [`Io::fetch_light_block_cached`]: crate::components::io::Io::fetch_light_block_cached
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::verifier::types::{Hash, LightBlock};
+
+/// The key of a block in a [`BlockCache`]: the hash of its header.
+///
+/// This is the hash computed from the header itself, not the block id found in the
+/// commit, which is only checked against the header once the block is verified.
+pub type BlockCacheKey = Hash;
+
+#[derive(Debug, Default)]
+struct Entries {
+    blocks: HashMap<BlockCacheKey, Arc<LightBlock>>,
+    /// Number of scopes currently open on the cache.
+    scopes: usize,
+}
+
+/// Cache of fetched light blocks, keyed by header hash.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug, Default)]
+pub struct BlockCache {
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+}
+
+impl BlockCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of blocks currently cached.
+    pub fn len(&self) -> usize {
+        self.lock().blocks.len()
+    }
+
+    /// Whether this cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of lookups which found a block in the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Remove all cached blocks, even if a scope is open. The hit counter is left untouched.
+    pub fn clear(&self) {
+        self.lock().blocks.clear();
+    }
+
+    /// Open a scope on this cache, which keeps its blocks until the scope, and any other
+    /// open at the same time, is dropped.
+    pub fn scope(&self) -> BlockCacheScope<'_> {
+        self.lock().scopes += 1;
+        BlockCacheScope(self)
+    }
+
+    /// Look up the block whose header hashes to the given key.
+    ///
+    /// Only the validator sets of the returned block are to be reused. Its commit and
+    /// provider are those of the peer which served it first.
+    pub fn get(&self, key: &BlockCacheKey) -> Option<Arc<LightBlock>> {
+        let light_block = self.lock().blocks.get(key).cloned()?;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+
+        Some(light_block)
+    }
+
+    /// Remember the given block, under the given key.
+    ///
+    /// The key must be the hash of the block's header, as computed by the caller
+    /// when looking the block up, so that it is not computed again. The block is only
+    /// remembered if its validator sets hash to the `validators_hash` and
+    /// `next_validators_hash` of its header, so that those of a tampered block are
+    /// never handed out.
+    pub fn insert(&self, key: BlockCacheKey, light_block: &LightBlock) {
+        let header = &light_block.signed_header.header;
+        if light_block.validators.hash() != header.validators_hash
+            || light_block.next_validators.hash() != header.next_validators_hash
+        {
+            return;
+        }
+
+        self.lock()
+            .blocks
+            .entry(key)
+            .or_insert_with(|| Arc::new(light_block.clone()));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        // The cache holds no invariant which a panic could break, so recover from poisoning.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A scope open on a [`BlockCache`] with [`BlockCache::scope`].
+///
+/// The cache is cleared once the last of its open scopes is dropped.
+#[derive(Debug)]
+pub struct BlockCacheScope<'a>(&'a BlockCache);
+
+impl Drop for BlockCacheScope<'_> {
+    fn drop(&mut self) {
+        let mut entries = self.0.lock();
+        entries.scopes -= 1;
+
+        if entries.scopes == 0 {
+            entries.blocks.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint_testgen::{Generator, LightChain};
+
+    use super::*;
+    use crate::verifier::types::ValidatorSet;
+
+    fn light_block() -> LightBlock {
+        let tm_lb = LightChain::default_with_length(1).light_blocks[0]
+            .generate()
+            .unwrap();
+
+        LightBlock::new(
+            tm_lb.signed_header,
+            tm_lb.validators,
+            tm_lb.next_validators,
+            tm_lb.provider,
+        )
+    }
+
+    #[test]
+    fn blocks_are_cached_until_the_last_scope_ends() {
+        let light_block = light_block();
+        let hash = light_block.signed_header.header.hash();
+
+        let cache = BlockCache::new();
+        assert!(cache.get(&hash).is_none());
+
+        let outer = cache.scope();
+        let inner = cache.scope();
+        cache.insert(hash, &light_block);
+        drop(inner);
+
+        let cached = cache.get(&hash).unwrap();
+        assert_eq!(cached.validators, light_block.validators);
+        assert_eq!(cached.next_validators, light_block.next_validators);
+        assert!(Arc::ptr_eq(&cached, &cache.get(&hash).unwrap()));
+        assert_eq!(cache.hits(), 2);
+
+        drop(outer);
+        assert!(cache.is_empty());
+        assert_eq!(cache.hits(), 2);
+    }
+
+    #[test]
+    fn mismatched_validator_sets_are_not_cached() {
+        let mut light_block = light_block();
+        let hash = light_block.signed_header.header.hash();
+        light_block.next_validators = ValidatorSet::without_proposer(vec![]);
+
+        let cache = BlockCache::new();
+        cache.insert(hash, &light_block);
+        assert!(cache.is_empty());
+    }
+}
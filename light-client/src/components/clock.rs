@@ -1,4 +1,11 @@
 //! Provides an interface and a default implementation of the `Clock` component
+//!
+//! The clock is the only source of the current time of the light client: the trusting
+//! period and clock drift checks get it from their light client's clock, unless
+//! overridden with `Options::now_override`, and the verifier is always given it as
+//! an argument. Targets without a wall clock, eg. embedded ones, can thus supply their
+//! own `Clock`, eg. a monotonic counter plus an offset set at boot, to
+//! `LightClient::new` or to `LightClientBuilder::clock`.
 
 use time::OffsetDateTime;
 
@@ -10,7 +17,7 @@ pub trait Clock: Send + Sync {
     fn now(&self) -> Time;
 }
 
-/// Provides the current wall clock time.
+/// Provides the current wall clock time, as read from the operating system.
 #[derive(Copy, Clone, Debug)]
 pub struct SystemClock;
 impl Clock for SystemClock {
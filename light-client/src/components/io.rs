@@ -1,13 +1,19 @@
 //! Provides an interface and a default implementation of the `Io` component
 
-use std::time::Duration;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use flex_error::{define_error, TraceError};
 use tendermint_rpc as rpc;
 #[cfg(feature = "rpc-client")]
 use tendermint_rpc::Client;
 
-use crate::verifier::types::{Height, LightBlock};
+#[cfg(feature = "rust-crypto")]
+use crate::components::block_cache::BlockCache;
+use crate::verifier::types::{Height, LightBlock, PeerId};
 
 #[cfg(feature = "tokio")]
 type TimeoutError = flex_error::DisplayOnly<tokio::time::error::Elapsed>;
@@ -111,10 +117,73 @@ impl IoErrorDetail {
     }
 }
 
+/// Rolling statistics of the time taken by peers to answer light block requests.
+///
+/// The latency of each peer is an exponentially weighted moving average of the
+/// latencies of its successful responses, where the latest response accounts for 1/8th.
+///
+/// Cloning this value shares the underlying statistics, so that the I/O components
+/// of the primary and of the witnesses can record into the same ones.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyStats {
+    latencies: Arc<Mutex<BTreeMap<PeerId, Duration>>>,
+}
+
+impl LatencyStats {
+    /// Create empty statistics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the given peer took `elapsed` to answer a request.
+    pub fn record(&self, peer: PeerId, elapsed: Duration) {
+        self.lock()
+            .entry(peer)
+            .and_modify(|latency| *latency = (*latency * 7 + elapsed) / 8)
+            .or_insert(elapsed);
+    }
+
+    /// The rolling latency of the given peer, if it answered any request yet.
+    pub fn get(&self, peer: &PeerId) -> Option<Duration> {
+        self.lock().get(peer).copied()
+    }
+
+    /// The rolling latency of every peer which answered a request.
+    pub fn snapshot(&self) -> BTreeMap<PeerId, Duration> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PeerId, Duration>> {
+        // The map holds no invariant which a panic could break, so recover from poisoning.
+        self.latencies.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 /// Interface for fetching light blocks from a full node, typically via the RPC client.
 pub trait Io: Send + Sync {
     /// Fetch a light block at the given height from a peer
     fn fetch_light_block(&self, height: AtHeight) -> Result<LightBlock, IoError>;
+
+    /// Same as [`Io::fetch_light_block`], but reusing the validator sets of a block
+    /// found in the given cache, and remembering those of a block fetched in full.
+    ///
+    /// The default implementation ignores the cache.
+    #[cfg(feature = "rust-crypto")]
+    fn fetch_light_block_cached(
+        &self,
+        height: AtHeight,
+        cache: &BlockCache,
+    ) -> Result<LightBlock, IoError> {
+        let _ = cache;
+        self.fetch_light_block(height)
+    }
+
+    /// The rolling latency of the peers queried by this component, see [`LatencyStats`].
+    ///
+    /// The default implementation does not record latencies, and returns an empty map.
+    fn latencies(&self) -> BTreeMap<PeerId, Duration> {
+        BTreeMap::new()
+    }
 }
 
 impl<F: Send + Sync> Io for F
@@ -131,14 +200,16 @@ pub use self::prod::ProdIo;
 
 #[cfg(feature = "rpc-client")]
 mod prod {
+    use std::time::Instant;
     use tendermint::{
         account::Id as TMAccountId, block::signed_header::SignedHeader as TMSignedHeader,
         validator::Set as TMValidatorSet,
     };
+
     use tendermint_rpc::Paging;
 
     use super::*;
-    use crate::{utils::block_on, verifier::types::PeerId};
+    use crate::utils::block_on;
 
     /// Production implementation of the Io component, which fetches
     /// light blocks from full nodes via RPC.
@@ -147,11 +218,61 @@ mod prod {
         peer_id: PeerId,
         rpc_client: rpc::HttpClient,
         timeout: Option<Duration>,
+        latency: LatencyStats,
     }
 
     impl Io for ProdIo {
         fn fetch_light_block(&self, height: AtHeight) -> Result<LightBlock, IoError> {
+            self.timed(|| self.fetch_light_block_untimed(height, None))
+        }
+
+        fn fetch_light_block_cached(
+            &self,
+            height: AtHeight,
+            cache: &BlockCache,
+        ) -> Result<LightBlock, IoError> {
+            self.timed(|| self.fetch_light_block_untimed(height, Some(cache)))
+        }
+
+        fn latencies(&self) -> BTreeMap<PeerId, Duration> {
+            self.latency.snapshot()
+        }
+    }
+
+    impl ProdIo {
+        fn timed(
+            &self,
+            fetch: impl FnOnce() -> Result<LightBlock, IoError>,
+        ) -> Result<LightBlock, IoError> {
+            let start = Instant::now();
+            let light_block = fetch()?;
+            self.latency.record(self.peer_id, start.elapsed());
+
+            Ok(light_block)
+        }
+
+        fn fetch_light_block_untimed(
+            &self,
+            height: AtHeight,
+            block_cache: Option<&BlockCache>,
+        ) -> Result<LightBlock, IoError> {
             let signed_header = self.fetch_signed_header(height)?;
+
+            // Only hash the header once, to look it up and then to insert it
+            let cache_key = block_cache.map(|_| signed_header.header.hash());
+
+            if let (Some(cache), Some(key)) = (block_cache, &cache_key) {
+                // Keep the commit served by this peer, and attribute the block to it
+                if let Some(cached) = cache.get(key) {
+                    return Ok(LightBlock::new(
+                        signed_header,
+                        cached.validators.clone(),
+                        cached.next_validators.clone(),
+                        self.peer_id,
+                    ));
+                }
+            }
+
             let height = signed_header.header.height;
             let proposer_address = signed_header.header.proposer_address;
 
@@ -165,11 +286,13 @@ mod prod {
                 self.peer_id,
             );
 
+            if let (Some(cache), Some(key)) = (block_cache, cache_key) {
+                cache.insert(key, &light_block);
+            }
+
             Ok(light_block)
         }
-    }
 
-    impl ProdIo {
         /// Constructs a new ProdIo component.
         ///
         /// A peer map which maps peer IDS to their network address must be supplied.
@@ -183,9 +306,17 @@ mod prod {
                 peer_id,
                 rpc_client,
                 timeout,
+                latency: LatencyStats::new(),
             }
         }
 
+        /// Record the latency of the peer into the given statistics, eg. to share
+        /// them with the I/O components of other peers.
+        pub fn with_latency_stats(mut self, latency: LatencyStats) -> Self {
+            self.latency = latency;
+            self
+        }
+
         pub fn peer_id(&self) -> PeerId {
             self.peer_id
         }
@@ -243,3 +374,31 @@ mod prod {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_is_a_moving_average_per_peer() {
+        let a: PeerId = "BADFADAD0BEFEEDC0C0ADEADBEEFC0FFEEFACADE".parse().unwrap();
+        let b: PeerId = "CEFEEDBADFADAD0C0CEEFACADE0ADEADBEEFC0FF".parse().unwrap();
+
+        let stats = LatencyStats::new();
+        let shared = stats.clone();
+        assert_eq!(stats.get(&a), None);
+
+        stats.record(a, Duration::from_millis(80));
+        shared.record(a, Duration::from_millis(160));
+        stats.record(b, Duration::from_millis(10));
+
+        assert_eq!(stats.get(&a), Some(Duration::from_millis(90)));
+        assert_eq!(
+            shared.snapshot(),
+            BTreeMap::from([
+                (a, Duration::from_millis(90)),
+                (b, Duration::from_millis(10)),
+            ])
+        );
+    }
+}
@@ -2,7 +2,10 @@
 
 use contracts::*;
 
-use crate::{store::LightStore, verifier::types::Height};
+use crate::{
+    store::LightStore,
+    verifier::{bisection_midpoint, types::Height},
+};
 
 /// The scheduler decides what block to verify next given the current and target heights.
 ///
@@ -121,7 +124,5 @@ pub fn valid_schedule(
 #[requires(low <= high)]
 #[ensures(low <= ret && ret <= high)]
 fn midpoint(low: Height, high: Height) -> Height {
-    (low.value() + (high.value() + 1 - low.value()) / 2)
-        .try_into()
-        .unwrap() // Will panic if midpoint is higher than i64::MAX
+    bisection_midpoint(low, high)
 }
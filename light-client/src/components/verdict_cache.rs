@@ -0,0 +1,367 @@
+//! Bounded LRU cache of verification verdicts.
+//!
+//! Verifying a target block against a trusted block is a pure function of the two
+//! blocks, the verification options and the current time. Re-running it is wasteful
+//! when the light client is asked to verify the same pair of blocks repeatedly, eg. by
+//! a relayer polling for the same headers. The [`VerdictCache`] remembers the outcome of
+//! past verifications, keyed by the pair of `(trusted_hash, target_hash)` header hashes
+//! along with the hashes of everything else the verdict depends on: the validator sets
+//! of both blocks and the commit of the target block. A block whose header was seen
+//! before, but which comes with other validators or signatures, is thus verified again.
+//!
+//! Only `Success` and `NotEnoughTrust` verdicts are cached: an `Invalid` verdict may
+//! depend on the current time (eg. a header from the future), and is therefore always
+//! recomputed. A cached entry is discarded as soon as the trusting period of its
+//! trusted block has lapsed.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use tendermint::{
+    block::Commit,
+    crypto::{default::Sha256, Sha256 as _},
+    Hash,
+};
+
+use crate::verifier::{
+    operations::VotingPowerTally,
+    types::{checked_sub, LightBlock, Time},
+    Verdict,
+};
+
+/// Key of a cache entry, made of the hashes of every part of the
+/// trusted and target blocks which the verdict depends on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    trusted_hash: Hash,
+    trusted_next_validators_hash: Hash,
+    target_hash: Hash,
+    target_validators_hash: Hash,
+    target_next_validators_hash: Hash,
+    target_commit_hash: Hash,
+}
+
+/// The subset of verdicts which can be cached.
+#[derive(Clone, Debug)]
+enum CachedVerdict {
+    Success,
+    NotEnoughTrust(VotingPowerTally),
+}
+
+impl From<&CachedVerdict> for Verdict {
+    fn from(cached: &CachedVerdict) -> Self {
+        match cached {
+            CachedVerdict::Success => Verdict::Success,
+            CachedVerdict::NotEnoughTrust(tally) => Verdict::NotEnoughTrust(*tally),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    verdict: CachedVerdict,
+    trusted_time: Time,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<Key, Entry>,
+    tick: u64,
+}
+
+/// Hit/miss counters of a [`VerdictCache`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerdictCacheStats {
+    /// Number of lookups which returned a cached verdict.
+    pub hits: u64,
+    /// Number of lookups which did not find a (valid) cached verdict.
+    pub misses: u64,
+}
+
+/// Bounded LRU cache of verification verdicts, keyed by the trusted and
+/// target blocks.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct VerdictCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl VerdictCache {
+    /// Create a new cache holding at most `capacity` verdicts.
+    ///
+    /// A cache with a capacity of zero never stores anything.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Maximum number of verdicts held by this cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of verdicts currently held by this cache.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Whether this cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Current hit/miss counters.
+    pub fn stats(&self) -> VerdictCacheStats {
+        VerdictCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Remove all cached verdicts. The hit/miss counters are left untouched.
+    ///
+    /// This must be called whenever the verification options change,
+    /// as cached verdicts were computed with the previous options.
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// Look up the verdict of verifying `target` against `trusted`.
+    ///
+    /// Returns `None`, and evicts the entry, if the trusting period of the
+    /// trusted block has lapsed relative to `now`.
+    pub fn get(
+        &self,
+        trusted: &LightBlock,
+        target: &LightBlock,
+        trusting_period: Duration,
+        now: Time,
+    ) -> Option<Verdict> {
+        let key = key(trusted, target);
+        let mut inner = self.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let verdict = match inner.entries.get_mut(&key) {
+            Some(entry) if within_trust_period(entry.trusted_time, trusting_period, now) => {
+                entry.last_used = tick;
+                Some(Verdict::from(&entry.verdict))
+            },
+            Some(_) => {
+                inner.entries.remove(&key);
+                None
+            },
+            None => None,
+        };
+
+        let counter = if verdict.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        verdict
+    }
+
+    /// Record the verdict of verifying `target` against `trusted`,
+    /// evicting the least recently used entry if the cache is full.
+    ///
+    /// `Invalid` verdicts are not cached.
+    pub fn insert(&self, trusted: &LightBlock, target: &LightBlock, verdict: &Verdict) {
+        let verdict = match verdict {
+            Verdict::Success => CachedVerdict::Success,
+            Verdict::NotEnoughTrust(tally) => CachedVerdict::NotEnoughTrust(*tally),
+            Verdict::Invalid(_) => return,
+        };
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = key(trusted, target);
+        let mut inner = self.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            let lru = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+
+            if let Some(lru) = lru {
+                inner.entries.remove(&lru);
+            }
+        }
+
+        inner.entries.insert(
+            key,
+            Entry {
+                verdict,
+                trusted_time: trusted.time(),
+                last_used: tick,
+            },
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // The cache holds no invariant which a panic could break, so recover from poisoning.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn key(trusted: &LightBlock, target: &LightBlock) -> Key {
+    Key {
+        trusted_hash: trusted.signed_header.header.hash(),
+        trusted_next_validators_hash: trusted.next_validators.hash(),
+        target_hash: target.signed_header.header.hash(),
+        target_validators_hash: target.validators.hash(),
+        target_next_validators_hash: target.next_validators.hash(),
+        target_commit_hash: commit_hash(&target.signed_header.commit),
+    }
+}
+
+/// Hash of the CBOR encoding of a commit, which covers all of its signatures.
+fn commit_hash(commit: &Commit) -> Hash {
+    let bytes = serde_cbor::to_vec(commit).expect("a commit is always serializable");
+    Hash::Sha256(Sha256::digest(bytes))
+}
+
+fn within_trust_period(header_time: Time, trusting_period: Duration, now: Time) -> bool {
+    matches!(checked_sub(now, trusting_period), Ok(start) if header_time > start)
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint_testgen::{light_block::TmLightBlock as TGLightBlock, Generator, LightChain};
+
+    use super::*;
+    use crate::verifier::{errors::VerificationError, types::TrustThreshold};
+
+    fn testgen_to_lb(tm_lb: TGLightBlock) -> LightBlock {
+        LightBlock {
+            signed_header: tm_lb.signed_header,
+            validators: tm_lb.validators,
+            next_validators: tm_lb.next_validators,
+            provider: tm_lb.provider,
+        }
+    }
+
+    fn blocks(n: u64) -> Vec<LightBlock> {
+        LightChain::default_with_length(n)
+            .light_blocks
+            .into_iter()
+            .map(|lb| testgen_to_lb(lb.generate().unwrap()))
+            .collect()
+    }
+
+    const TRUSTING_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn hits_and_misses_are_counted() {
+        let blocks = blocks(3);
+        let now = blocks[2].time();
+        let cache = VerdictCache::new(8);
+
+        assert_eq!(
+            cache.get(&blocks[0], &blocks[2], TRUSTING_PERIOD, now),
+            None
+        );
+
+        cache.insert(&blocks[0], &blocks[2], &Verdict::Success);
+        assert_eq!(
+            cache.get(&blocks[0], &blocks[2], TRUSTING_PERIOD, now),
+            Some(Verdict::Success)
+        );
+
+        // The key is ordered: swapping the blocks is a different entry
+        assert_eq!(
+            cache.get(&blocks[2], &blocks[0], TRUSTING_PERIOD, now),
+            None
+        );
+
+        assert_eq!(cache.stats(), VerdictCacheStats { hits: 1, misses: 2 });
+    }
+
+    #[test]
+    fn invalid_verdicts_are_not_cached() {
+        let blocks = blocks(2);
+        let cache = VerdictCache::new(8);
+
+        let tally = VotingPowerTally {
+            total: 100,
+            tallied: 10,
+            trust_threshold: TrustThreshold::ONE_THIRD,
+        };
+        let invalid = VerificationError::not_enough_trust(tally);
+        cache.insert(
+            &blocks[0],
+            &blocks[1],
+            &Verdict::Invalid(invalid.into_detail()),
+        );
+        assert!(cache.is_empty());
+
+        cache.insert(&blocks[0], &blocks[1], &Verdict::NotEnoughTrust(tally));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let blocks = blocks(4);
+        let now = blocks[3].time();
+        let cache = VerdictCache::new(2);
+
+        cache.insert(&blocks[0], &blocks[1], &Verdict::Success);
+        cache.insert(&blocks[0], &blocks[2], &Verdict::Success);
+
+        // Touch the first entry, so that the second one becomes the LRU
+        assert!(cache
+            .get(&blocks[0], &blocks[1], TRUSTING_PERIOD, now)
+            .is_some());
+
+        cache.insert(&blocks[0], &blocks[3], &Verdict::Success);
+        assert_eq!(cache.len(), 2);
+        assert!(cache
+            .get(&blocks[0], &blocks[1], TRUSTING_PERIOD, now)
+            .is_some());
+        assert!(cache
+            .get(&blocks[0], &blocks[2], TRUSTING_PERIOD, now)
+            .is_none());
+        assert!(cache
+            .get(&blocks[0], &blocks[3], TRUSTING_PERIOD, now)
+            .is_some());
+    }
+
+    #[test]
+    fn entries_expire_with_the_trusting_period() {
+        let blocks = blocks(2);
+        let trusted_time = blocks[0].time();
+        let cache = VerdictCache::new(8);
+
+        cache.insert(&blocks[0], &blocks[1], &Verdict::Success);
+
+        let later = (trusted_time + TRUSTING_PERIOD).unwrap();
+        assert_eq!(
+            cache.get(&blocks[0], &blocks[1], TRUSTING_PERIOD, later),
+            None
+        );
+        assert!(cache.is_empty());
+    }
+}
@@ -3,8 +3,9 @@
 use std::time::Duration;
 
 use crate::{
-    store::LightStore,
-    verifier::types::{Height, LightBlock, Status, Time},
+    errors::Error,
+    store::{HeightRange, LightStore},
+    verifier::types::{checked_sub, Height, LightBlock, Status, Time},
 };
 
 /// Whether or not the given light store contains a verified or
@@ -17,17 +18,114 @@ pub fn trusted_store_contains_block_at_target_height(
         || light_store.get(target_height, Status::Trusted).is_some()
 }
 
+/// Whether or not the given light store contains a verified or trusted
+/// block at every height of the given range, bounds included.
+pub fn trusted_store_contains_contiguous_range(
+    light_store: &dyn LightStore,
+    range: HeightRange,
+) -> bool {
+    range
+        .heights()
+        .all(|height| trusted_store_contains_block_at_target_height(light_store, height))
+}
+
+/// Whether or not the `target` block immediately follows the `trusted` block,
+/// ie. whether it can be verified sequentially rather than by skipping.
+pub fn is_adjacent(trusted: &LightBlock, target: &LightBlock) -> bool {
+    target
+        .as_untrusted_state()
+        .is_adjacent_to(&trusted.as_trusted_state())
+}
+
+/// Check that the `higher` block links to the `lower` one through its `last_block_id`,
+/// as when verifying adjacent blocks with [`Options::adjacent_only`], eg. to check the
+/// integrity of a light store with the same logic.
+///
+/// The hash of the `lower` block is computed from its header, rather than taken from the
+/// block id of its commit, so that a block whose commit was never checked against its
+/// header still has to match. The heights of the blocks are not checked, and must be
+/// consecutive for the check to be meaningful.
+///
+/// ## Error conditions
+/// - `MissingLastBlockId`, with the height of the `higher` block, if it has no `last_block_id`
+/// - `InvalidAdjacentHeaders`, with the hash of the `lower` header and then the
+///   `last_block_id` hash of the `higher` one, if they differ
+///
+/// [`Options::adjacent_only`]: crate::light_client::Options::adjacent_only
+#[cfg(feature = "rust-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+pub fn adjacent_blocks_chain(lower: &LightBlock, higher: &LightBlock) -> Result<(), Error> {
+    let lower_hash = lower.signed_header.header.hash();
+    match higher.signed_header.header.last_block_id {
+        Some(last_block_id) if last_block_id.hash == lower_hash => Ok(()),
+        Some(last_block_id) => Err(Error::invalid_adjacent_headers(
+            lower_hash,
+            last_block_id.hash,
+        )),
+        None => Err(Error::missing_last_block_id(higher.height())),
+    }
+}
+
+/// Outcome of checking whether a block is within the trusting period.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrustPeriodVerdict {
+    /// The block is within the trusting period.
+    Valid,
+    /// The trusting period of the block has lapsed.
+    Expired,
+    /// The start of the trusting period could not be computed,
+    /// ie. `now - trusting_period` is not a valid time.
+    HeaderInFuture,
+}
+
+/// Check whether the given block is within the given trusting period,
+/// relative to the given time, and report why it is not.
+pub fn trust_period_verdict(
+    light_block: &LightBlock,
+    trusting_period: Duration,
+    now: Time,
+) -> TrustPeriodVerdict {
+    let header_time = light_block.time();
+    match checked_sub(now, trusting_period) {
+        Ok(start) if header_time > start => TrustPeriodVerdict::Valid,
+        Ok(_) => TrustPeriodVerdict::Expired,
+        Err(_) => TrustPeriodVerdict::HeaderInFuture,
+    }
+}
+
 /// Whether or not the given block is within the given trusting period,
 /// relative to the given time.
+///
+/// See `trust_period_verdict`.
 pub fn is_within_trust_period(
     light_block: &LightBlock,
     trusting_period: Duration,
     now: Time,
 ) -> bool {
-    let header_time = light_block.signed_header.header.time;
-    match now - trusting_period {
-        Ok(start) => header_time > start,
-        Err(_) => false,
+    trust_period_verdict(light_block, trusting_period, now) == TrustPeriodVerdict::Valid
+}
+
+/// Whether the trust in the given block should be refreshed, ie. whether its age
+/// relative to `now` exceeds `refresh_fraction` of the trusting period.
+///
+/// This lets a scheduler re-verify proactively, before the trusting period lapses.
+/// A block whose time is after `now` is never due for a refresh.
+///
+/// ## Error conditions
+/// - `InvalidRefreshFraction`, if `refresh_fraction` is not within `(0, 1]`
+pub fn should_refresh_trust(
+    light_block: &LightBlock,
+    trusting_period: Duration,
+    refresh_fraction: f64,
+    now: Time,
+) -> Result<bool, Error> {
+    if !(refresh_fraction > 0.0 && refresh_fraction <= 1.0) {
+        return Err(Error::invalid_refresh_fraction(refresh_fraction));
+    }
+
+    match now.duration_since(light_block.time()) {
+        Ok(age) => Ok(age > trusting_period.mul_f64(refresh_fraction)),
+        Err(_) => Ok(false),
     }
 }
 
@@ -9,10 +9,11 @@ use flex_error::{define_error, DisplayError, TraceError};
 pub use crate::verifier::errors::ErrorExt;
 use crate::{
     components::io::IoError,
+    peer_labeler::PeerLabeler,
     verifier::{
         errors::VerificationErrorDetail,
         operations::voting_power::VotingPowerTally,
-        options::Options,
+        options::OptionsSnapshot,
         types::{Hash, Height, LightBlock, PeerId, Status},
     },
 };
@@ -40,10 +41,13 @@ define_error! {
             | _ | { "no witnesses left" },
 
         ForkDetected
-            { peers: Vec<PeerId> }
+            {
+                peers: Vec<PeerId>,
+                labels: Vec<String>,
+            }
             | e | {
-                format_args!("fork detected peers={0:?}",
-                    e.peers)
+                format_args!("fork detected peers=[{0}]",
+                    e.labels.join(", "))
             },
 
         NoInitialTrustedState
@@ -56,6 +60,15 @@ define_error! {
                     e.status)
             },
 
+        NotInStore
+            {
+                height: Height,
+            }
+            | e | {
+                format_args!("no light block at height {0} in the light store",
+                    e.height)
+            },
+
         TargetLowerThanTrustedState
             {
                 target_height: Height,
@@ -66,6 +79,16 @@ define_error! {
                     e.target_height, e.trusted_height)
             },
 
+        BelowMinHeight
+            {
+                target: Height,
+                min: Height,
+            }
+            | e | {
+                format_args!("target height ({0}) is below the minimum verifiable height ({1})",
+                    e.target, e.min)
+            },
+
         HeightTooHigh
             {
                 height: Height,
@@ -76,13 +99,34 @@ define_error! {
                     e.height, e.latest_height)
             },
 
+        CommitHeightMismatch
+            {
+                header_height: Height,
+                commit_height: Height,
+            }
+            | e | {
+                format_args!("header at height {0} comes with a commit for height {1}",
+                    e.header_height, e.commit_height)
+            },
+
+        PrefetchedHeightMismatch
+            {
+                target_height: Height,
+                height: Height,
+            }
+            | e | {
+                format_args!("pre-fetched block at height {1} does not match target height {0}",
+                    e.target_height, e.height)
+            },
+
         TrustedStateOutsideTrustingPeriod
             {
                 trusted_state: Box<LightBlock>,
-                options: Options,
+                options: OptionsSnapshot,
             }
-            | _ | {
-                format_args!("trusted state outside of trusting period")
+            | e | {
+                format_args!("trusted state outside of trusting period ({})",
+                    e.options)
             },
 
         BisectionFailed
@@ -95,9 +139,80 @@ define_error! {
                     e.target_height, e.trusted_height)
             },
 
+        BisectionStepLimitExceeded
+            {
+                target_height: Height,
+                steps: usize,
+            }
+            | e | {
+                format_args!("bisection for target at height {0} exceeded the limit of {1} steps",
+                    e.target_height, e.steps)
+            },
+
+        NoProgress
+            {
+                stalled_at: Height,
+                iterations: usize,
+            }
+            | e | {
+                format_args!("verification made no progress from trusted state at height {0} for {1} iterations",
+                    e.stalled_at, e.iterations)
+            },
+
+        StaleCursor
+            {
+                target_height: Height,
+                trusted_height: Height,
+            }
+            | e | {
+                format_args!("verification cursor towards target at height {0} from trusted state at height {1} does not match the light store",
+                    e.target_height, e.trusted_height)
+            },
+
         InvalidLightBlock
+            {
+                provider: PeerId,
+                provider_label: String,
+            }
             [ DisplayError<VerificationErrorDetail> ]
-            | _ | { "invalid light block" },
+            | e | {
+                format_args!("invalid light block provided by peer {0}",
+                    e.provider_label)
+            },
+
+        TrustedHashMismatch
+            {
+                expected: Hash,
+                got: Hash,
+            }
+            | e | {
+                format_args!("trusted block does not have the trusted hash: expected={0} got={1}",
+                    e.expected, e.got)
+            },
+
+        ValidatorSetHashMismatch
+            {
+                provider: PeerId,
+                provider_label: String,
+                header_hash: Hash,
+                computed_hash: Hash,
+            }
+            | e | {
+                format_args!("validator set provided by peer {0} does not match the header: header validators_hash={1} computed hash={2}",
+                    e.provider_label, e.header_hash, e.computed_hash)
+            },
+
+        ValidatorCountMismatch
+            {
+                provider: PeerId,
+                provider_label: String,
+                expected: usize,
+                got: usize,
+            }
+            | e | {
+                format_args!("validator set provided by peer {0} is truncated or padded: expected {1} validators, got {2}",
+                    e.provider_label, e.expected, e.got)
+            },
 
         InvalidAdjacentHeaders
             {
@@ -116,6 +231,23 @@ define_error! {
                     e.height)
             },
 
+        InvertedHeightRange
+            {
+                from: Height,
+                to: Height,
+            }
+            | e | {
+                format_args!("invalid height range: from ({0}) is greater than to ({1})",
+                    e.from, e.to)
+            },
+
+        InvalidRefreshFraction
+            { fraction: f64 }
+            | e | {
+                format_args!("refresh fraction must be within (0, 1], got {0}",
+                    e.fraction)
+            },
+
         ChannelDisconnected
             | _ | { "internal channel disconnected" },
 
@@ -127,6 +259,28 @@ define_error! {
             [ TraceError<serde_cbor::Error> ]
             | _ | { "serde cbor error" },
 
+        SerdeJson
+            [ TraceError<serde_json::Error> ]
+            | _ | { "serde json error" },
+
+        Parse
+            { what: String }
+            [ TraceError<serde_json::Error> ]
+            | e | {
+                format_args!("failed to parse {0} from JSON",
+                    e.what)
+            },
+
+        StoreFormatMismatch
+            {
+                requested: String,
+                found: String,
+            }
+            | e | {
+                format_args!("store was written with format {0} but was opened with format {1}",
+                    e.found, e.requested)
+            },
+
     }
 }
 
@@ -173,4 +327,38 @@ impl Error {
     pub fn recv(_e: crossbeam::RecvError) -> Error {
         Error::channel_disconnected()
     }
+
+    /// Build the error for a light block which failed verification.
+    ///
+    /// A validator set which does not have as many validators as the commit has signatures
+    /// is reported as `ValidatorCountMismatch`, one which does not hash to the header's
+    /// `validators_hash` as `ValidatorSetHashMismatch`, and any other failure as
+    /// `InvalidLightBlock`, all along with the peer which provided the block, labeled by
+    /// the given labeler.
+    pub fn verification_failed(
+        labeler: &dyn PeerLabeler,
+        provider: PeerId,
+        e: VerificationErrorDetail,
+    ) -> Error {
+        let label = labeler.label(&provider);
+
+        match e {
+            VerificationErrorDetail::ValidatorCountMismatch(e) => {
+                Error::validator_count_mismatch(provider, label, e.expected, e.got)
+            },
+            VerificationErrorDetail::InvalidValidatorSet(e) => Error::validator_set_hash_mismatch(
+                provider,
+                label,
+                e.header_validators_hash,
+                e.validators_hash,
+            ),
+            e => Error::invalid_light_block(provider, label, e),
+        }
+    }
+
+    /// Build the error for a fork detected by the given peers, labeled by the given labeler.
+    pub fn fork_detected_by(labeler: &dyn PeerLabeler, peers: Vec<PeerId>) -> Error {
+        let labels = peers.iter().map(|peer| labeler.label(peer)).collect();
+        Error::fork_detected(peers, labels)
+    }
 }
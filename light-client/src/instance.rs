@@ -1,12 +1,14 @@
 //! Supervisor and Handle implementation.
 
+use std::{collections::BTreeMap, time::Duration};
+
 use tendermint::block::Height;
 
 use crate::{
     errors::Error,
     light_client::LightClient,
     state::State,
-    verifier::types::{LightBlock, Status},
+    verifier::types::{LightBlock, PeerId, Status},
 };
 
 /// A light client `Instance` packages a `LightClient` together with its `State`.
@@ -33,6 +35,12 @@ impl Instance {
         &self.light_client.peer
     }
 
+    /// The rolling latency of the peers queried by this instance,
+    /// see [`LightClient::witness_latencies`].
+    pub fn witness_latencies(&self) -> BTreeMap<PeerId, Duration> {
+        self.light_client.witness_latencies()
+    }
+
     /// Get the latest trusted block.
     pub fn latest_trusted(&self) -> Option<LightBlock> {
         self.state.light_store.highest(Status::Trusted)
@@ -15,14 +15,19 @@
 
 //! See the `light_client` module for the main documentation.
 
+pub mod backoff;
 pub mod builder;
 pub mod components;
 pub mod contracts;
 pub mod errors;
 pub mod instance;
 pub mod light_client;
+pub mod peer_labeler;
 pub mod state;
 pub mod store;
+#[cfg(feature = "rust-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+pub mod trusted_json;
 
 pub(crate) mod utils;
 
@@ -2,23 +2,90 @@
 //!
 //! [1]: https://github.com/informalsystems/tendermint-rs/blob/main/docs/spec/lightclient/verification/verification.md
 
-use core::fmt;
+use core::{fmt, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Instant,
+};
 
 use contracts::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "rust-crypto")]
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+#[cfg(feature = "rust-crypto")]
+use crate::components::{block_cache::BlockCache, verdict_cache::VerdictCache};
 // Re-export for backward compatibility
 pub use crate::verifier::options::Options;
 use crate::{
     components::{clock::Clock, io::*, scheduler::*},
     contracts::*,
-    errors::Error,
+    errors::{Error, ErrorDetail},
+    peer_labeler::{DefaultPeerLabeler, PeerLabeler},
     state::State,
+    store::{memory::MemoryStore, overlay::OverlayStore, FailureReason, HeightRange, LightStore},
     verifier::{
-        types::{Height, LightBlock, PeerId, Status},
+        errors::{ErrorExt, VerificationError},
+        operations::VotingPowerTally,
+        types::{
+            Hash, Height, LightBlock, PeerId, SignedHeader, Status, Time, TrustThreshold,
+            ValidatorSet,
+        },
         Verdict, Verifier,
     },
 };
 
+/// Statistics about a successful verification, as returned by
+/// [`LightClient::verify_to_target_with_stats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyStats {
+    /// Number of verification steps performed, ie. the number of light blocks
+    /// checked against a trusted state, either by bisection or by hash-linking.
+    pub steps: usize,
+    /// Number of light blocks which had to be fetched from the primary,
+    /// as opposed to being found in the light store.
+    pub io_requests: usize,
+    /// Sum of the voting power which signed each block verified by bisection, out of
+    /// the validator set of that block, as reported by the verifier.
+    ///
+    /// Blocks whose verdict came from the verdict cache, or checked by hash-linking,
+    /// are not counted, as no signature was verified for them.
+    pub total_voting_power_verified: u64,
+    /// Wall clock time spent verifying the target block.
+    pub elapsed: Duration,
+}
+
+/// Progress of a forward verification towards a target block, as reported by
+/// [`LightClient::verify_to_target_resumable`] after each step.
+///
+/// A cursor can be persisted, eg. with [`VerifyCursor::to_bytes`], to resume the
+/// verification after a restart, instead of bisecting all over again.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyCursor {
+    /// Height of the block being verified.
+    pub target_height: Height,
+    /// Height of the trusted or verified block the next step is verified against.
+    pub trusted_height: Height,
+    /// Hash of the trusted or verified block the next step is verified against.
+    pub trusted_hash: Hash,
+    /// Height of the next block to verify, as picked by the scheduler.
+    pub current_height: Height,
+}
+
+impl VerifyCursor {
+    /// Serialize this cursor with CBOR.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        serde_cbor::to_vec(self).map_err(Error::serde_cbor)
+    }
+
+    /// Deserialize a cursor previously serialized with [`VerifyCursor::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        serde_cbor::from_slice(bytes).map_err(Error::serde_cbor)
+    }
+}
+
 /// The light client implements a read operation of a header from the blockchain,
 /// by communicating with full nodes. As full nodes may be faulty, it cannot trust
 /// the received information, but the light client has to check whether the header
@@ -39,6 +106,14 @@ pub struct LightClient {
     scheduler: Box<dyn Scheduler>,
     verifier: Box<dyn Verifier>,
     io: Box<dyn Io>,
+    peer_labeler: Arc<dyn PeerLabeler>,
+    #[cfg(feature = "rust-crypto")]
+    verdict_cache: Option<VerdictCache>,
+    #[cfg(feature = "rust-crypto")]
+    block_cache: Arc<BlockCache>,
+    /// Number of dry runs in progress, during which no verdict is cached.
+    #[cfg(feature = "rust-crypto")]
+    dry_runs: AtomicUsize,
 }
 
 impl fmt::Debug for LightClient {
@@ -67,6 +142,13 @@ impl LightClient {
             scheduler: Box::new(scheduler),
             verifier: Box::new(verifier),
             io: Box::new(io),
+            peer_labeler: Arc::new(DefaultPeerLabeler),
+            #[cfg(feature = "rust-crypto")]
+            verdict_cache: None,
+            #[cfg(feature = "rust-crypto")]
+            block_cache: Arc::new(BlockCache::new()),
+            #[cfg(feature = "rust-crypto")]
+            dry_runs: AtomicUsize::new(0),
         }
     }
 
@@ -86,17 +168,155 @@ impl LightClient {
             scheduler,
             verifier,
             io,
+            peer_labeler: Arc::new(DefaultPeerLabeler),
+            #[cfg(feature = "rust-crypto")]
+            verdict_cache: None,
+            #[cfg(feature = "rust-crypto")]
+            block_cache: Arc::new(BlockCache::new()),
+            #[cfg(feature = "rust-crypto")]
+            dry_runs: AtomicUsize::new(0),
         }
     }
 
+    /// Show peers with the given labeler in the errors and log lines of this light client,
+    /// rather than with [`DefaultPeerLabeler`].
+    pub fn with_peer_labeler(mut self, labeler: Arc<dyn PeerLabeler>) -> Self {
+        self.peer_labeler = labeler;
+        self
+    }
+
+    /// The labeler showing peers in the errors and log lines of this light client.
+    pub fn peer_labeler(&self) -> &dyn PeerLabeler {
+        self.peer_labeler.as_ref()
+    }
+
+    /// Cache the verdicts of verification steps in the given [`VerdictCache`],
+    /// so that verifying the same pair of blocks again skips the verifier.
+    ///
+    /// The cache must be cleared if the `options` of this light client change.
+    #[cfg(feature = "rust-crypto")]
+    pub fn with_verdict_cache(mut self, cache: VerdictCache) -> Self {
+        self.verdict_cache = Some(cache);
+        self
+    }
+
+    /// The verdict cache of this light client, if any.
+    #[cfg(feature = "rust-crypto")]
+    pub fn verdict_cache(&self) -> Option<&VerdictCache> {
+        self.verdict_cache.as_ref()
+    }
+
+    /// Share the given block cache with other light clients, eg. those of the primary and
+    /// the witnesses during fork detection, so that a block fetched from one of them has
+    /// its validator sets reused when fetched from the others.
+    #[cfg(feature = "rust-crypto")]
+    pub fn with_block_cache(mut self, cache: Arc<BlockCache>) -> Self {
+        self.block_cache = cache;
+        self
+    }
+
+    /// The block cache of this light client, see [`LightClient::with_block_cache`].
+    #[cfg(feature = "rust-crypto")]
+    pub fn block_cache(&self) -> &Arc<BlockCache> {
+        &self.block_cache
+    }
+
+    /// Number of blocks whose validator sets were reused from the [`BlockCache`] of this
+    /// client, rather than fetched again, since it was created. This counts the hits of
+    /// every light client sharing the cache.
+    ///
+    /// The cache only holds the blocks fetched while a verification call, or another
+    /// [`BlockCacheScope`], is in progress, and is cleared once the last one ends.
+    ///
+    /// [`BlockCacheScope`]: crate::components::block_cache::BlockCacheScope
+    #[cfg(feature = "rust-crypto")]
+    pub fn block_cache_hits(&self) -> u64 {
+        self.block_cache.hits()
+    }
+
+    /// The rolling latency of the peers queried by the I/O component of this client.
+    ///
+    /// This only covers the peer of this client, unless its I/O component shares its
+    /// [`LatencyStats`] with those of other clients, eg. the witnesses, as when they are
+    /// all built with the same statistics through
+    /// [`LightClientBuilder::prod_with_latency_stats`].
+    ///
+    /// [`LatencyStats`]: crate::components::io::LatencyStats
+    /// [`LightClientBuilder::prod_with_latency_stats`]: crate::builder::LightClientBuilder::prod_with_latency_stats
+    pub fn witness_latencies(&self) -> BTreeMap<PeerId, Duration> {
+        self.io.latencies()
+    }
+
+    /// Seed the light store with the block of the primary node at the given height, whose
+    /// header must hash to the given hash, as the initial trusted state.
+    ///
+    /// This is the secure way to bootstrap trust from a height and header hash obtained
+    /// out of band. The validator sets of the block are checked against the hashes of its
+    /// header, which commits to them, and the block must be within the trusting period. It
+    /// is then stored with the `Trusted` status, and returned.
+    ///
+    /// ## Error conditions
+    /// - The block cannot be fetched from the primary node
+    /// - The header hash of the fetched block is not the given hash
+    /// - The validator sets of the fetched block do not match its header
+    /// - The fetched block is outside of the trusting period [LCV-INV-TP.1]
+    #[cfg(feature = "rust-crypto")]
+    pub fn bootstrap(
+        &self,
+        trusted_height: Height,
+        trusted_hash: Hash,
+        state: &mut State,
+    ) -> Result<LightBlock, Error> {
+        let light_block = self
+            .io
+            .fetch_light_block(AtHeight::At(trusted_height))
+            .map_err(Error::io)?;
+
+        let header = &light_block.signed_header.header;
+        let header_hash = header.hash();
+        if header_hash != trusted_hash {
+            return Err(Error::trusted_hash_mismatch(trusted_hash, header_hash));
+        }
+
+        for (validators, expected) in [
+            (&light_block.validators, header.validators_hash),
+            (&light_block.next_validators, header.next_validators_hash),
+        ] {
+            if validators.hash() != expected {
+                return Err(Error::validator_set_hash_mismatch(
+                    light_block.provider,
+                    self.peer_labeler.label(&light_block.provider),
+                    expected,
+                    validators.hash(),
+                ));
+            }
+        }
+
+        self.ensure_within_trusting_period(light_block.clone())?;
+
+        state
+            .light_store
+            .insert(light_block.clone(), Status::Trusted);
+
+        Ok(light_block)
+    }
+
     /// Attempt to update the light client to the highest block of the primary node.
     ///
     /// Note: This function delegates the actual work to `verify_to_target`.
+    ///
+    /// Fails with `TrustedStateOutsideTrustingPeriod` before querying the primary node
+    /// if the highest trusted or verified block of the store has expired.
     pub fn verify_to_highest(&mut self, state: &mut State) -> Result<LightBlock, Error> {
-        let target_block = self
-            .io
-            .fetch_light_block(AtHeight::Highest)
-            .map_err(Error::io)?;
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+
+        if let Some(highest) = state.light_store.highest_trusted_or_verified() {
+            self.ensure_within_trusting_period(highest)?;
+        }
+
+        // The target block is fetched again by its height, reusing its validator sets
+        let target_block = self.fetch(AtHeight::Highest)?;
 
         self.verify_to_target(target_block.height(), state)
     }
@@ -131,6 +351,7 @@ impl LightClient {
     /// - If the core verification loop invariant is violated [LCV-INV-TP.1]
     /// - If verification of a light block fails
     /// - If the fetching a light block from the primary node fails
+    /// - If the target height is below [`Options::min_verifiable_height`], before any I/O
     #[allow(clippy::nonminimal_bool)]
     #[ensures(
         ret.is_ok() -> trusted_store_contains_block_at_target_height(
@@ -143,6 +364,153 @@ impl LightClient {
         target_height: Height,
         state: &mut State,
     ) -> Result<LightBlock, Error> {
+        self.verify_to_target_with_stats(target_height, state)
+            .map(|(light_block, _)| light_block)
+    }
+
+    /// Same as [`LightClient::verify_to_target`], but additionally reports how many
+    /// verification steps and I/O requests were needed to reach the target block.
+    pub fn verify_to_target_with_stats(
+        &self,
+        target_height: Height,
+        state: &mut State,
+    ) -> Result<(LightBlock, VerifyStats), Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        let start = Instant::now();
+        let mut stats = VerifyStats::default();
+
+        let light_block = self.verify_to_target_inner(target_height, state, &mut stats)?;
+
+        stats.elapsed = start.elapsed();
+        Ok((light_block, stats))
+    }
+
+    /// Same as [`LightClient::verify_to_target`], but returns every block verified during
+    /// the call, ie. which was not trusted or verified yet and now is, in increasing order
+    /// of height, eg. to ship the whole verified chain to another node.
+    ///
+    /// The blocks which could not be trusted on the way, eg. those tried by bisection
+    /// before finding enough overlap, are left out. The target block comes last, unless it
+    /// was already trusted or verified, in which case nothing is verified and the list is
+    /// empty.
+    pub fn verify_to_target_with_verified_blocks(
+        &self,
+        target_height: Height,
+        state: &mut State,
+    ) -> Result<Vec<LightBlock>, Error> {
+        // Blocks are only verified between the trusted state and the target block
+        let start = state
+            .light_store
+            .highest_trusted_or_verified_before(target_height)
+            .or_else(|| state.light_store.lowest_trusted_or_verified())
+            .map_or(target_height, |lb| lb.height());
+        let span = HeightRange::new(start.min(target_height), start.max(target_height))
+            .expect("the bounds are ordered");
+
+        let before: BTreeSet<Height> = trusted_or_verified_in(state, span)
+            .map(|lb| lb.height())
+            .collect();
+
+        self.verify_to_target(target_height, state)?;
+
+        let after: BTreeMap<Height, LightBlock> = trusted_or_verified_in(state, span)
+            .filter(|lb| !before.contains(&lb.height()))
+            .map(|lb| (lb.height(), lb))
+            .collect();
+
+        Ok(after.into_values().collect())
+    }
+
+    /// Same as [`LightClient::verify_to_target_with_stats`], but without persisting anything:
+    /// neither the target block nor the intermediate blocks fetched and verified on the way
+    /// are written to the light store, nor recorded in the verification trace, and no
+    /// verdict is added to the verdict cache.
+    ///
+    /// The verification runs against an [`OverlayStore`] over the light store of the given
+    /// state, which is restored untouched once it completes, whether it succeeds, fails or
+    /// panics. Returns whether the target block would verify, along with the statistics of
+    /// the verification, even if the block is invalid. After a successful dry run, the
+    /// caller can verify the target for real, eg. with [`LightClient::verify_to_target`].
+    ///
+    /// ## Error conditions
+    /// - As for [`LightClient::verify_to_target`], except for the verification failures
+    ///   reported in the returned verdict
+    pub fn verify_to_target_dry_run(
+        &self,
+        target_height: Height,
+        state: &mut State,
+    ) -> Result<(Verdict, VerifyStats), Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        #[cfg(feature = "rust-crypto")]
+        let _dry_run = DryRun::start(&self.dry_runs);
+        let start = Instant::now();
+        let mut stats = VerifyStats::default();
+
+        let restore = RestoreStore::take(state);
+        let mut overlay = State::new(OverlayStore::new(restore.base.clone()));
+        let result = self.verify_to_target_inner(target_height, &mut overlay, &mut stats);
+        drop(overlay);
+        drop(restore);
+
+        stats.elapsed = start.elapsed();
+        dry_run_verdict(result).map(|verdict| (verdict, stats))
+    }
+
+    /// Verify the block at the given target height against the given trusted block, rather
+    /// than against the best trusted or verified block of a light store, eg. to reproduce
+    /// the verification of a block from a specific trusted state in tests.
+    ///
+    /// The verification runs on a fresh state whose light store only holds the given
+    /// block, as `Trusted`: the blocks fetched on the way are fetched from the primary,
+    /// and nothing is written to any other light store, as they are only as trustworthy as
+    /// the given block. Verification goes forward only.
+    ///
+    /// ## Error conditions
+    /// - The given block is outside of the trusting period [LCV-INV-TP.1]
+    /// - The target height is lower than the height of the given block
+    /// - As for [`LightClient::verify_to_target`]
+    pub fn verify_to_target_from(
+        &self,
+        trusted_block: LightBlock,
+        target_height: Height,
+    ) -> Result<LightBlock, Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        self.ensure_above_min_height(target_height)?;
+
+        if target_height < trusted_block.height() {
+            return Err(Error::target_lower_than_trusted_state(
+                target_height,
+                trusted_block.height(),
+            ));
+        }
+
+        self.ensure_within_trusting_period(trusted_block.clone())?;
+
+        let mut light_store = MemoryStore::new();
+        light_store.insert(trusted_block, Status::Trusted);
+        let mut state = State::new(light_store);
+
+        self.verify_forward(
+            target_height,
+            target_height,
+            &mut state,
+            &mut VerifyStats::default(),
+            &mut |_| {},
+            &mut |_| Ok(()),
+        )
+    }
+
+    fn verify_to_target_inner(
+        &self,
+        target_height: Height,
+        state: &mut State,
+        stats: &mut VerifyStats,
+    ) -> Result<LightBlock, Error> {
+        self.ensure_above_min_height(target_height)?;
+
         // Let's first look in the store to see whether
         // we have already successfully verified this block.
         if let Some(light_block) = state.light_store.get_trusted_or_verified(target_height) {
@@ -157,24 +525,395 @@ impl LightClient {
             .ok_or_else(Error::no_initial_trusted_state)?;
 
         if target_height >= highest.height() {
+            self.ensure_within_trusting_period(highest.clone())?;
+
+            #[cfg(feature = "rust-crypto")]
+            if self.options.adjacent_only {
+                if let Some(light_block) =
+                    self.verify_adjacent_chain(target_height, highest, state, stats)?
+                {
+                    return Ok(light_block);
+                }
+            }
+
             // Perform forward verification with bisection
-            self.verify_forward(target_height, state)
+            self.verify_forward(
+                target_height,
+                target_height,
+                state,
+                stats,
+                &mut |_| {},
+                &mut |_| Ok(()),
+            )
         } else {
             // Perform sequential backward verification
-            self.verify_backward(target_height, state)
+            self.verify_backward_inner(target_height, state, stats)
+        }
+    }
+
+    /// Update the light client to the blocks at each of the given heights, in a single pass.
+    ///
+    /// Targets are verified in increasing order of height, each one starting from the
+    /// closest trusted or verified block below it, which includes the targets verified
+    /// before it and the intermediate blocks verified to reach them. Each hop is thus
+    /// only verified once, rather than once per target. Duplicate heights are verified
+    /// once.
+    ///
+    /// A failure to verify one target does not prevent verifying the others: the result
+    /// of each verification is reported under its height.
+    pub fn verify_to_targets(
+        &self,
+        target_heights: impl IntoIterator<Item = Height>,
+        state: &mut State,
+    ) -> BTreeMap<Height, Result<LightBlock, Error>> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        let target_heights: BTreeSet<Height> = target_heights.into_iter().collect();
+
+        target_heights
+            .into_iter()
+            .map(|target_height| {
+                let result =
+                    self.verify_to_target_inner(target_height, state, &mut VerifyStats::default());
+                (target_height, result)
+            })
+            .collect()
+    }
+
+    /// Update the light client to the block made of the given signed header and validator
+    /// sets, for data sources which deliver them separately.
+    ///
+    /// The parts are assembled into a light block attributed to the given provider, ie.
+    /// the peer they were received from, which is put in the light store with the `Unverified` status, unless a trusted or verified block
+    /// is already stored at its height. It is then verified as with
+    /// [`LightClient::verify_to_target`], so that any intermediate block needed for
+    /// bisection is still fetched from the primary.
+    ///
+    /// ## Error conditions
+    /// - The commit of the signed header is not for the height of its header
+    /// - As for [`LightClient::verify_to_target`]
+    pub fn verify_from_parts(
+        &self,
+        signed_header: SignedHeader,
+        validators: ValidatorSet,
+        next_validators: ValidatorSet,
+        provider: PeerId,
+        state: &mut State,
+    ) -> Result<LightBlock, Error> {
+        let height = signed_header.header.height;
+        if signed_header.commit.height != height {
+            return Err(Error::commit_height_mismatch(
+                height,
+                signed_header.commit.height,
+            ));
+        }
+
+        let light_block = LightBlock::new(signed_header, validators, next_validators, provider);
+        self.verify_prefetched(height, light_block, state)
+    }
+
+    /// Update the light client to the given target height, using the given block, eg.
+    /// received from a block stream, instead of fetching it from the primary.
+    ///
+    /// The block is put in the light store with the `Unverified` status, unless a trusted or
+    /// verified block is already stored at its height, and is then verified as with
+    /// [`LightClient::verify_to_target`]. Any intermediate block needed for bisection is
+    /// still fetched from the primary, and the verification trace is recorded as usual, so
+    /// that it can be checked against the witnesses for fork detection.
+    ///
+    /// ## Error conditions
+    /// - The given block is not at the target height
+    /// - As for [`LightClient::verify_to_target`]
+    pub fn verify_prefetched(
+        &self,
+        target_height: Height,
+        light_block: LightBlock,
+        state: &mut State,
+    ) -> Result<LightBlock, Error> {
+        if light_block.height() != target_height {
+            return Err(Error::prefetched_height_mismatch(
+                target_height,
+                light_block.height(),
+            ));
+        }
+
+        if state
+            .light_store
+            .get_trusted_or_verified(target_height)
+            .is_none()
+        {
+            state.light_store.insert(light_block, Status::Unverified);
+        }
+
+        self.verify_to_target(target_height, state)
+    }
+
+    /// Verify again the block of the light store at the given height, against the trusted
+    /// or verified block closest below it, eg. to audit the store after an upgrade which
+    /// tightened the verification rules. No block is fetched from any peer.
+    ///
+    /// The block is verified as of its own time, rather than the current one, so that old
+    /// blocks are not rejected for having left the trusting period since. The verdict cache
+    /// is bypassed. If the block is found invalid, it is demoted to `Status::Failed`. A
+    /// block which cannot be trusted from the one below it is left as is, as it may have
+    /// been verified against another one. A block with none below it is a root of trust,
+    /// of which only the checks not depending on any trusted block are performed, see
+    /// [`Verifier::verify_standalone_header`].
+    ///
+    /// ## Error conditions
+    /// - There is no block at the given height in the light store, or it failed verification
+    pub fn revalidate_stored(&self, height: Height, state: &mut State) -> Result<Verdict, Error> {
+        let (light_block, _) = state
+            .light_store
+            .get_non_failed(height)
+            .ok_or_else(|| Error::not_in_store(height))?;
+
+        let trusted_block = height
+            .value()
+            .checked_sub(1)
+            .and_then(|below| Height::try_from(below).ok())
+            .and_then(|below| state.light_store.highest_trusted_or_verified_before(below));
+
+        let verdict = match trusted_block {
+            Some(trusted_block) => self.verifier.verify_update_header(
+                light_block.as_untrusted_state(),
+                trusted_block.as_trusted_state(),
+                &self.options,
+                light_block.time(),
+            ),
+            None => self
+                .verifier
+                .verify_standalone_header(light_block.as_untrusted_state()),
+        };
+
+        if let Verdict::Invalid(e) = &verdict {
+            self.store_failed(&light_block, e.into(), state);
+        }
+
+        Ok(verdict)
+    }
+
+    /// Update the light client to the block at the given height, by first skipping
+    /// towards it and then verifying the last `adjacent_tail` blocks sequentially.
+    ///
+    /// If the target block is more than `adjacent_tail` blocks above the highest
+    /// trusted or verified block, the block at `target_height - adjacent_tail` is first
+    /// verified with bisection, as with [`LightClient::verify_to_target`]. Each of the
+    /// remaining blocks up to the target is then verified against the block right below
+    /// it. Every block verified along the way is stored with the `Verified` status.
+    ///
+    /// An `adjacent_tail` of zero behaves as [`LightClient::verify_to_target`].
+    ///
+    /// ## Error conditions
+    /// - As for [`LightClient::verify_to_target`], for each of the verifications performed.
+    pub fn verify_to_target_hybrid(
+        &self,
+        target_height: Height,
+        adjacent_tail: u64,
+        state: &mut State,
+    ) -> Result<LightBlock, Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        self.ensure_above_min_height(target_height)?;
+
+        if let Some(light_block) = state.light_store.get_trusted_or_verified(target_height) {
+            return Ok(light_block);
+        }
+
+        let mut stats = VerifyStats::default();
+
+        let highest = state
+            .light_store
+            .highest_trusted_or_verified_before(target_height)
+            .or_else(|| state.light_store.lowest_trusted_or_verified())
+            .ok_or_else(Error::no_initial_trusted_state)?;
+
+        if target_height < highest.height() {
+            return self.verify_backward_inner(target_height, state, &mut stats);
+        }
+
+        self.ensure_within_trusting_period(highest.clone())?;
+
+        // Skip to the first block of the tail, if it is not within reach already
+        let mut current = highest;
+        let tail_start = target_height.value().saturating_sub(adjacent_tail);
+
+        if tail_start > current.height().value() {
+            let tail_start =
+                Height::try_from(tail_start).expect("heights between two valid heights are valid");
+
+            current = self.verify_forward(
+                tail_start,
+                tail_start,
+                state,
+                &mut stats,
+                &mut |_| {},
+                &mut |_| Ok(()),
+            )?;
+        }
+
+        // Close the remaining gap one adjacent block at a time
+        while current.height() < target_height {
+            let next = current.height().increment();
+            current =
+                self.verify_forward(next, next, state, &mut stats, &mut |_| {}, &mut |_| Ok(()))?;
+        }
+
+        Ok(current)
+    }
+
+    /// Same as [`LightClient::verify_to_target`], but reports the progress of
+    /// the verification to `checkpoint` before each step, and optionally resumes
+    /// from a cursor previously reported this way.
+    ///
+    /// This only performs forward verification, ie. the target block must be higher
+    /// than the highest trusted or verified block of the store.
+    ///
+    /// If the target block is already trusted or verified, it is returned right away,
+    /// whatever the cursor.
+    ///
+    /// ## Error conditions
+    /// - If the cursor is for another target block, or if the block it was about to be
+    ///   verified against cannot be found in the light store anymore, or if the light
+    ///   store has since moved past it, verification fails with `StaleCursor`.
+    /// - Otherwise, as for [`LightClient::verify_to_target`].
+    pub fn verify_to_target_resumable(
+        &self,
+        target_height: Height,
+        cursor: Option<&VerifyCursor>,
+        state: &mut State,
+        mut checkpoint: impl FnMut(&VerifyCursor),
+    ) -> Result<LightBlock, Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        self.ensure_above_min_height(target_height)?;
+
+        if let Some(light_block) = state.light_store.get_trusted_or_verified(target_height) {
+            return Ok(light_block);
+        }
+
+        let current_height = match cursor {
+            Some(cursor) => self.check_cursor(cursor, target_height, state)?,
+            None => target_height,
+        };
+
+        if let Some(highest) = state
+            .light_store
+            .highest_trusted_or_verified_before(target_height)
+        {
+            self.ensure_within_trusting_period(highest)?;
+        }
+
+        self.verify_forward(
+            target_height,
+            current_height,
+            state,
+            &mut VerifyStats::default(),
+            &mut checkpoint,
+            &mut |_| Ok(()),
+        )
+    }
+
+    /// Same as [`LightClient::verify_to_target`], but sends each block into `sender` as soon
+    /// as it is verified, in the order they are verified, so that the caller can process
+    /// them while the verification goes on, eg. from another thread.
+    ///
+    /// With a bounded channel, verification blocks until the receiver catches up whenever
+    /// the channel is full. The target block is sent last, unless it was already trusted
+    /// or verified, in which case nothing is sent.
+    ///
+    /// This only performs forward verification, ie. the target block must be higher
+    /// than the highest trusted or verified block of the store.
+    ///
+    /// ## Error conditions
+    /// - If the receiver is dropped before verification completes, verification fails
+    ///   with `ChannelDisconnected`, see [`Error::send`].
+    /// - Otherwise, as for [`LightClient::verify_to_target`].
+    pub fn verify_to_target_streaming(
+        &self,
+        target_height: Height,
+        state: &mut State,
+        sender: &crossbeam_channel::Sender<LightBlock>,
+    ) -> Result<LightBlock, Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        self.ensure_above_min_height(target_height)?;
+
+        if let Some(light_block) = state.light_store.get_trusted_or_verified(target_height) {
+            return Ok(light_block);
         }
+
+        if let Some(highest) = state
+            .light_store
+            .highest_trusted_or_verified_before(target_height)
+        {
+            self.ensure_within_trusting_period(highest)?;
+        }
+
+        self.verify_forward(
+            target_height,
+            target_height,
+            state,
+            &mut VerifyStats::default(),
+            &mut |_| {},
+            &mut |light_block| sender.send(light_block.clone()).map_err(Error::send),
+        )
     }
 
-    /// Perform forward verification with bisection.
+    /// Check that the given cursor can be resumed from towards the given target,
+    /// and return the height of the next block to verify.
+    fn check_cursor(
+        &self,
+        cursor: &VerifyCursor,
+        target_height: Height,
+        state: &State,
+    ) -> Result<Height, Error> {
+        let stale = || Error::stale_cursor(cursor.target_height, cursor.trusted_height);
+
+        if cursor.target_height != target_height || cursor.current_height > target_height {
+            return Err(stale());
+        }
+
+        let trusted_block = state
+            .light_store
+            .get_trusted_or_verified(cursor.trusted_height)
+            .ok_or_else(stale)?;
+
+        if trusted_block.signed_header.commit.block_id.hash != cursor.trusted_hash {
+            return Err(stale());
+        }
+
+        let highest = state
+            .light_store
+            .highest_trusted_or_verified_before(target_height)
+            .ok_or_else(stale)?;
+
+        if highest.height() > cursor.current_height {
+            return Err(stale());
+        }
+
+        Ok(cursor.current_height)
+    }
+
+    /// Perform forward verification with bisection, starting with the block at `current_height`.
+    ///
+    /// Each block is handed to `verified` as soon as it is verified, and verification is
+    /// aborted with the error it returns, if any.
     fn verify_forward(
         &self,
         target_height: Height,
+        mut current_height: Height,
         state: &mut State,
+        stats: &mut VerifyStats,
+        checkpoint: &mut dyn FnMut(&VerifyCursor),
+        verified: &mut dyn FnMut(&LightBlock) -> Result<(), Error>,
     ) -> Result<LightBlock, Error> {
-        let mut current_height = target_height;
+        let mut steps = 0;
+        let mut last_trusted_height = None;
+        let mut stalled = 0;
 
         loop {
-            let now = self.clock.now();
+            let now = self.now();
 
             // Get the latest trusted state
             let trusted_block = state
@@ -193,7 +932,7 @@ impl LightClient {
             if !is_within_trust_period(&trusted_block, self.options.trusting_period, now) {
                 return Err(Error::trusted_state_outside_trusting_period(
                     Box::new(trusted_block),
-                    self.options,
+                    self.options.snapshot(),
                 ));
             }
 
@@ -206,34 +945,67 @@ impl LightClient {
                 return Ok(trusted_block);
             }
 
+            checkpoint(&VerifyCursor {
+                target_height,
+                trusted_height: trusted_block.height(),
+                trusted_hash: trusted_block.signed_header.commit.block_id.hash,
+                current_height,
+            });
+
+            // Give up if the trusted height has not been raised for too long
+            if last_trusted_height == Some(trusted_block.height()) {
+                stalled += 1;
+                if stalled >= self.options.max_stalled_iterations {
+                    return Err(Error::no_progress(trusted_block.height(), stalled));
+                }
+            } else {
+                last_trusted_height = Some(trusted_block.height());
+                stalled = 0;
+            }
+
+            // Bound the work done on adversarial inputs
+            if steps >= self.options.max_bisection_steps {
+                return Err(Error::bisection_step_limit_exceeded(target_height, steps));
+            }
+            steps += 1;
+
             // Fetch the block at the current height from the light store if already present,
             // or from the primary peer otherwise.
-            let (current_block, status) = self.get_or_fetch_block(current_height, state)?;
+            let (current_block, status) =
+                self.get_or_fetch_block_counted(current_height, state, stats)?;
+
+            stats.steps += 1;
 
             // Validate and verify the current block
-            let verdict = self.verifier.verify_update_header(
-                current_block.as_untrusted_state(),
-                trusted_block.as_trusted_state(),
-                &self.options,
-                now,
-            );
+            let (verdict, tally) = self.verify_update_header(&current_block, &trusted_block, now);
 
             match verdict {
                 Verdict::Success => {
                     // Verification succeeded, add the block to the light store with
-                    // the `Verified` status or higher if already trusted.
-                    let new_status = Status::most_trusted(Status::Verified, status);
+                    // the `Verified` status, unless configured to trust it directly,
+                    // or higher if already trusted.
+                    let new_status = Status::most_trusted(self.verified_status(), status);
                     state.light_store.update(&current_block, new_status);
 
+                    if let Some(tally) = tally {
+                        stats.total_voting_power_verified += tally.tallied;
+                    }
+
                     // Log the trusted height as a dependency of the block at the current height
                     state.trace_block(current_height, trusted_block.height());
+
+                    verified(&current_block)?;
                 },
                 Verdict::Invalid(e) => {
                     // Verification failed, add the block to the light store with `Failed` status,
                     // and abort.
-                    state.light_store.update(&current_block, Status::Failed);
+                    self.store_failed(&current_block, (&e).into(), state);
 
-                    return Err(Error::invalid_light_block(e));
+                    return Err(Error::verification_failed(
+                        self.peer_labeler(),
+                        current_block.provider,
+                        e,
+                    ));
                 },
                 Verdict::NotEnoughTrust(_) => {
                     // The current block cannot be trusted because of a missing overlap in the
@@ -242,6 +1014,15 @@ impl LightClient {
                     // attempt to raise the height of the highest trusted state
                     // until there is enough overlap.
                     state.light_store.update(&current_block, Status::Unverified);
+
+                    let next_height = trusted_block.height().increment();
+                    if self.options.step_through_validator_churn
+                        && current_height > next_height
+                        && has_churned(&trusted_block, &current_block, self.options.trust_threshold)
+                    {
+                        current_height = next_height;
+                        continue;
+                    }
                 },
             }
 
@@ -252,13 +1033,157 @@ impl LightClient {
         }
     }
 
+    /// Verify the target block as an adjacent block of the given trusted block,
+    /// see [`Options::adjacent_only`].
+    ///
+    /// The target block must be right above the trusted block, which is the highest
+    /// verified or trusted block below it: the target alone is then verified against it.
+    /// Returns `None` without verifying anything otherwise, or if the target block cannot
+    /// be trusted from the one below it, so that bisection takes over.
+    #[cfg(feature = "rust-crypto")]
+    fn verify_adjacent_chain(
+        &self,
+        target_height: Height,
+        trusted_block: LightBlock,
+        state: &mut State,
+        stats: &mut VerifyStats,
+    ) -> Result<Option<LightBlock>, Error> {
+        if trusted_block.height().increment() != target_height {
+            return Ok(None);
+        }
+
+        let (current_block, status) =
+            self.get_or_fetch_block_counted(target_height, state, stats)?;
+
+        stats.steps += 1;
+
+        // The target must link to the header of the trusted block through its `last_block_id`
+        if let Err(e) = adjacent_blocks_chain(&trusted_block, &current_block) {
+            let reason = FailureReason {
+                message: e.detail().to_string(),
+            };
+            self.store_failed(&current_block, reason, state);
+            return Err(e);
+        }
+
+        let now = self.now();
+        let (verdict, tally) = self.verify_update_header(&current_block, &trusted_block, now);
+
+        match verdict {
+            Verdict::Success => {
+                let new_status = Status::most_trusted(self.verified_status(), status);
+                state.light_store.update(&current_block, new_status);
+
+                if let Some(tally) = tally {
+                    stats.total_voting_power_verified += tally.tallied;
+                }
+
+                state.trace_block(target_height, trusted_block.height());
+                Ok(Some(current_block))
+            },
+            Verdict::Invalid(e) => {
+                self.store_failed(&current_block, (&e).into(), state);
+                Err(Error::verification_failed(
+                    self.peer_labeler(),
+                    current_block.provider,
+                    e,
+                ))
+            },
+            Verdict::NotEnoughTrust(_) => Ok(None),
+        }
+    }
+
+    /// Store the given block with the `Failed` status, along with the reason it failed
+    /// verification if [`Options::retain_failed`] is set.
+    fn store_failed(&self, light_block: &LightBlock, reason: FailureReason, state: &mut State) {
+        if self.options.retain_failed {
+            state.light_store.update_failed(light_block, reason);
+        } else {
+            state.light_store.update(light_block, Status::Failed);
+        }
+    }
+
+    /// Fail with `BelowMinHeight` if the given target is below
+    /// [`Options::min_verifiable_height`], before doing any I/O.
+    fn ensure_above_min_height(&self, target_height: Height) -> Result<(), Error> {
+        match self.options.min_verifiable_height {
+            Some(min) if target_height < min => Err(Error::below_min_height(target_height, min)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail with `TrustedStateOutsideTrustingPeriod` if the given trusted block has expired,
+    /// so that a sync which cannot possibly succeed fails before doing any I/O.
+    fn ensure_within_trusting_period(&self, trusted_block: LightBlock) -> Result<(), Error> {
+        if !is_within_trust_period(&trusted_block, self.options.trusting_period, self.now()) {
+            return Err(Error::trusted_state_outside_trusting_period(
+                Box::new(trusted_block),
+                self.options.snapshot(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The current time, as given by `options.now_override` if set, or by the clock otherwise.
+    fn now(&self) -> Time {
+        self.options
+            .now_override
+            .unwrap_or_else(|| self.clock.now())
+    }
+
+    /// The status given to successfully verified blocks, see [`Options::auto_trust_verified`].
+    fn verified_status(&self) -> Status {
+        if self.options.auto_trust_verified {
+            Status::Trusted
+        } else {
+            Status::Verified
+        }
+    }
+
+    /// Verify the `untrusted` block against the `trusted` one,
+    /// going through the verdict cache if there is one.
+    ///
+    /// Also returns the voting power which signed the `untrusted` block, if known.
+    fn verify_update_header(
+        &self,
+        untrusted: &LightBlock,
+        trusted: &LightBlock,
+        now: Time,
+    ) -> (Verdict, Option<VotingPowerTally>) {
+        #[cfg(feature = "rust-crypto")]
+        if let Some(cache) = &self.verdict_cache {
+            if let Some(verdict) = cache.get(trusted, untrusted, self.options.trusting_period, now)
+            {
+                return (verdict, None);
+            }
+        }
+
+        let (verdict, tally) = self.verifier.verify_update_header_with_tally(
+            untrusted.as_untrusted_state(),
+            trusted.as_trusted_state(),
+            &self.options,
+            now,
+        );
+
+        #[cfg(feature = "rust-crypto")]
+        if let Some(cache) = &self.verdict_cache {
+            if self.dry_runs.load(Ordering::Acquire) == 0 {
+                cache.insert(trusted, untrusted, &verdict);
+            }
+        }
+
+        (verdict, tally)
+    }
+
     /// Stub for when "unstable" feature is disabled.
     #[doc(hidden)]
     #[cfg(not(feature = "unstable"))]
-    fn verify_backward(
+    fn verify_backward_inner(
         &self,
         target_height: Height,
         state: &mut State,
+        _stats: &mut VerifyStats,
     ) -> Result<LightBlock, Error> {
         let trusted_state = state
             .light_store
@@ -272,45 +1197,90 @@ impl LightClient {
         ))
     }
 
-    /// Perform sequential backward verification.
+    /// Perform sequential backward verification, as part of [`LightClient::verify_to_target`].
+    ///
+    /// See [`LightClient::verify_backward`] for the algorithm.
+    ///
+    /// ## Stability
+    /// This feature is only available if the `unstable` flag of is enabled.
+    /// If the flag is disabled, then any attempt to verify a block whose
+    /// height is lower than the highest trusted state will result in a
+    /// `TargetLowerThanTrustedState` error.
+    #[cfg(feature = "unstable")]
+    fn verify_backward_inner(
+        &self,
+        target_height: Height,
+        state: &mut State,
+        stats: &mut VerifyStats,
+    ) -> Result<LightBlock, Error> {
+        let root = state
+            .light_store
+            .highest_trusted_or_verified_before(target_height)
+            .or_else(|| state.light_store.lowest_trusted_or_verified())
+            .ok_or_else(Error::no_initial_trusted_state)?;
+
+        self.verify_backward_from(root, target_height, state, stats)
+    }
+
+    /// Verify the block at the given target height backward, from the lowest trusted block
+    /// at or above it, eg. to backfill the history below the trusted anchor of the store.
     ///
     /// Backward verification is implemented by taking a sliding window
     /// of length two between the trusted state and the target block and
     /// checking whether the last_block_id hash of the higher block
-    /// matches the computed hash of the lower block.
+    /// matches the computed hash of the lower block. Every block on the way
+    /// down is stored with the `Trusted` status.
+    ///
+    /// Unlike backward verification through [`LightClient::verify_to_target`], this does
+    /// not require the `unstable` feature, as it is explicitly asked for.
     ///
     /// ## Performance
     /// The algorithm implemented is very inefficient in case the target
-    /// block is much lower than the highest trusted state.
+    /// block is much lower than the trusted state.
     /// For a trusted state at height `T`, and a target block at height `H`,
     /// it will fetch and check hashes of `T - H` blocks.
     ///
-    /// ## Stability
-    /// This feature is only available if the `unstable` flag of is enabled.
-    /// If the flag is disabled, then any attempt to verify a block whose
-    /// height is lower than the highest trusted state will result in a
-    /// `TargetLowerThanTrustedState` error.
-    #[cfg(feature = "unstable")]
-    fn verify_backward(
+    /// ## Error conditions
+    /// - If there is no trusted block at or above the target height
+    /// - The trusted block is outside of the trusting period [LCV-INV-TP.1]
+    /// - If a block on the way down has no `last_block_id`, with `MissingLastBlockId`,
+    ///   or if it does not match the hash of the block below it
+    /// - If the fetching a light block from the primary node fails
+    /// - If the target height is below [`Options::min_verifiable_height`], before any I/O
+    #[cfg(feature = "rust-crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+    pub fn verify_backward(
         &self,
         target_height: Height,
         state: &mut State,
     ) -> Result<LightBlock, Error> {
-        use tendermint::crypto::default::Sha256;
+        let _scope = self.block_cache.scope();
+        self.ensure_above_min_height(target_height)?;
 
         let root = state
             .light_store
-            .highest_trusted_or_verified_before(target_height)
-            .or_else(|| state.light_store.lowest_trusted_or_verified())
+            .trusted_ceiling(target_height)
             .ok_or_else(Error::no_initial_trusted_state)?;
 
+        self.verify_backward_from(root, target_height, state, &mut VerifyStats::default())
+    }
+
+    /// Hash-link the blocks below the given root, down to the target one.
+    #[cfg(feature = "rust-crypto")]
+    fn verify_backward_from(
+        &self,
+        root: LightBlock,
+        target_height: Height,
+        state: &mut State,
+        stats: &mut VerifyStats,
+    ) -> Result<LightBlock, Error> {
         assert!(root.height() >= target_height);
 
         // Check invariant [LCV-INV-TP.1]
-        if !is_within_trust_period(&root, self.options.trusting_period, self.clock.now()) {
+        if !is_within_trust_period(&root, self.options.trusting_period, self.now()) {
             return Err(Error::trusted_state_outside_trusting_period(
                 Box::new(root),
-                self.options,
+                self.options.snapshot(),
             ));
         }
 
@@ -321,22 +1291,11 @@ impl LightClient {
         let mut latest = root;
 
         for height in heights {
-            let (current, _status) = self.get_or_fetch_block(height, state)?;
+            let (current, _status) = self.get_or_fetch_block_counted(height, state, stats)?;
 
-            let latest_last_block_id = latest
-                .signed_header
-                .header
-                .last_block_id
-                .ok_or_else(|| Error::missing_last_block_id(latest.height()))?;
+            stats.steps += 1;
 
-            let current_hash = current.signed_header.header.hash_with::<Sha256>();
-
-            if current_hash != latest_last_block_id.hash {
-                return Err(Error::invalid_adjacent_headers(
-                    current_hash,
-                    latest_last_block_id.hash,
-                ));
-            }
+            adjacent_blocks_chain(&current, &latest)?;
 
             // `latest` and `current` are linked together by `last_block_id`,
             // therefore it is not relevant which we verified first.
@@ -368,6 +1327,19 @@ impl LightClient {
         &self,
         height: Height,
         state: &mut State,
+    ) -> Result<(LightBlock, Status), Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
+        self.get_or_fetch_block_counted(height, state, &mut VerifyStats::default())
+    }
+
+    /// Same as `get_or_fetch_block`, but records in the given stats
+    /// whether the block had to be fetched from the peer.
+    fn get_or_fetch_block_counted(
+        &self,
+        height: Height,
+        state: &mut State,
+        stats: &mut VerifyStats,
     ) -> Result<(LightBlock, Status), Error> {
         let block = state.light_store.get_non_failed(height);
 
@@ -375,16 +1347,26 @@ impl LightClient {
             return Ok(block);
         }
 
-        let block = self
-            .io
-            .fetch_light_block(AtHeight::At(height))
-            .map_err(Error::io)?;
+        stats.io_requests += 1;
+
+        let block = self.fetch(AtHeight::At(height))?;
 
         state.light_store.insert(block.clone(), Status::Unverified);
 
         Ok((block, Status::Unverified))
     }
 
+    /// Fetch a light block from the primary, through the block cache of the current
+    /// verification call.
+    fn fetch(&self, height: AtHeight) -> Result<LightBlock, Error> {
+        #[cfg(feature = "rust-crypto")]
+        let block = self.io.fetch_light_block_cached(height, &self.block_cache);
+        #[cfg(not(feature = "rust-crypto"))]
+        let block = self.io.fetch_light_block(height);
+
+        block.map_err(Error::io)
+    }
+
     /// Get the block at the given height or the latest block from the chain if the given height is
     /// lower than the latest height.
     pub fn get_target_block_or_latest(
@@ -392,22 +1374,21 @@ impl LightClient {
         height: Height,
         state: &mut State,
     ) -> Result<TargetOrLatest, Error> {
+        #[cfg(feature = "rust-crypto")]
+        let _scope = self.block_cache.scope();
         let block = state.light_store.get_non_failed(height);
 
         if let Some((block, _)) = block {
             return Ok(TargetOrLatest::Target(block));
         }
 
-        let block = self.io.fetch_light_block(AtHeight::At(height));
+        let block = self.fetch(AtHeight::At(height));
 
         if let Ok(block) = block {
             return Ok(TargetOrLatest::Target(block));
         }
 
-        let latest = self
-            .io
-            .fetch_light_block(AtHeight::Highest)
-            .map_err(Error::io)?;
+        let latest = self.fetch(AtHeight::Highest)?;
 
         if latest.height() == height {
             Ok(TargetOrLatest::Target(latest))
@@ -421,3 +1402,102 @@ pub enum TargetOrLatest {
     Latest(LightBlock),
     Target(LightBlock),
 }
+
+/// Keeps the verdicts out of the verdict cache while a dry run is in progress.
+#[cfg(feature = "rust-crypto")]
+struct DryRun<'a>(&'a AtomicUsize);
+
+#[cfg(feature = "rust-crypto")]
+impl<'a> DryRun<'a> {
+    fn start(dry_runs: &'a AtomicUsize) -> Self {
+        dry_runs.fetch_add(1, Ordering::AcqRel);
+        Self(dry_runs)
+    }
+}
+
+#[cfg(feature = "rust-crypto")]
+impl Drop for DryRun<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Puts the light store taken out of a state for a dry run back into it when dropped,
+/// once the overlay over it is dropped.
+struct RestoreStore<'a> {
+    state: &'a mut State,
+    base: Arc<Box<dyn LightStore>>,
+}
+
+impl<'a> RestoreStore<'a> {
+    fn take(state: &'a mut State) -> Self {
+        let base = core::mem::replace(&mut state.light_store, Box::new(MemoryStore::new()));
+
+        Self {
+            state,
+            base: Arc::new(base),
+        }
+    }
+}
+
+impl Drop for RestoreStore<'_> {
+    fn drop(&mut self) {
+        let base = core::mem::replace(&mut self.base, Arc::new(Box::new(MemoryStore::new())));
+
+        // Do not panic again if a panicking verification still holds the overlay
+        if let Ok(light_store) = Arc::try_unwrap(base) {
+            self.state.light_store = light_store;
+        }
+    }
+}
+
+/// The verdict of a dry run which ended with the given result, or the error which
+/// prevented it from reaching one.
+fn dry_run_verdict(result: Result<LightBlock, Error>) -> Result<Verdict, Error> {
+    let e = match result {
+        Ok(_) => return Ok(Verdict::Success),
+        Err(e) => e,
+    };
+
+    let verdict = match e.detail() {
+        ErrorDetail::InvalidLightBlock(e) => match e.source.not_enough_trust() {
+            Some(tally) => Verdict::NotEnoughTrust(tally),
+            None => Verdict::Invalid(e.source.clone()),
+        },
+        ErrorDetail::ValidatorCountMismatch(e) => {
+            Verdict::Invalid(VerificationError::validator_count_mismatch(e.expected, e.got).0)
+        },
+        ErrorDetail::ValidatorSetHashMismatch(e) => Verdict::Invalid(
+            VerificationError::invalid_validator_set(e.header_hash, e.computed_hash).0,
+        ),
+        _ => return Err(e),
+    };
+
+    Ok(verdict)
+}
+
+/// The trusted or verified blocks of the light store within the given range of heights.
+fn trusted_or_verified_in(
+    state: &State,
+    range: HeightRange,
+) -> impl Iterator<Item = LightBlock> + '_ {
+    [Status::Verified, Status::Trusted]
+        .into_iter()
+        .flat_map(move |status| state.light_store.get_range(range, status))
+}
+
+/// Whether the validators of the untrusted block hold less than the given fraction of
+/// the voting power of the next validators of the trusted block.
+fn has_churned(trusted: &LightBlock, untrusted: &LightBlock, threshold: TrustThreshold) -> bool {
+    let total = trusted.next_validators.total_voting_power().value();
+    let retained: u64 = trusted
+        .next_validators
+        .validators()
+        .iter()
+        .filter(|validator| untrusted.validators.validator(validator.address).is_some())
+        .map(|validator| validator.power())
+        .sum();
+
+    u128::from(retained) * u128::from(threshold.denominator())
+        < u128::from(total) * u128::from(threshold.numerator())
+}
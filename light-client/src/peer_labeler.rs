@@ -0,0 +1,84 @@
+//! Human-readable labels for peers, used in errors and log lines.
+//!
+//! By default, peers are shown as their hex-encoded [`PeerId`]. Operators can give a
+//! light client a [`PeerLabeler`] with [`LightClientBuilder::peer_labeler`] to show them
+//! under more meaningful names instead, eg. the hostnames of the nodes.
+//!
+//! [`LightClientBuilder::peer_labeler`]: crate::builder::LightClientBuilder::peer_labeler
+
+use std::fmt;
+
+use crate::verifier::types::PeerId;
+
+/// Maps peer ids to the labels under which they are shown in errors and logs.
+pub trait PeerLabeler: Send + Sync {
+    /// The label of the given peer.
+    fn label(&self, peer: &PeerId) -> String;
+}
+
+/// Labels peers with their hex-encoded id.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultPeerLabeler;
+
+impl PeerLabeler for DefaultPeerLabeler {
+    fn label(&self, peer: &PeerId) -> String {
+        peer.to_string()
+    }
+}
+
+impl<F> PeerLabeler for F
+where
+    F: Fn(&PeerId) -> String + Send + Sync,
+{
+    fn label(&self, peer: &PeerId) -> String {
+        self(peer)
+    }
+}
+
+/// Displays a peer with the label given by a labeler, for use in format strings and
+/// log fields.
+#[derive(Copy, Clone)]
+pub struct Labeled<'a>(pub &'a dyn PeerLabeler, pub &'a PeerId);
+
+impl fmt::Display for Labeled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.label(self.1))
+    }
+}
+
+impl fmt::Debug for Labeled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    #[test]
+    fn given_labeler_is_used_in_errors() {
+        let a: PeerId = "BADFADAD0BEFEEDC0C0ADEADBEEFC0FFEEFACADE".parse().unwrap();
+        let b: PeerId = "CEFEEDBADFADAD0C0CEEFACADE0ADEADBEEFC0FF".parse().unwrap();
+
+        assert_eq!(
+            Labeled(&DefaultPeerLabeler, &a).to_string(),
+            "badfadad0befeedc0c0adeadbeefc0ffeefacade"
+        );
+
+        let labeler = move |peer: &PeerId| {
+            if *peer == a {
+                "node-a.example.com".to_string()
+            } else {
+                DefaultPeerLabeler.label(peer)
+            }
+        };
+
+        let error = Error::fork_detected_by(&labeler, vec![a, b]);
+        assert_eq!(
+            error.detail().to_string(),
+            "fork detected peers=[node-a.example.com, cefeedbadfadad0c0ceefacade0adeadbeefc0ff]"
+        );
+    }
+}
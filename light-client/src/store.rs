@@ -4,20 +4,156 @@
 //!
 //! - a transient, in-memory implementation for testing purposes
 //! - a persistent, on-disk, sled-backed implementation for production
+//!
+//! The `async_store` module additionally defines an asynchronous counterpart
+//! to the `LightStore` trait, along with adapters in both directions.
 
 use std::fmt::Debug;
 
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
 use crate::{
+    errors::Error,
     utils::std_ext,
-    verifier::types::{Height, LightBlock, Status},
+    verifier::{
+        errors::VerificationErrorDetail,
+        types::{Height, LightBlock, Status},
+    },
 };
 
+use crate::verifier::types::Hash;
+#[cfg(feature = "rust-crypto")]
+use std::fmt;
+
 pub mod memory;
+pub mod overlay;
+
+mod range;
+pub use range::HeightRange;
+
+#[cfg(feature = "rust-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+mod diff;
+#[cfg(feature = "rust-crypto")]
+pub use diff::{diff, StoreDiff};
+
+#[cfg(feature = "rust-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+mod integrity;
+#[cfg(feature = "rust-crypto")]
+pub use integrity::IntegrityIssue;
+
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub mod async_store;
 
 #[cfg(feature = "lightstore-sled")]
 #[cfg_attr(docsrs, doc(cfg(feature = "lightstore-sled")))]
 pub mod sled;
 
+/// Encode the given light block with CBOR and decode it back, the way the sled store
+/// writes and reads light blocks with its default format.
+///
+/// This lets code which serializes light blocks with `serde_cbor` on its own check that
+/// they survive the encoding of the store unchanged.
+pub fn cbor_round_trip(light_block: &LightBlock) -> Result<LightBlock, Error> {
+    from_cbor(&to_cbor(light_block)?)
+}
+
+/// Encode a value with CBOR, as stored by the sled store.
+pub(crate) fn to_cbor<V: Serialize>(value: &V) -> Result<Vec<u8>, Error> {
+    serde_cbor::to_vec(value).map_err(Error::serde_cbor)
+}
+
+/// Decode a value encoded by [`to_cbor`].
+pub(crate) fn from_cbor<V: DeserializeOwned>(bytes: &[u8]) -> Result<V, Error> {
+    serde_cbor::from_slice(bytes).map_err(Error::serde_cbor)
+}
+
+/// Hash of the header of the given light block, as looked up by [`LightStore::get_by_hash`].
+///
+/// Headers can only be hashed with the `rust-crypto` feature. Without it, this is the
+/// hash of the block the commit was signed for instead, which verification checks
+/// against the header.
+pub(crate) fn header_hash(light_block: &LightBlock) -> Hash {
+    #[cfg(feature = "rust-crypto")]
+    return light_block.signed_header.header.hash();
+    #[cfg(not(feature = "rust-crypto"))]
+    return light_block.signed_header.commit.block_id.hash;
+}
+
+/// Remove the light blocks strictly below the given height, whatever their status,
+/// but the highest `Trusted` one, and return the number of blocks removed.
+pub(crate) fn prune_for_compaction<S: LightStore + ?Sized>(store: &mut S, below: Height) -> usize {
+    let trusted_below = store
+        .highest(Status::Trusted)
+        .map_or(below, |highest| highest.height().min(below));
+
+    Status::iter()
+        .iter()
+        .map(|status| match status {
+            Status::Trusted => store.prune_below(trusted_below, *status),
+            _ => store.prune_below(below, *status),
+        })
+        .sum()
+}
+
+/// What [`LightStore::compact`] removed from a store, and the space it reclaimed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// Size of the store on disk before compaction, or `None` if it is not persisted to disk
+    pub bytes_before: Option<u64>,
+    /// Size of the store on disk after compaction, or `None` if it is not persisted to disk
+    pub bytes_after: Option<u64>,
+    /// Number of light blocks removed, whatever their status
+    pub entries_removed: usize,
+}
+
+/// Why a light block failed verification, as recorded by [`LightStore::update_failed`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureReason {
+    /// The verification error, as displayed
+    pub message: String,
+}
+
+impl From<&VerificationErrorDetail> for FailureReason {
+    fn from(detail: &VerificationErrorDetail) -> Self {
+        Self {
+            message: detail.to_string(),
+        }
+    }
+}
+
+/// A different block is already stored at the height and status of the block
+/// given to [`LightStore::insert_checked`].
+#[cfg(feature = "rust-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreConflict {
+    /// Height of the conflicting blocks
+    pub height: Height,
+    /// Status of the conflicting blocks
+    pub status: Status,
+    /// Hash of the header of the block already in the store
+    pub existing: Hash,
+    /// Hash of the header of the block which was to be inserted
+    pub inserted: Hash,
+}
+
+#[cfg(feature = "rust-crypto")]
+impl fmt::Display for StoreConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "conflicting blocks with status {:?} at height {}: {} is stored, {} was inserted",
+            self.status, self.height, self.existing, self.inserted
+        )
+    }
+}
+
+#[cfg(feature = "rust-crypto")]
+impl std::error::Error for StoreConflict {}
+
 /// Store for light blocks.
 ///
 /// The light store records light blocks received from peers, and their verification status.
@@ -30,16 +166,165 @@ pub trait LightStore: Debug + Send + Sync {
     /// Get the light block at the given height with the given status, or return `None` otherwise.
     fn get(&self, height: Height, status: Status) -> Option<LightBlock>;
 
+    /// Get the light blocks at each of the given heights with the given statuses, in the
+    /// order of the keys, with `None` for those which are not in the store.
+    ///
+    /// The default implementation looks the keys up one by one, with [`LightStore::get`].
+    fn get_many(&self, keys: &[(Height, Status)]) -> Vec<Option<LightBlock>> {
+        keys.iter()
+            .map(|&(height, status)| self.get(height, status))
+            .collect()
+    }
+
     /// Update the `status` of the given `light_block`.
     fn update(&mut self, light_block: &LightBlock, status: Status);
 
+    /// Same as [`LightStore::update`] with the `Failed` status, additionally recording
+    /// why the block failed verification, see [`LightStore::failures`].
+    ///
+    /// The default implementation discards the reason.
+    fn update_failed(&mut self, light_block: &LightBlock, _reason: FailureReason) {
+        self.update(light_block, Status::Failed);
+    }
+
+    /// Get an iterator of the `Failed` blocks whose failure was recorded with
+    /// [`LightStore::update_failed`], along with the reason, in increasing order of height.
+    ///
+    /// The default implementation returns none, as it does not record reasons.
+    fn failures(&self) -> Box<dyn Iterator<Item = (LightBlock, FailureReason)>> {
+        Box::new(std::iter::empty())
+    }
+
     /// Insert a new light block in the store with the given status.
     /// Overrides any other block with the same height and status.
     fn insert(&mut self, light_block: LightBlock, status: Status);
 
+    /// Same as [`LightStore::insert`], but fails instead of overriding a different
+    /// block with the same height and status.
+    ///
+    /// Blocks are told apart by the hash of their header. Inserting the same block again
+    /// is not a conflict, and replaces the existing entry, eg. with a different provider.
+    #[cfg(feature = "rust-crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+    fn insert_checked(
+        &mut self,
+        light_block: LightBlock,
+        status: Status,
+    ) -> Result<(), StoreConflict> {
+        if let Some(existing) = self.get(light_block.height(), status) {
+            let existing = existing.signed_header.header.hash();
+            let inserted = light_block.signed_header.header.hash();
+
+            if existing != inserted {
+                return Err(StoreConflict {
+                    height: light_block.height(),
+                    status,
+                    existing,
+                    inserted,
+                });
+            }
+        }
+
+        self.insert(light_block, status);
+        Ok(())
+    }
+
     /// Remove the light block with the given height and status, if any.
     fn remove(&mut self, height: Height, status: Status);
 
+    /// Remove all the light blocks with the given status,
+    /// and return the number of blocks removed.
+    fn remove_all(&mut self, status: Status) -> usize {
+        let heights: Vec<_> = self.all(status).map(|lb| lb.height()).collect();
+        for height in &heights {
+            self.remove(*height, status);
+        }
+        heights.len()
+    }
+
+    /// Remove the light blocks with the given status whose height is strictly
+    /// greater than the given height, and return the number of blocks removed.
+    fn remove_above(&mut self, height: Height, status: Status) -> usize {
+        let heights: Vec<_> = self
+            .all(status)
+            .map(|lb| lb.height())
+            .filter(|h| *h > height)
+            .collect();
+        for height in &heights {
+            self.remove(*height, status);
+        }
+        heights.len()
+    }
+
+    /// Remove the light blocks with the given status whose height is strictly
+    /// lower than the given height, and return the number of blocks removed.
+    fn prune_below(&mut self, height: Height, status: Status) -> usize {
+        let heights: Vec<_> = self
+            .all(status)
+            .map(|lb| lb.height())
+            .filter(|h| *h < height)
+            .collect();
+        for height in &heights {
+            self.remove(*height, status);
+        }
+        heights.len()
+    }
+
+    /// Remove every light block whose height is strictly lower than the given height,
+    /// whatever its status, and report how much space this reclaimed, as measured by
+    /// [`LightStore::size_on_disk`] before and after.
+    ///
+    /// The highest `Trusted` block is always kept, even below the given height, so that
+    /// the light client can still verify from it.
+    ///
+    /// Stores not persisted to disk report the number of blocks removed only. Note that
+    /// a persistent store may reclaim the space of the removed blocks lazily, in which
+    /// case its size on disk does not shrink right away.
+    fn compact(&mut self, below: Height) -> Result<CompactionStats, Error> {
+        let bytes_before = self.size_on_disk()?;
+        let entries_removed = prune_for_compaction(self, below);
+        let bytes_after = self.size_on_disk()?;
+
+        Ok(CompactionStats {
+            bytes_before,
+            bytes_after,
+            entries_removed,
+        })
+    }
+
+    /// Make the block at the given height the trusted anchor of the store.
+    ///
+    /// The block is marked as `Trusted`, and every other `Trusted` block is demoted to
+    /// `Archived`, so that it is kept for audit but no longer used for verification.
+    /// Right after this call, `all(Status::Trusted)` therefore only returns the anchor,
+    /// until more blocks are trusted.
+    ///
+    /// Only a block which is already `Verified`, `Trusted` or `Archived` can become the
+    /// anchor. Returns whether there was one at the given height, leaving the store
+    /// untouched otherwise.
+    fn set_anchor(&mut self, height: Height) -> bool {
+        let anchor = [Status::Trusted, Status::Verified, Status::Archived]
+            .iter()
+            .find_map(|status| self.get(height, *status));
+
+        let anchor = match anchor {
+            Some(anchor) => anchor,
+            None => return false,
+        };
+
+        let demoted: Vec<_> = self
+            .all(Status::Trusted)
+            .filter(|light_block| light_block.height() != height)
+            .collect();
+
+        for light_block in &demoted {
+            self.update(light_block, Status::Archived);
+        }
+
+        self.update(&anchor, Status::Trusted);
+        true
+    }
+
     /// Get the light block of greatest height with the given status.
     fn highest(&self, status: Status) -> Option<LightBlock>;
 
@@ -49,9 +334,105 @@ pub trait LightStore: Debug + Send + Sync {
     /// Get the light block of lowest height with the given status.
     fn lowest(&self, status: Status) -> Option<LightBlock>;
 
+    /// Get the trusted light block of lowest height greater than or equal to the given height.
+    fn trusted_ceiling(&self, height: Height) -> Option<LightBlock> {
+        self.all(Status::Trusted)
+            .filter(|lb| lb.height() >= height)
+            .min_by_key(|lb| lb.height())
+    }
+
+    /// Get the trusted light block of greatest height lower than or equal to the given height.
+    fn trusted_floor(&self, height: Height) -> Option<LightBlock> {
+        self.highest_before(height, Status::Trusted)
+    }
+
+    /// Get an iterator of the light blocks with the given status whose height
+    /// lies within the given range, in increasing order of height.
+    fn get_range(
+        &self,
+        range: HeightRange,
+        status: Status,
+    ) -> Box<dyn Iterator<Item = LightBlock>> {
+        Box::new(
+            self.all(status)
+                .filter(move |lb| range.contains(lb.height())),
+        )
+    }
+
     /// Get an iterator of all light blocks with the given status.
     fn all(&self, status: Status) -> Box<dyn Iterator<Item = LightBlock>>;
 
+    /// Get an iterator of the light blocks awaiting promotion to `Trusted`, ie. of all
+    /// the `Verified` blocks, in increasing order of height.
+    ///
+    /// The default implementation sorts the blocks returned by [`LightStore::all`].
+    /// Implementations whose blocks are already ordered by height override it to
+    /// yield them as they go.
+    fn pending_promotion(&self) -> Box<dyn Iterator<Item = LightBlock>> {
+        let mut pending: Vec<_> = self.all(Status::Verified).collect();
+        pending.sort_by_key(|lb| lb.height());
+        Box::new(pending.into_iter())
+    }
+
+    /// Get the light block whose header hashes to the given hash, whatever its
+    /// verification status, or return `None` otherwise.
+    ///
+    /// If the block is stored with more than one status, the most trusted one is returned.
+    ///
+    /// The default implementation scans the blocks of every status, most trusted first.
+    /// Implementations which index their blocks by hash override it.
+    fn get_by_hash(&self, hash: Hash) -> Option<LightBlock> {
+        let mut statuses = Status::iter().to_vec();
+        statuses.sort_unstable_by(|a, b| b.cmp(a));
+
+        statuses
+            .into_iter()
+            .find_map(|status| self.all(status).find(|lb| header_hash(lb) == hash))
+    }
+
+    /// A commitment to the set of trusted blocks of the store, eg. to cheaply check whether
+    /// two stores trust the same blocks.
+    ///
+    /// This is the simple Merkle root of the `Trusted` blocks in increasing order of height,
+    /// each leaf being the big-endian height of a block followed by the hash of its header.
+    /// Stores trusting the same blocks thus have the same commitment, whatever their other
+    /// blocks, and an empty set of trusted blocks commits to the hash of the empty string.
+    #[cfg(feature = "rust-crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+    fn trusted_commitment(&self) -> Hash {
+        use tendermint::{crypto::default::Sha256, merkle::simple_hash_from_byte_vectors};
+
+        let mut trusted: Vec<_> = self.all(Status::Trusted).collect();
+        trusted.sort_by_key(|lb| lb.height());
+
+        let leaves: Vec<Vec<u8>> = trusted
+            .iter()
+            .map(|lb| {
+                let mut leaf = lb.height().value().to_be_bytes().to_vec();
+                leaf.extend_from_slice(lb.signed_header.header.hash().as_bytes());
+                leaf
+            })
+            .collect();
+
+        Hash::Sha256(simple_hash_from_byte_vectors::<Sha256>(&leaves))
+    }
+
+    /// Size of the store on disk, in bytes, or `None` if it is not persisted to disk.
+    fn size_on_disk(&self) -> Result<Option<u64>, Error> {
+        Ok(None)
+    }
+
+    /// Check the internal consistency of the store, without modifying it.
+    ///
+    /// The default implementation checks that every trusted or verified block
+    /// links, through its `last_block_id`, to the trusted or verified block right below it,
+    /// if any. Implementations may additionally check their own storage invariants.
+    #[cfg(feature = "rust-crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rust-crypto")))]
+    fn verify_integrity(&self) -> Result<(), Vec<IntegrityIssue>> {
+        integrity::into_result(integrity::adjacency_issues(self))
+    }
+
     /// Get a block at a given height whatever its verification status as long as it hasn't failed
     /// verification (ie. its status is not `Status::Failed`).
     fn get_non_failed(&self, height: Height) -> Option<(LightBlock, Status)> {
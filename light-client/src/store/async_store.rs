@@ -0,0 +1,209 @@
+//! Asynchronous interface to the light block store.
+//!
+//! Backends which are inherently asynchronous (eg. backed by a remote service)
+//! can implement [`AsyncLightStore`] and be used with the synchronous light client
+//! through [`BlockingAdapter`]. Conversely, any [`LightStore`] can be used where an
+//! [`AsyncLightStore`] is expected through [`AsyncAdapter`].
+
+use async_trait::async_trait;
+
+use crate::{
+    store::LightStore,
+    verifier::types::{Hash, Height, LightBlock, Status},
+};
+
+/// Asynchronous store for light blocks.
+///
+/// This mirrors the required methods of [`LightStore`], along with [`LightStore::get_by_hash`],
+/// see there for their semantics.
+#[async_trait]
+pub trait AsyncLightStore: core::fmt::Debug + Send + Sync {
+    /// Get the light block at the given height with the given status, or return `None` otherwise.
+    async fn get(&self, height: Height, status: Status) -> Option<LightBlock>;
+
+    /// Update the `status` of the given `light_block`.
+    async fn update(&mut self, light_block: &LightBlock, status: Status);
+
+    /// Insert a new light block in the store with the given status.
+    /// Overrides any other block with the same height and status.
+    async fn insert(&mut self, light_block: LightBlock, status: Status);
+
+    /// Remove the light block with the given height and status, if any.
+    async fn remove(&mut self, height: Height, status: Status);
+
+    /// Get the light block of greatest height with the given status.
+    async fn highest(&self, status: Status) -> Option<LightBlock>;
+
+    /// Get the light block of greatest height before the given height with the given status.
+    async fn highest_before(&self, height: Height, status: Status) -> Option<LightBlock>;
+
+    /// Get the light block of lowest height with the given status.
+    async fn lowest(&self, status: Status) -> Option<LightBlock>;
+
+    /// Get all light blocks with the given status.
+    async fn all(&self, status: Status) -> Vec<LightBlock>;
+
+    /// Get the light block whose header hashes to the given hash, whatever its
+    /// verification status, or return `None` otherwise.
+    async fn get_by_hash(&self, hash: Hash) -> Option<LightBlock>;
+}
+
+/// Exposes an [`AsyncLightStore`] as a synchronous [`LightStore`].
+///
+/// Every operation blocks the current thread until the underlying future completes.
+/// As such, this adapter must not be used from within an asynchronous context.
+#[derive(Debug)]
+pub struct BlockingAdapter<S>(S);
+
+impl<S: AsyncLightStore> BlockingAdapter<S> {
+    /// Wrap the given asynchronous store.
+    pub fn new(store: S) -> Self {
+        Self(store)
+    }
+
+    /// Get back the wrapped asynchronous store.
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+impl<S: AsyncLightStore> LightStore for BlockingAdapter<S> {
+    fn get(&self, height: Height, status: Status) -> Option<LightBlock> {
+        futures::executor::block_on(self.0.get(height, status))
+    }
+
+    fn update(&mut self, light_block: &LightBlock, status: Status) {
+        futures::executor::block_on(self.0.update(light_block, status))
+    }
+
+    fn insert(&mut self, light_block: LightBlock, status: Status) {
+        futures::executor::block_on(self.0.insert(light_block, status))
+    }
+
+    fn remove(&mut self, height: Height, status: Status) {
+        futures::executor::block_on(self.0.remove(height, status))
+    }
+
+    fn highest(&self, status: Status) -> Option<LightBlock> {
+        futures::executor::block_on(self.0.highest(status))
+    }
+
+    fn highest_before(&self, height: Height, status: Status) -> Option<LightBlock> {
+        futures::executor::block_on(self.0.highest_before(height, status))
+    }
+
+    fn lowest(&self, status: Status) -> Option<LightBlock> {
+        futures::executor::block_on(self.0.lowest(status))
+    }
+
+    fn all(&self, status: Status) -> Box<dyn Iterator<Item = LightBlock>> {
+        let light_blocks = futures::executor::block_on(self.0.all(status));
+        Box::new(light_blocks.into_iter())
+    }
+
+    fn get_by_hash(&self, hash: Hash) -> Option<LightBlock> {
+        futures::executor::block_on(self.0.get_by_hash(hash))
+    }
+}
+
+/// Exposes a synchronous [`LightStore`], such as the memory or sled stores,
+/// as an [`AsyncLightStore`].
+///
+/// Every operation completes immediately, on the calling task.
+#[derive(Debug)]
+pub struct AsyncAdapter<S>(S);
+
+impl<S: LightStore> AsyncAdapter<S> {
+    /// Wrap the given synchronous store.
+    pub fn new(store: S) -> Self {
+        Self(store)
+    }
+
+    /// Get back the wrapped synchronous store.
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+#[async_trait]
+impl<S: LightStore> AsyncLightStore for AsyncAdapter<S> {
+    async fn get(&self, height: Height, status: Status) -> Option<LightBlock> {
+        self.0.get(height, status)
+    }
+
+    async fn update(&mut self, light_block: &LightBlock, status: Status) {
+        self.0.update(light_block, status)
+    }
+
+    async fn insert(&mut self, light_block: LightBlock, status: Status) {
+        self.0.insert(light_block, status)
+    }
+
+    async fn remove(&mut self, height: Height, status: Status) {
+        self.0.remove(height, status)
+    }
+
+    async fn highest(&self, status: Status) -> Option<LightBlock> {
+        self.0.highest(status)
+    }
+
+    async fn highest_before(&self, height: Height, status: Status) -> Option<LightBlock> {
+        self.0.highest_before(height, status)
+    }
+
+    async fn lowest(&self, status: Status) -> Option<LightBlock> {
+        self.0.lowest(status)
+    }
+
+    async fn all(&self, status: Status) -> Vec<LightBlock> {
+        self.0.all(status).collect()
+    }
+
+    async fn get_by_hash(&self, hash: Hash) -> Option<LightBlock> {
+        self.0.get_by_hash(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint_testgen::{light_block::TmLightBlock as TGLightBlock, Generator, LightChain};
+
+    use super::*;
+    use crate::store::memory::MemoryStore;
+
+    #[test]
+    fn blocking_adapter_round_trips_through_async_adapter() {
+        let mut store = BlockingAdapter::new(AsyncAdapter::new(MemoryStore::new()));
+
+        let blocks = LightChain::default_with_length(3)
+            .light_blocks
+            .into_iter()
+            .map(|lb| lb.generate().unwrap())
+            .map(testgen_to_lb)
+            .collect::<Vec<_>>();
+
+        for block in &blocks {
+            store.insert(block.clone(), Status::Verified);
+        }
+
+        assert_eq!(store.highest(Status::Verified).as_ref(), blocks.last());
+        assert_eq!(store.lowest(Status::Verified).as_ref(), blocks.first());
+        assert_eq!(store.all(Status::Verified).count(), blocks.len());
+
+        store.update(&blocks[0], Status::Trusted);
+        assert_eq!(
+            store.get(blocks[0].height(), Status::Trusted),
+            Some(blocks[0].clone())
+        );
+        assert_eq!(store.get(blocks[0].height(), Status::Verified), None);
+    }
+
+    fn testgen_to_lb(tm_lb: TGLightBlock) -> LightBlock {
+        LightBlock {
+            signed_header: tm_lb.signed_header,
+            validators: tm_lb.validators,
+            next_validators: tm_lb.next_validators,
+            provider: tm_lb.provider,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Comparison of the contents of two light stores.
+
+use std::cmp::Ordering;
+
+use crate::{
+    store::LightStore,
+    verifier::types::{Height, LightBlock, Status},
+};
+
+/// The heights at which two light stores differ, as computed by [`diff`].
+///
+/// Heights are listed in increasing order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreDiff {
+    /// Heights of the blocks only found in the first store
+    pub only_in_a: Vec<Height>,
+    /// Heights of the blocks only found in the second store
+    pub only_in_b: Vec<Height>,
+    /// Heights found in both stores, but with blocks whose headers hash differently
+    pub different: Vec<Height>,
+}
+
+impl StoreDiff {
+    /// Whether both stores hold the same blocks.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.different.is_empty()
+    }
+}
+
+/// Compare the blocks with the given status of two light stores, eg. a reference store and
+/// a replica of it, to find out at which heights they disagree.
+///
+/// Both stores are walked side by side, in increasing order of height.
+pub fn diff(a: &dyn LightStore, b: &dyn LightStore, status: Status) -> StoreDiff {
+    let mut a = sorted(a, status).into_iter().peekable();
+    let mut b = sorted(b, status).into_iter().peekable();
+    let mut diff = StoreDiff::default();
+
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (Some(lb_a), Some(lb_b)) => lb_a.height().cmp(&lb_b.height()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+
+        match ordering {
+            Ordering::Less => diff.only_in_a.extend(a.next().map(|lb| lb.height())),
+            Ordering::Greater => diff.only_in_b.extend(b.next().map(|lb| lb.height())),
+            Ordering::Equal => {
+                let (lb_a, lb_b) = (a.next().unwrap(), b.next().unwrap());
+                if lb_a.signed_header.header.hash() != lb_b.signed_header.header.hash() {
+                    diff.different.push(lb_a.height());
+                }
+            },
+        }
+    }
+
+    diff
+}
+
+/// The blocks of the store with the given status, in increasing order of height.
+fn sorted(store: &dyn LightStore, status: Status) -> Vec<LightBlock> {
+    let mut blocks: Vec<_> = store.all(status).collect();
+    blocks.sort_by_key(|lb| lb.height());
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint::AppHash;
+    use tendermint_testgen::{light_block::TmLightBlock as TGLightBlock, Generator, LightChain};
+
+    use super::*;
+    use crate::store::memory::MemoryStore;
+
+    #[test]
+    fn heights_are_sorted_by_kind_of_difference() {
+        let blocks = LightChain::default_with_length(5)
+            .light_blocks
+            .into_iter()
+            .map(|lb| lb.generate().unwrap())
+            .map(testgen_to_lb)
+            .collect::<Vec<_>>();
+
+        let mut a = MemoryStore::new();
+        let mut b = MemoryStore::new();
+
+        for block in &blocks[..4] {
+            a.insert(block.clone(), Status::Trusted);
+        }
+        for block in &blocks[1..] {
+            b.insert(block.clone(), Status::Trusted);
+        }
+
+        // Same height, another header
+        let mut forged = blocks[2].clone();
+        forged.signed_header.header.app_hash = AppHash::try_from(vec![1; 32]).unwrap();
+        b.insert(forged, Status::Trusted);
+
+        // Blocks with another status are ignored
+        b.insert(blocks[0].clone(), Status::Verified);
+
+        let heights = |indices: &[usize]| -> Vec<Height> {
+            indices.iter().map(|&i| blocks[i].height()).collect()
+        };
+
+        assert_eq!(
+            diff(&a, &b, Status::Trusted),
+            StoreDiff {
+                only_in_a: heights(&[0]),
+                only_in_b: heights(&[4]),
+                different: heights(&[2]),
+            }
+        );
+        assert!(diff(&a, &a, Status::Trusted).is_empty());
+    }
+
+    fn testgen_to_lb(tm_lb: TGLightBlock) -> LightBlock {
+        LightBlock {
+            signed_header: tm_lb.signed_header,
+            validators: tm_lb.validators,
+            next_validators: tm_lb.next_validators,
+            provider: tm_lb.provider,
+        }
+    }
+}
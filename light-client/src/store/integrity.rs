@@ -0,0 +1,79 @@
+//! Consistency checks over the contents of a light store.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    contracts::adjacent_blocks_chain,
+    store::LightStore,
+    verifier::types::{Hash, Height, LightBlock, Status},
+};
+
+/// An inconsistency found in a light store by [`LightStore::verify_integrity`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// The block stored under the given height actually has another height.
+    HeightMismatch {
+        /// Status of the entry
+        status: Status,
+        /// Height the block is stored under
+        key: Height,
+        /// Height of the block itself
+        height: Height,
+    },
+    /// The entry stored under the given key could not be decoded.
+    Corrupt {
+        /// Status of the entry
+        status: Status,
+        /// Raw key of the entry
+        key: Vec<u8>,
+    },
+    /// The block at the given height does not link to the block right below it.
+    BrokenAdjacency {
+        /// Height of the block whose `last_block_id` does not match
+        height: Height,
+        /// Hash of the header of the block right below
+        expected: Hash,
+        /// Hash found in the `last_block_id` of the block, if any
+        found: Option<Hash>,
+    },
+}
+
+/// Check that every trusted or verified block in the store links to the
+/// trusted or verified block right below it.
+pub(crate) fn adjacency_issues<S>(store: &S) -> Vec<IntegrityIssue>
+where
+    S: LightStore + ?Sized,
+{
+    // Prefer the trusted block if a height is stored with both statuses
+    let mut blocks: BTreeMap<Height, LightBlock> = BTreeMap::new();
+    for status in [Status::Verified, Status::Trusted] {
+        blocks.extend(store.all(status).map(|lb| (lb.height(), lb)));
+    }
+
+    let mut issues = Vec::new();
+    let mut below: Option<&LightBlock> = None;
+
+    for block in blocks.values() {
+        if let Some(below) = below.filter(|b| b.height().increment() == block.height()) {
+            if adjacent_blocks_chain(below, block).is_err() {
+                issues.push(IntegrityIssue::BrokenAdjacency {
+                    height: block.height(),
+                    expected: below.signed_header.header.hash(),
+                    found: block.signed_header.header.last_block_id.map(|id| id.hash),
+                });
+            }
+        }
+
+        below = Some(block);
+    }
+
+    issues
+}
+
+pub(crate) fn into_result(issues: Vec<IntegrityIssue>) -> Result<(), Vec<IntegrityIssue>> {
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
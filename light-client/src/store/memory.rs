@@ -3,7 +3,7 @@
 use std::collections::{btree_map::Entry::*, BTreeMap};
 
 use crate::{
-    store::{LightStore, Status},
+    store::{FailureReason, HeightRange, LightStore, Status},
     verifier::types::{Height, LightBlock},
 };
 
@@ -12,6 +12,8 @@ use crate::{
 struct StoreEntry {
     light_block: LightBlock,
     status: Status,
+    /// Why the block failed verification, if recorded
+    reason: Option<FailureReason>,
 }
 
 impl StoreEntry {
@@ -19,6 +21,7 @@ impl StoreEntry {
         Self {
             light_block,
             status,
+            reason: None,
         }
     }
 }
@@ -64,6 +67,23 @@ impl LightStore for MemoryStore {
         self.insert(light_block.clone(), status);
     }
 
+    fn update_failed(&mut self, light_block: &LightBlock, reason: FailureReason) {
+        let mut entry = StoreEntry::new(light_block.clone(), Status::Failed);
+        entry.reason = Some(reason);
+        self.store.insert(light_block.height(), entry);
+    }
+
+    fn failures(&self) -> Box<dyn Iterator<Item = (LightBlock, FailureReason)>> {
+        let failures: Vec<_> = self
+            .store
+            .values()
+            .filter(|e| e.status == Status::Failed)
+            .filter_map(|e| Some((e.light_block.clone(), e.reason.clone()?)))
+            .collect();
+
+        Box::new(failures.into_iter())
+    }
+
     fn highest(&self, status: Status) -> Option<LightBlock> {
         self.store
             .iter()
@@ -89,6 +109,36 @@ impl LightStore for MemoryStore {
             .map(|(_, e)| e.light_block.clone())
     }
 
+    fn trusted_ceiling(&self, height: Height) -> Option<LightBlock> {
+        self.store
+            .range(height..)
+            .find(|(_, e)| e.status == Status::Trusted)
+            .map(|(_, e)| e.light_block.clone())
+    }
+
+    fn trusted_floor(&self, height: Height) -> Option<LightBlock> {
+        self.store
+            .range(..=height)
+            .rev()
+            .find(|(_, e)| e.status == Status::Trusted)
+            .map(|(_, e)| e.light_block.clone())
+    }
+
+    fn get_range(
+        &self,
+        range: HeightRange,
+        status: Status,
+    ) -> Box<dyn Iterator<Item = LightBlock>> {
+        let light_blocks: Vec<_> = self
+            .store
+            .range(range.from()..=range.to())
+            .filter(|(_, e)| e.status == status)
+            .map(|(_, e)| e.light_block.clone())
+            .collect();
+
+        Box::new(light_blocks.into_iter())
+    }
+
     #[allow(clippy::needless_collect)]
     fn all(&self, status: Status) -> Box<dyn Iterator<Item = LightBlock>> {
         let light_blocks: Vec<_> = self
@@ -100,4 +150,9 @@ impl LightStore for MemoryStore {
 
         Box::new(light_blocks.into_iter())
     }
+
+    /// The entries are already ordered by height.
+    fn pending_promotion(&self) -> Box<dyn Iterator<Item = LightBlock>> {
+        self.all(Status::Verified)
+    }
 }
@@ -0,0 +1,213 @@
+//! Store layering transient writes over another store, which is left untouched.
+//!
+//! An [`OverlayStore`] answers reads from its own writes first, and from the underlying
+//! store otherwise. Writes, including removals, are only recorded in memory, so that
+//! the underlying store can be used to try out a verification without persisting any of
+//! the blocks it writes. See [`LightClient::verify_to_target_dry_run`].
+//!
+//! [`LightClient::verify_to_target_dry_run`]: crate::light_client::LightClient::verify_to_target_dry_run
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    store::{header_hash, LightStore, Status},
+    verifier::types::{Hash, Height, LightBlock},
+};
+
+/// Transient in-memory writes over a shared, read-only, light store.
+#[derive(Debug)]
+pub struct OverlayStore {
+    base: Arc<Box<dyn LightStore>>,
+    /// Blocks written to the overlay, or `None` where a block of the base was removed.
+    writes: BTreeMap<(Height, Status), Option<LightBlock>>,
+}
+
+impl OverlayStore {
+    /// Create an empty overlay over the given store.
+    ///
+    /// The base store is only ever read from. It is shared, so that it can be taken back
+    /// with [`Arc::try_unwrap`] once the overlay is dropped.
+    pub fn new(base: Arc<Box<dyn LightStore>>) -> Self {
+        Self {
+            base,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    /// Number of blocks inserted or removed through the overlay.
+    pub fn pending_writes(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// The first block of the base, from the given one downwards,
+    /// which is not shadowed by the overlay.
+    fn unshadowed_below(&self, mut light_block: LightBlock, status: Status) -> Option<LightBlock> {
+        while self.writes.contains_key(&(light_block.height(), status)) {
+            let below = light_block.height().value().checked_sub(1)?;
+            light_block = self
+                .base
+                .highest_before(Height::try_from(below).ok()?, status)?;
+        }
+
+        Some(light_block)
+    }
+
+    /// The highest block written to the overlay with the given status,
+    /// at or below the given height.
+    fn written_before(&self, height: Height, status: Status) -> Option<LightBlock> {
+        self.writes
+            .range(..=(height, Status::Trusted))
+            .rev()
+            .filter(|((_, s), _)| *s == status)
+            .find_map(|(_, light_block)| light_block.clone())
+    }
+}
+
+impl LightStore for OverlayStore {
+    fn get(&self, height: Height, status: Status) -> Option<LightBlock> {
+        match self.writes.get(&(height, status)) {
+            Some(written) => written.clone(),
+            None => self.base.get(height, status),
+        }
+    }
+
+    fn update(&mut self, light_block: &LightBlock, status: Status) {
+        let height = light_block.height();
+        for &other in Status::iter() {
+            if other != status && self.get(height, other).is_some() {
+                self.writes.insert((height, other), None);
+            }
+        }
+
+        self.insert(light_block.clone(), status);
+    }
+
+    fn insert(&mut self, light_block: LightBlock, status: Status) {
+        self.writes
+            .insert((light_block.height(), status), Some(light_block));
+    }
+
+    fn remove(&mut self, height: Height, status: Status) {
+        self.writes.insert((height, status), None);
+    }
+
+    fn highest(&self, status: Status) -> Option<LightBlock> {
+        let written = self
+            .writes
+            .iter()
+            .rev()
+            .filter(|((_, s), _)| *s == status)
+            .find_map(|(_, light_block)| light_block.clone());
+        let base = self
+            .base
+            .highest(status)
+            .and_then(|lb| self.unshadowed_below(lb, status));
+
+        written.into_iter().chain(base).max_by_key(|lb| lb.height())
+    }
+
+    fn highest_before(&self, height: Height, status: Status) -> Option<LightBlock> {
+        let written = self.written_before(height, status);
+        let base = self
+            .base
+            .highest_before(height, status)
+            .and_then(|lb| self.unshadowed_below(lb, status));
+
+        written.into_iter().chain(base).max_by_key(|lb| lb.height())
+    }
+
+    fn lowest(&self, status: Status) -> Option<LightBlock> {
+        self.all(status).next()
+    }
+
+    fn all(&self, status: Status) -> Box<dyn Iterator<Item = LightBlock>> {
+        let mut light_blocks: BTreeMap<_, _> =
+            self.base.all(status).map(|lb| (lb.height(), lb)).collect();
+
+        for ((height, _), written) in self.writes.iter().filter(|((_, s), _)| *s == status) {
+            match written {
+                Some(light_block) => light_blocks.insert(*height, light_block.clone()),
+                None => light_blocks.remove(height),
+            };
+        }
+
+        Box::new(light_blocks.into_values())
+    }
+
+    fn get_by_hash(&self, hash: Hash) -> Option<LightBlock> {
+        let height = self
+            .writes
+            .values()
+            .flatten()
+            .find(|lb| header_hash(lb) == hash)
+            .cloned()
+            .or_else(|| self.base.get_by_hash(hash))?
+            .height();
+
+        // Look the block up again through the overlay, most trusted status first,
+        // as the overlay may have removed it from the base under some of them.
+        let mut statuses = Status::iter().to_vec();
+        statuses.sort_by(|a, b| b.cmp(a));
+
+        statuses.into_iter().find_map(|status| {
+            self.get(height, status)
+                .filter(|lb| header_hash(lb) == hash)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint_testgen::{light_block::TmLightBlock as TGLightBlock, Generator, LightChain};
+
+    use super::*;
+    use crate::store::memory::MemoryStore;
+
+    #[test]
+    fn writes_shadow_the_base_store() {
+        let blocks = LightChain::default_with_length(4)
+            .light_blocks
+            .into_iter()
+            .map(|lb| lb.generate().unwrap())
+            .map(testgen_to_lb)
+            .collect::<Vec<_>>();
+
+        let mut base = MemoryStore::new();
+        for block in &blocks[..3] {
+            base.insert(block.clone(), Status::Verified);
+        }
+        let base: Arc<Box<dyn LightStore>> = Arc::new(Box::new(base));
+
+        let mut overlay = OverlayStore::new(base.clone());
+        overlay.remove(blocks[2].height(), Status::Verified);
+        overlay.update(&blocks[1], Status::Trusted);
+        overlay.insert(blocks[3].clone(), Status::Unverified);
+
+        assert_eq!(overlay.highest(Status::Verified).as_ref(), blocks.first());
+        assert_eq!(
+            overlay
+                .highest_before(blocks[3].height(), Status::Verified)
+                .as_ref(),
+            blocks.first()
+        );
+        assert_eq!(overlay.all(Status::Verified).count(), 1);
+        assert_eq!(overlay.lowest(Status::Trusted).as_ref(), blocks.get(1));
+        assert_eq!(overlay.highest(Status::Unverified).as_ref(), blocks.last());
+
+        // The base store is left as it was
+        drop(overlay);
+        let base = Arc::try_unwrap(base).unwrap();
+        assert_eq!(base.all(Status::Verified).count(), 3);
+        assert_eq!(base.all(Status::Trusted).count(), 0);
+        assert_eq!(base.all(Status::Unverified).count(), 0);
+    }
+
+    fn testgen_to_lb(tm_lb: TGLightBlock) -> LightBlock {
+        LightBlock {
+            signed_header: tm_lb.signed_header,
+            validators: tm_lb.validators,
+            next_validators: tm_lb.next_validators,
+            provider: tm_lb.provider,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Inclusive ranges of heights, used to query the light store.
+
+use core::fmt;
+
+use crate::{errors::Error, verifier::types::Height};
+
+/// A non-empty, inclusive range of heights `from..=to`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HeightRange {
+    from: Height,
+    to: Height,
+}
+
+impl HeightRange {
+    /// Construct the range of heights from `from` up to and including `to`.
+    ///
+    /// Returns an error if `from` is greater than `to`, instead of silently
+    /// yielding an empty range.
+    pub fn new(from: Height, to: Height) -> Result<Self, Error> {
+        if from > to {
+            return Err(Error::inverted_height_range(from, to));
+        }
+
+        Ok(Self { from, to })
+    }
+
+    /// The range containing only the given height.
+    pub fn single(height: Height) -> Self {
+        Self {
+            from: height,
+            to: height,
+        }
+    }
+
+    /// The lowest height in this range.
+    pub fn from(&self) -> Height {
+        self.from
+    }
+
+    /// The highest height in this range, included in the range.
+    pub fn to(&self) -> Height {
+        self.to
+    }
+
+    /// Whether the given height lies within this range, bounds included.
+    pub fn contains(&self, height: Height) -> bool {
+        self.from <= height && height <= self.to
+    }
+
+    /// The number of heights in this range, which is always at least one.
+    pub fn len(&self) -> u64 {
+        self.to.value() - self.from.value() + 1
+    }
+
+    /// Always `false`, as a range contains at least one height.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Iterate over the heights in this range, in increasing order.
+    pub fn heights(&self) -> impl Iterator<Item = Height> {
+        let to = self.to;
+        // Check the bound before incrementing, which would overflow past the maximum height
+        core::iter::successors(Some(self.from), move |h| (*h < to).then(|| h.increment()))
+    }
+}
+
+impl fmt::Display for HeightRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..={}", self.from, self.to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(height: u32) -> Height {
+        height.into()
+    }
+
+    #[test]
+    fn bounds_are_inclusive() {
+        let range = HeightRange::new(h(3), h(5)).unwrap();
+
+        assert!(!range.contains(h(2)));
+        assert!(range.contains(h(3)));
+        assert!(range.contains(h(5)));
+        assert!(!range.contains(h(6)));
+        assert_eq!(range.len(), 3);
+        assert_eq!(range.heights().collect::<Vec<_>>(), vec![h(3), h(4), h(5)]);
+    }
+
+    #[test]
+    fn single_height_range() {
+        let range = HeightRange::new(h(7), h(7)).unwrap();
+
+        assert_eq!(range, HeightRange::single(h(7)));
+        assert_eq!(range.len(), 1);
+        assert_eq!(range.heights().collect::<Vec<_>>(), vec![h(7)]);
+    }
+
+    #[test]
+    fn range_may_end_at_the_maximum_height() {
+        let max = Height::try_from(i64::MAX as u64).unwrap();
+        let before_max = Height::try_from(i64::MAX as u64 - 1).unwrap();
+        let range = HeightRange::new(before_max, max).unwrap();
+
+        assert_eq!(range.heights().collect::<Vec<_>>(), vec![before_max, max]);
+    }
+
+    #[test]
+    fn inverted_range_is_rejected() {
+        assert!(HeightRange::new(h(5), h(3)).is_err());
+    }
+}
@@ -1,41 +1,113 @@
 //! Persistent store backed by an on-disk `sled` database.
 
 pub mod utils;
-use std::path::Path;
+use std::{ops::Bound, path::Path};
 
 use utils::HeightIndexedDb;
+pub use utils::StoreFormat;
 
-use super::{LightStore, Status};
-use crate::verifier::types::{Height, LightBlock};
+use super::{
+    header_hash, prune_for_compaction, CompactionStats, FailureReason, HeightRange, LightStore,
+    Status,
+};
+#[cfg(feature = "rust-crypto")]
+use super::{integrity, IntegrityIssue};
+use crate::{
+    errors::Error,
+    verifier::types::{Hash, Height, LightBlock},
+};
 
 const UNVERIFIED: &str = "unverified";
 const VERIFIED: &str = "verified";
 const TRUSTED: &str = "trusted";
 const FAILED: &str = "failed";
+const ARCHIVED: &str = "archived";
+const HASH_INDEX: &str = "hash_index";
+const HEIGHT_INDEX: &str = "height_index";
+const FAILURE_REASONS: &str = "failure_reasons";
+
+/// Key, in the default tree, of the schema version and format of the store.
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+const SCHEMA_VERSION: u8 = 1;
 
 /// Persistent store backed by an on-disk `sled` database.
+///
+/// ## Hash index
+/// Besides the per-status trees, the store maintains a secondary index mapping
+/// header hashes to heights, which backs [`LightStore::get_by_hash`], and its reverse
+/// mapping heights to header hashes, used to find the entry to drop on removal.
+/// Keeping them up to date costs a header hash and two extra writes on every insert,
+/// and a few extra lookups on every removal to decide whether the entries must be dropped.
+///
+/// ## Failure reasons
+/// The reasons recorded by [`LightStore::update_failed`] are kept in a tree of their own,
+/// by height, and dropped whenever the failed block at that height is updated, replaced
+/// or removed.
+///
+/// ## Format
+/// Light blocks are serialized with CBOR by default, or with JSON when opened with
+/// [`StoreFormat::Json`], eg. to inspect the database while debugging.
+/// The format is recorded alongside the schema version when the store is first
+/// opened, and opening it later on with another format is rejected.
 #[derive(Debug, Clone)]
 pub struct SledStore {
     unverified_db: HeightIndexedDb<LightBlock>,
     verified_db: HeightIndexedDb<LightBlock>,
     trusted_db: HeightIndexedDb<LightBlock>,
     failed_db: HeightIndexedDb<LightBlock>,
+    archived_db: HeightIndexedDb<LightBlock>,
+    hash_index: sled::Tree,
+    height_index: sled::Tree,
+    failure_reasons: HeightIndexedDb<FailureReason>,
+    db: sled::Db,
 }
 
 impl SledStore {
     /// Open a sled database and create a new persistent store from it.
-    pub fn open(db: impl AsRef<Path>) -> Result<Self, sled::Error> {
-        Ok(Self::new(sled::open(db)?))
+    ///
+    /// Fails if the store was previously written with a format other than CBOR.
+    pub fn open(db: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open_with_format(db, StoreFormat::Cbor)
     }
 
     /// Create a new persistent store from a sled database that is already open.
-    pub fn new(db: sled::Db) -> Self {
-        Self {
-            unverified_db: HeightIndexedDb::new(db.open_tree(UNVERIFIED).unwrap()),
-            verified_db: HeightIndexedDb::new(db.open_tree(VERIFIED).unwrap()),
-            trusted_db: HeightIndexedDb::new(db.open_tree(TRUSTED).unwrap()),
-            failed_db: HeightIndexedDb::new(db.open_tree(FAILED).unwrap()),
-        }
+    ///
+    /// Fails if the store was previously written with a format other than CBOR.
+    pub fn new(db: sled::Db) -> Result<Self, Error> {
+        Self::with_format(db, StoreFormat::Cbor)
+    }
+
+    /// Open a sled database and create a new persistent store from it,
+    /// serializing light blocks with the given format.
+    pub fn open_with_format(db: impl AsRef<Path>, format: StoreFormat) -> Result<Self, Error> {
+        Self::with_format(sled::open(db).map_err(Error::sled)?, format)
+    }
+
+    /// Create a new persistent store from a sled database that is already open,
+    /// serializing light blocks with the given format.
+    pub fn with_format(db: sled::Db, format: StoreFormat) -> Result<Self, Error> {
+        check_format(&db, format)?;
+
+        let open_tree = |name| {
+            db.open_tree(name)
+                .map(|tree| HeightIndexedDb::with_format(tree, format))
+                .map_err(Error::sled)
+        };
+
+        Ok(Self {
+            unverified_db: open_tree(UNVERIFIED)?,
+            verified_db: open_tree(VERIFIED)?,
+            trusted_db: open_tree(TRUSTED)?,
+            failed_db: open_tree(FAILED)?,
+            archived_db: open_tree(ARCHIVED)?,
+            hash_index: db.open_tree(HASH_INDEX).map_err(Error::sled)?,
+            height_index: db.open_tree(HEIGHT_INDEX).map_err(Error::sled)?,
+            failure_reasons: db
+                .open_tree(FAILURE_REASONS)
+                .map(|tree| HeightIndexedDb::with_format(tree, format))
+                .map_err(Error::sled)?,
+            db,
+        })
     }
 
     fn db(&self, status: Status) -> &HeightIndexedDb<LightBlock> {
@@ -44,7 +116,92 @@ impl SledStore {
             Status::Verified => &self.verified_db,
             Status::Trusted => &self.trusted_db,
             Status::Failed => &self.failed_db,
+            Status::Archived => &self.archived_db,
+        }
+    }
+
+    fn index_hash(&self, light_block: &LightBlock) {
+        let hash = header_hash(light_block);
+        let height = light_block.height().value().to_be_bytes();
+
+        // Only one hash is kept per height, drop the one of the block previously
+        // stored at that height, if any.
+        let previous = self
+            .height_index
+            .insert(height, hash.as_bytes())
+            .ok()
+            .flatten();
+
+        if let Some(previous) = previous.filter(|previous| previous != hash.as_bytes()) {
+            self.hash_index.remove(previous).ok();
+        }
+
+        self.hash_index.insert(hash.as_bytes(), &height).ok();
+    }
+
+    /// Drop the failure reasons recorded at the given heights, when the blocks
+    /// stored there with the given status are replaced or removed.
+    fn drop_failure_reasons(&self, status: Status, heights: &[Height]) {
+        if status != Status::Failed {
+            return;
+        }
+
+        for height in heights {
+            self.failure_reasons.remove(*height).ok();
+        }
+    }
+
+    /// Drop the index entry for the block at the given height,
+    /// unless it is still stored under another status.
+    fn unindex_hash(&self, height: Height) {
+        let still_stored = Status::iter()
+            .iter()
+            .any(|status| self.db(*status).contains_key(height).unwrap_or(false));
+
+        if still_stored {
+            return;
         }
+
+        let stale = self
+            .height_index
+            .remove(height.value().to_be_bytes())
+            .ok()
+            .flatten();
+
+        if let Some(hash) = stale {
+            self.hash_index.remove(hash).ok();
+        }
+    }
+}
+
+/// Record the schema version and format of the store if it is new,
+/// or check that they match the requested format otherwise.
+///
+/// Stores written before the schema version was recorded are CBOR-encoded.
+fn check_format(db: &sled::Db, format: StoreFormat) -> Result<(), Error> {
+    let found = match db.get(SCHEMA_VERSION_KEY).map_err(Error::sled)? {
+        Some(schema) => match schema.as_ref() {
+            [SCHEMA_VERSION, tag] => StoreFormat::from_tag(*tag),
+            _ => None,
+        },
+        None if db.tree_names().len() > 1 => Some(StoreFormat::Cbor),
+        None => {
+            db.insert(SCHEMA_VERSION_KEY, &[SCHEMA_VERSION, format.tag()])
+                .map_err(Error::sled)?;
+            return Ok(());
+        },
+    };
+
+    match found {
+        Some(found) if found == format => Ok(()),
+        Some(found) => Err(Error::store_format_mismatch(
+            format.to_string(),
+            found.to_string(),
+        )),
+        None => Err(Error::store_format_mismatch(
+            format.to_string(),
+            "unknown".to_string(),
+        )),
     }
 }
 
@@ -53,6 +210,26 @@ impl LightStore for SledStore {
         self.db(status).get(height).ok().flatten()
     }
 
+    /// Looks the keys up tree by tree, each in increasing order of height, as sled has no
+    /// multi-get of its own. Each block is only read and deserialized once, however many
+    /// times its key is given.
+    fn get_many(&self, keys: &[(Height, Status)]) -> Vec<Option<LightBlock>> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by_key(|&i| (keys[i].1, keys[i].0));
+
+        let mut found = vec![None; keys.len()];
+        let mut previous: Option<usize> = None;
+        for i in order {
+            found[i] = match previous {
+                Some(p) if keys[p] == keys[i] => found[p].clone(),
+                _ => self.get(keys[i].0, keys[i].1),
+            };
+            previous = Some(i);
+        }
+
+        found
+    }
+
     fn update(&mut self, light_block: &LightBlock, status: Status) {
         let height = light_block.height();
 
@@ -63,16 +240,82 @@ impl LightStore for SledStore {
         }
 
         self.db(status).insert(height, light_block).ok();
+        self.failure_reasons.remove(height).ok();
+        self.index_hash(light_block);
+    }
+
+    fn update_failed(&mut self, light_block: &LightBlock, reason: FailureReason) {
+        self.update(light_block, Status::Failed);
+        self.failure_reasons
+            .insert(light_block.height(), &reason)
+            .ok();
+    }
+
+    fn failures(&self) -> Box<dyn Iterator<Item = (LightBlock, FailureReason)>> {
+        let failure_reasons = self.failure_reasons.clone();
+        Box::new(self.failed_db.iter().filter_map(move |light_block| {
+            let reason = failure_reasons.get(light_block.height()).ok().flatten()?;
+            Some((light_block, reason))
+        }))
     }
 
     fn insert(&mut self, light_block: LightBlock, status: Status) {
         self.db(status)
             .insert(light_block.height(), &light_block)
             .ok();
+        self.drop_failure_reasons(status, &[light_block.height()]);
+        self.index_hash(&light_block);
     }
 
     fn remove(&mut self, height: Height, status: Status) {
         self.db(status).remove(height).ok();
+        self.drop_failure_reasons(status, &[height]);
+        self.unindex_hash(height);
+    }
+
+    fn remove_all(&mut self, status: Status) -> usize {
+        let removed = self.db(status).remove_range(..).unwrap_or_default();
+        self.drop_failure_reasons(status, &removed);
+        for height in &removed {
+            self.unindex_hash(*height);
+        }
+        removed.len()
+    }
+
+    fn remove_above(&mut self, height: Height, status: Status) -> usize {
+        let removed = self
+            .db(status)
+            .remove_range((Bound::Excluded(height), Bound::Unbounded))
+            .unwrap_or_default();
+        self.drop_failure_reasons(status, &removed);
+        for height in &removed {
+            self.unindex_hash(*height);
+        }
+        removed.len()
+    }
+
+    fn prune_below(&mut self, height: Height, status: Status) -> usize {
+        let removed = self.db(status).remove_range(..height).unwrap_or_default();
+        self.drop_failure_reasons(status, &removed);
+        for height in &removed {
+            self.unindex_hash(*height);
+        }
+        removed.len()
+    }
+
+    /// Flushes the database before measuring its size again, so that the removals
+    /// are accounted for.
+    fn compact(&mut self, below: Height) -> Result<CompactionStats, Error> {
+        let bytes_before = self.size_on_disk()?;
+        let entries_removed = prune_for_compaction(self, below);
+        self.db.flush().map_err(Error::sled)?;
+        let bytes_after = self.size_on_disk()?;
+
+        Ok(CompactionStats {
+            bytes_before,
+            bytes_after,
+            entries_removed,
+        })
     }
 
     fn highest(&self, status: Status) -> Option<LightBlock> {
@@ -87,17 +330,101 @@ impl LightStore for SledStore {
         self.db(status).iter().next()
     }
 
+    fn trusted_ceiling(&self, height: Height) -> Option<LightBlock> {
+        self.trusted_db.range(height..).next()
+    }
+
+    fn trusted_floor(&self, height: Height) -> Option<LightBlock> {
+        self.trusted_db.range(..=height).next_back()
+    }
+
+    fn get_range(
+        &self,
+        range: HeightRange,
+        status: Status,
+    ) -> Box<dyn Iterator<Item = LightBlock>> {
+        Box::new(self.db(status).range(range.from()..=range.to()))
+    }
+
     fn all(&self, status: Status) -> Box<dyn Iterator<Item = LightBlock>> {
         Box::new(self.db(status).iter())
     }
+
+    /// Yields the verified blocks as they are read, as the keys of the store
+    /// are big-endian heights.
+    fn pending_promotion(&self) -> Box<dyn Iterator<Item = LightBlock>> {
+        self.all(Status::Verified)
+    }
+
+    fn size_on_disk(&self) -> Result<Option<u64>, Error> {
+        self.db.size_on_disk().map(Some).map_err(Error::sled)
+    }
+
+    /// In addition to the default checks, ensures that every entry can be decoded
+    /// and that every block is stored under its own height.
+    #[cfg(feature = "rust-crypto")]
+    fn verify_integrity(&self) -> Result<(), Vec<IntegrityIssue>> {
+        let mut issues = Vec::new();
+
+        for status in Status::iter() {
+            for (key, value) in self.db(*status).entries() {
+                let light_block = match value {
+                    Ok(light_block) => light_block,
+                    Err(_) => {
+                        issues.push(IntegrityIssue::Corrupt {
+                            status: *status,
+                            key: key.to_vec(),
+                        });
+                        continue;
+                    },
+                };
+
+                let stored_height = <[u8; 8]>::try_from(key.as_ref())
+                    .ok()
+                    .map(u64::from_be_bytes)
+                    .and_then(|h| Height::try_from(h).ok());
+
+                match stored_height {
+                    Some(height) if height == light_block.height() => (),
+                    Some(height) => issues.push(IntegrityIssue::HeightMismatch {
+                        status: *status,
+                        key: height,
+                        height: light_block.height(),
+                    }),
+                    None => issues.push(IntegrityIssue::Corrupt {
+                        status: *status,
+                        key: key.to_vec(),
+                    }),
+                }
+            }
+        }
+
+        issues.extend(integrity::adjacency_issues(self));
+        integrity::into_result(issues)
+    }
+
+    fn get_by_hash(&self, hash: Hash) -> Option<LightBlock> {
+        let height = self.hash_index.get(hash.as_bytes()).ok().flatten()?;
+        let height = Height::try_from(u64::from_be_bytes(height.as_ref().try_into().ok()?)).ok()?;
+
+        // Check the most trusted statuses first
+        let mut statuses = Status::iter().to_vec();
+        statuses.sort_unstable_by(|a, b| b.cmp(a));
+
+        statuses
+            .into_iter()
+            .flat_map(|status| self.get(height, status))
+            .find(|lb| header_hash(lb) == hash)
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "rust-crypto"))]
 mod tests {
     use tempfile::tempdir;
     use tendermint_testgen::{light_block::TmLightBlock as TGLightBlock, Generator, LightChain};
 
     use super::*;
+    use crate::store::{cbor_round_trip, memory::MemoryStore, StoreConflict};
 
     #[test]
     fn highest_returns_latest_block() {
@@ -131,6 +458,225 @@ mod tests {
         })
     }
 
+    #[test]
+    fn get_range_returns_blocks_within_bounds() {
+        with_blocks(10, |mut db, blocks| {
+            for block in blocks.iter().cloned() {
+                db.insert(block, Status::Verified);
+            }
+
+            let range = HeightRange::new(3_u32.into(), 6_u32.into()).unwrap();
+            let in_range: Vec<_> = db.get_range(range, Status::Verified).collect();
+
+            assert_eq!(in_range, blocks[2..6].to_vec());
+            assert_eq!(db.get_range(range, Status::Trusted).count(), 0);
+        })
+    }
+
+    #[test]
+    fn pending_promotion_yields_verified_blocks_by_height() {
+        with_blocks(10, |mut db, blocks| {
+            for (i, block) in blocks.iter().cloned().enumerate().rev() {
+                let status = if i % 2 == 0 {
+                    Status::Verified
+                } else {
+                    Status::Trusted
+                };
+                db.insert(block, status);
+            }
+
+            let pending: Vec<_> = db.pending_promotion().collect();
+            let verified: Vec<_> = blocks.into_iter().step_by(2).collect();
+            assert_eq!(pending, verified);
+        })
+    }
+
+    #[test]
+    fn get_many_preserves_the_order_of_the_keys() {
+        with_blocks(4, |mut db, blocks| {
+            for block in &blocks[..3] {
+                db.insert(block.clone(), Status::Verified);
+            }
+            db.insert(blocks[3].clone(), Status::Trusted);
+
+            let keys = [
+                (blocks[3].height(), Status::Trusted),
+                (blocks[0].height(), Status::Verified),
+                (blocks[3].height(), Status::Verified),
+                (blocks[2].height(), Status::Verified),
+                (blocks[0].height(), Status::Verified),
+            ];
+
+            let expected = vec![
+                Some(blocks[3].clone()),
+                Some(blocks[0].clone()),
+                None,
+                Some(blocks[2].clone()),
+                Some(blocks[0].clone()),
+            ];
+            assert_eq!(db.get_many(&keys), expected);
+            assert!(db.get_many(&[]).is_empty());
+        })
+    }
+
+    #[test]
+    fn trusted_commitment_only_depends_on_trusted_blocks() {
+        with_blocks(4, |mut db, blocks| {
+            let empty = db.trusted_commitment();
+
+            for block in blocks.iter().rev() {
+                db.insert(block.clone(), Status::Trusted);
+            }
+            db.remove(blocks[2].height(), Status::Trusted);
+            db.insert(blocks[2].clone(), Status::Verified);
+
+            // Another store, filled in another order and with other untrusted blocks
+            let mut memory = MemoryStore::new();
+            for block in [&blocks[0], &blocks[1], &blocks[3]] {
+                memory.insert(block.clone(), Status::Trusted);
+            }
+            memory.insert(blocks[2].clone(), Status::Unverified);
+
+            assert_eq!(db.trusted_commitment(), memory.trusted_commitment());
+            assert_ne!(db.trusted_commitment(), empty);
+
+            memory.remove(blocks[0].height(), Status::Trusted);
+            assert_ne!(db.trusted_commitment(), memory.trusted_commitment());
+        })
+    }
+
+    #[test]
+    fn trusted_ceiling_and_floor_return_nearest_trusted_blocks() {
+        with_blocks(10, |mut db, blocks| {
+            for (i, block) in blocks.iter().cloned().enumerate() {
+                let status = if i % 3 == 0 {
+                    Status::Trusted
+                } else {
+                    Status::Verified
+                };
+                db.insert(block, status);
+            }
+
+            // Blocks at heights 1, 4, 7 and 10 are trusted
+            assert_eq!(db.trusted_ceiling(2_u32.into()).as_ref(), Some(&blocks[3]));
+            assert_eq!(db.trusted_ceiling(4_u32.into()).as_ref(), Some(&blocks[3]));
+            assert_eq!(db.trusted_ceiling(11_u32.into()), None);
+
+            assert_eq!(db.trusted_floor(6_u32.into()).as_ref(), Some(&blocks[3]));
+            assert_eq!(db.trusted_floor(7_u32.into()).as_ref(), Some(&blocks[6]));
+            assert_eq!(db.trusted_floor(20_u32.into()).as_ref(), Some(&blocks[9]));
+        })
+    }
+
+    #[test]
+    fn remove_above_and_remove_all_keep_other_statuses() {
+        with_blocks(10, |mut db, blocks| {
+            db.insert(blocks[0].clone(), Status::Trusted);
+            for block in blocks.iter().skip(1).cloned() {
+                db.insert(block, Status::Verified);
+            }
+
+            assert_eq!(db.remove_above(5_u32.into(), Status::Verified), 5);
+            assert_eq!(db.highest(Status::Verified).as_ref(), Some(&blocks[4]));
+            assert!(db
+                .get_by_hash(blocks[9].signed_header.header.hash())
+                .is_none());
+
+            assert_eq!(db.remove_all(Status::Verified), 4);
+            assert_eq!(db.all(Status::Verified).count(), 0);
+            assert_eq!(db.highest(Status::Trusted).as_ref(), Some(&blocks[0]));
+        })
+    }
+
+    #[test]
+    fn size_on_disk_grows_with_the_store() {
+        with_blocks(10, |mut db, blocks| {
+            let empty = db.size_on_disk().unwrap().unwrap();
+
+            for block in blocks {
+                db.insert(block, Status::Verified);
+            }
+            db.db.flush().unwrap();
+
+            assert!(db.size_on_disk().unwrap().unwrap() > empty);
+        })
+    }
+
+    #[test]
+    fn set_anchor_archives_previously_trusted_blocks() {
+        with_blocks(5, |mut db, blocks| {
+            db.insert(blocks[0].clone(), Status::Trusted);
+            db.insert(blocks[1].clone(), Status::Trusted);
+            db.insert(blocks[2].clone(), Status::Verified);
+            db.insert(blocks[4].clone(), Status::Unverified);
+
+            assert!(db.set_anchor(blocks[2].height()));
+
+            let trusted: Vec<_> = db.all(Status::Trusted).collect();
+            assert_eq!(trusted, vec![blocks[2].clone()]);
+            assert_eq!(db.all(Status::Verified).count(), 0);
+
+            let archived: Vec<_> = db.all(Status::Archived).collect();
+            assert_eq!(archived, vec![blocks[0].clone(), blocks[1].clone()]);
+
+            // Without a block at the given height, the store is left untouched
+            assert!(!db.set_anchor(blocks[3].height()));
+            assert_eq!(db.highest(Status::Trusted), Some(blocks[2].clone()));
+
+            // Nor can an unverified block become the anchor
+            assert!(!db.set_anchor(blocks[4].height()));
+            assert_eq!(db.highest(Status::Trusted), Some(blocks[2].clone()));
+            assert_eq!(
+                db.get(blocks[4].height(), Status::Unverified),
+                Some(blocks[4].clone())
+            );
+
+            // An archived block can be made the anchor again
+            assert!(db.set_anchor(blocks[0].height()));
+            assert_eq!(db.highest(Status::Trusted), Some(blocks[0].clone()));
+            assert_eq!(
+                db.get(blocks[2].height(), Status::Archived),
+                Some(blocks[2].clone())
+            );
+        })
+    }
+
+    #[test]
+    fn insert_checked_rejects_conflicting_blocks() {
+        with_blocks(2, |mut db, blocks| {
+            db.insert_checked(blocks[0].clone(), Status::Verified)
+                .unwrap();
+
+            // Inserting the same block again is fine
+            db.insert_checked(blocks[0].clone(), Status::Verified)
+                .unwrap();
+
+            let mut forged = blocks[0].clone();
+            forged.signed_header.header.app_hash = blocks[1].signed_header.header.app_hash.clone();
+            forged.signed_header.header.time = blocks[1].signed_header.header.time;
+
+            let conflict = db
+                .insert_checked(forged.clone(), Status::Verified)
+                .unwrap_err();
+            assert_eq!(
+                conflict,
+                StoreConflict {
+                    height: blocks[0].height(),
+                    status: Status::Verified,
+                    existing: blocks[0].signed_header.header.hash(),
+                    inserted: forged.signed_header.header.hash(),
+                }
+            );
+            assert_eq!(
+                db.get(blocks[0].height(), Status::Verified),
+                Some(blocks[0].clone())
+            );
+
+            // The same block under another status is not a conflict
+            db.insert_checked(forged, Status::Unverified).unwrap();
+        })
+    }
+
     #[test]
     fn lowest_returns_earliest_block() {
         with_blocks(10, |mut db, blocks| {
@@ -141,19 +687,292 @@ mod tests {
         })
     }
 
-    fn with_blocks(height: u64, f: impl FnOnce(SledStore, Vec<LightBlock>)) {
+    #[test]
+    fn get_by_hash_returns_block() {
+        with_blocks(10, |mut db, blocks| {
+            for block in blocks.iter().cloned() {
+                db.insert(block, Status::Verified);
+            }
+
+            for block in &blocks {
+                let hash = block.signed_header.header.hash();
+                assert_eq!(db.get_by_hash(hash).as_ref(), Some(block));
+            }
+
+            let removed = &blocks[3];
+            db.remove(removed.height(), Status::Verified);
+            assert_eq!(db.get_by_hash(removed.signed_header.header.hash()), None);
+
+            db.update(&blocks[4], Status::Trusted);
+            assert_eq!(
+                db.get_by_hash(blocks[4].signed_header.header.hash()),
+                Some(blocks[4].clone())
+            );
+        })
+    }
+
+    #[test]
+    fn get_by_hash_forgets_replaced_blocks() {
+        with_blocks(2, |mut db, blocks| {
+            db.insert(blocks[0].clone(), Status::Verified);
+
+            let mut forged = blocks[0].clone();
+            forged.signed_header.header.time = blocks[1].signed_header.header.time;
+            db.insert(forged.clone(), Status::Verified);
+
+            assert_eq!(db.get_by_hash(blocks[0].signed_header.header.hash()), None);
+            assert_eq!(
+                db.get_by_hash(forged.signed_header.header.hash()),
+                Some(forged)
+            );
+            assert!(!db
+                .hash_index
+                .contains_key(blocks[0].signed_header.header.hash().as_bytes())
+                .unwrap());
+        })
+    }
+
+    #[test]
+    fn cbor_round_trip_matches_the_store_encoding() {
+        with_blocks(1, |mut db, blocks| {
+            let block = blocks[0].clone();
+            db.insert(block.clone(), Status::Verified);
+
+            let round_tripped = cbor_round_trip(&block).unwrap();
+            assert_eq!(round_tripped, block);
+            assert_eq!(
+                db.get(block.height(), Status::Verified),
+                Some(round_tripped)
+            );
+        })
+    }
+
+    #[test]
+    fn json_format_round_trips() {
+        let tmp_dir = tempdir().unwrap();
+        let blocks = gen_blocks(3);
+
+        // Reopening the store from the same handle, as sled may still hold the lock
+        // on the database for a moment after the last handle is dropped
+        let sled_db = sled::open(tmp_dir.path()).unwrap();
+        {
+            let mut db = SledStore::with_format(sled_db.clone(), StoreFormat::Json).unwrap();
+            for block in blocks.iter().cloned() {
+                db.insert(block, Status::Verified);
+            }
+        }
+
+        let db = SledStore::with_format(sled_db, StoreFormat::Json).unwrap();
+        assert_eq!(db.all(Status::Verified).collect::<Vec<_>>(), blocks);
+    }
+
+    #[test]
+    fn mixed_formats_are_rejected() {
         let tmp_dir = tempdir().unwrap();
-        let db = SledStore::open(tmp_dir).unwrap();
 
-        let chain = LightChain::default_with_length(height);
-        let blocks = chain
+        let sled_db = sled::open(tmp_dir.path()).unwrap();
+        {
+            let mut db = SledStore::with_format(sled_db.clone(), StoreFormat::Cbor).unwrap();
+            db.insert(gen_blocks(1).remove(0), Status::Verified);
+        }
+
+        let err = SledStore::with_format(sled_db, StoreFormat::Json).unwrap_err();
+        assert!(matches!(
+            err.detail(),
+            crate::errors::ErrorDetail::StoreFormatMismatch(_)
+        ));
+    }
+
+    #[test]
+    fn cbor_store_rejects_json_data() {
+        let tmp_dir = tempdir().unwrap();
+
+        let sled_db = sled::open(tmp_dir.path()).unwrap();
+        {
+            let mut db = SledStore::with_format(sled_db.clone(), StoreFormat::Json).unwrap();
+            db.insert(gen_blocks(1).remove(0), Status::Verified);
+        }
+
+        let err = SledStore::new(sled_db).unwrap_err();
+        assert!(matches!(
+            err.detail(),
+            crate::errors::ErrorDetail::StoreFormatMismatch(_)
+        ));
+    }
+
+    #[test]
+    fn verify_integrity_reports_issues() {
+        let tmp_dir = tempdir().unwrap();
+        let db = sled::open(tmp_dir.path()).unwrap();
+        let mut store = SledStore::new(db.clone()).unwrap();
+
+        let blocks = gen_blocks(4);
+        for block in blocks.iter().cloned() {
+            store.insert(block, Status::Verified);
+        }
+        assert_eq!(store.verify_integrity(), Ok(()));
+
+        // Unlink block 2 from block 1, store block 4 under height 3,
+        // and garble the unverified tree
+        let mut unlinked = blocks[1].clone();
+        unlinked.signed_header.header.last_block_id = None;
+        store.insert(unlinked, Status::Verified);
+
+        let verified = db.open_tree(VERIFIED).unwrap();
+        verified.remove(3_u64.to_be_bytes()).unwrap();
+        let moved = verified.remove(4_u64.to_be_bytes()).unwrap().unwrap();
+        verified.insert(3_u64.to_be_bytes(), moved).unwrap();
+
+        let unverified = db.open_tree(UNVERIFIED).unwrap();
+        unverified.insert(5_u64.to_be_bytes(), b"garbage").unwrap();
+
+        let issues = store.verify_integrity().unwrap_err();
+        assert_eq!(issues.len(), 3, "{issues:?}");
+        assert!(issues.contains(&IntegrityIssue::HeightMismatch {
+            status: Status::Verified,
+            key: 3_u32.into(),
+            height: 4_u32.into(),
+        }));
+        assert!(issues.contains(&IntegrityIssue::Corrupt {
+            status: Status::Unverified,
+            key: 5_u64.to_be_bytes().to_vec(),
+        }));
+        assert!(issues.iter().any(|issue| matches!(
+            issue,
+            IntegrityIssue::BrokenAdjacency { height, found: None, .. } if *height == 2_u32.into()
+        )));
+    }
+
+    #[test]
+    fn compact_removes_the_blocks_below_the_given_height() {
+        with_blocks(10, |mut db, blocks| {
+            let mut memory = MemoryStore::new();
+            for block in &blocks {
+                db.insert(block.clone(), Status::Verified);
+                memory.insert(block.clone(), Status::Verified);
+            }
+            db.insert(blocks[0].clone(), Status::Trusted);
+            db.db.flush().unwrap();
+
+            let below = blocks[4].height();
+            let stats = db.compact(below).unwrap();
+            assert_eq!(stats.entries_removed, 4);
+            assert!(stats.bytes_before.is_some() && stats.bytes_after.is_some());
+            assert_eq!(db.lowest(Status::Verified).as_ref(), blocks.get(4));
+            assert_eq!(db.lowest(Status::Trusted).as_ref(), blocks.first());
+
+            let stats = memory.compact(below).unwrap();
+            assert_eq!(
+                stats,
+                CompactionStats {
+                    bytes_before: None,
+                    bytes_after: None,
+                    entries_removed: 4,
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn compact_keeps_the_highest_trusted_block() {
+        with_blocks(10, |mut db, blocks| {
+            let mut memory = MemoryStore::new();
+            for block in &blocks[..3] {
+                db.insert(block.clone(), Status::Trusted);
+                memory.insert(block.clone(), Status::Trusted);
+            }
+
+            let below = blocks[8].height();
+            let stores: [&mut dyn LightStore; 2] = [&mut db, &mut memory];
+            for store in stores {
+                assert_eq!(store.compact(below).unwrap().entries_removed, 2);
+                assert_eq!(
+                    store.all(Status::Trusted).collect::<Vec<_>>(),
+                    [blocks[2].clone()]
+                );
+
+                // The only trusted block is kept, even far below
+                assert_eq!(store.compact(below).unwrap().entries_removed, 0);
+                assert_eq!(store.highest(Status::Trusted).as_ref(), blocks.get(2));
+            }
+        })
+    }
+
+    #[test]
+    fn failure_reasons_are_dropped_on_update() {
+        with_blocks(3, |mut db, blocks| {
+            let reason = |message: &str| FailureReason {
+                message: message.to_string(),
+            };
+
+            db.update_failed(&blocks[0], reason("first"));
+            db.update_failed(&blocks[2], reason("third"));
+            // Not recorded with a reason
+            db.update(&blocks[1], Status::Failed);
+
+            let failures: Vec<_> = db.failures().collect();
+            assert_eq!(
+                failures,
+                vec![
+                    (blocks[0].clone(), reason("first")),
+                    (blocks[2].clone(), reason("third")),
+                ]
+            );
+
+            // Failing again with a plain update does not keep the stale reason
+            db.update(&blocks[0], Status::Verified);
+            db.update(&blocks[0], Status::Failed);
+            assert_eq!(
+                db.failures().collect::<Vec<_>>(),
+                vec![(blocks[2].clone(), reason("third"))]
+            );
+        })
+    }
+
+    #[test]
+    fn failure_reasons_are_dropped_with_their_blocks() {
+        with_blocks(4, |mut db, blocks| {
+            let reason = FailureReason {
+                message: "stale".to_string(),
+            };
+            for block in &blocks {
+                db.update_failed(block, reason.clone());
+            }
+
+            db.remove(blocks[0].height(), Status::Failed);
+            db.insert(blocks[0].clone(), Status::Failed);
+
+            // Replacing a failed block drops its reason too
+            db.insert(blocks[1].clone(), Status::Failed);
+
+            db.prune_below(blocks[3].height(), Status::Failed);
+            db.insert(blocks[2].clone(), Status::Failed);
+
+            assert_eq!(
+                db.failures().collect::<Vec<_>>(),
+                vec![(blocks[3].clone(), reason)]
+            );
+
+            db.remove_all(Status::Failed);
+            db.insert(blocks[3].clone(), Status::Failed);
+            assert_eq!(db.failures().count(), 0);
+        })
+    }
+
+    fn gen_blocks(height: u64) -> Vec<LightBlock> {
+        LightChain::default_with_length(height)
             .light_blocks
             .into_iter()
             .map(|lb| lb.generate().unwrap())
             .map(testgen_to_lb)
-            .collect::<Vec<_>>();
+            .collect()
+    }
+
+    fn with_blocks(height: u64, f: impl FnOnce(SledStore, Vec<LightBlock>)) {
+        let tmp_dir = tempdir().unwrap();
+        let db = SledStore::open(tmp_dir).unwrap();
 
-        f(db, blocks)
+        f(db, gen_blocks(height))
     }
 
     fn testgen_to_lb(tm_lb: TGLightBlock) -> LightBlock {
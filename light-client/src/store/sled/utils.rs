@@ -1,26 +1,91 @@
 //! This modules provides type-safe interfaces over the `sled` API,
 //! by taking care of (de)serializing keys and values with the
-//! CBOR binary encoding.
+//! CBOR binary encoding, or JSON.
 
+use std::fmt;
 use std::marker::PhantomData;
 use std::ops::{Bound, RangeBounds};
 
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{errors::Error, verifier::types::Height};
+use crate::{
+    errors::Error,
+    store::{from_cbor, to_cbor},
+    verifier::types::Height,
+};
+
+/// Serialization format of the values stored in the database.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StoreFormat {
+    /// Compact binary encoding
+    #[default]
+    Cbor,
+    /// Human-readable encoding, meant for debugging
+    Json,
+}
+
+impl StoreFormat {
+    /// The tag identifying this format on disk.
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            Self::Cbor => 0,
+            Self::Json => 1,
+        }
+    }
+
+    /// The format identified by the given on-disk tag, if any.
+    pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Cbor),
+            1 => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn serialize<V: Serialize>(self, value: &V) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Cbor => to_cbor(value),
+            Self::Json => serde_json::to_vec(value).map_err(Error::serde_json),
+        }
+    }
+
+    fn deserialize<V: DeserializeOwned>(self, bytes: &[u8]) -> Result<V, Error> {
+        match self {
+            Self::Cbor => from_cbor(bytes),
+            Self::Json => serde_json::from_slice(bytes).map_err(Error::serde_json),
+        }
+    }
+}
+
+impl fmt::Display for StoreFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cbor => write!(f, "CBOR"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
 
 /// Provides a view over the database for storing key/value pairs at the given prefix.
 #[derive(Clone, Debug)]
 pub struct HeightIndexedDb<V> {
     tree: sled::Tree,
+    format: StoreFormat,
     marker: PhantomData<V>,
 }
 
 impl<V> HeightIndexedDb<V> {
     /// Create a view over the database for storing key/value pairs at the given prefix.
     pub fn new(tree: sled::Tree) -> Self {
+        Self::with_format(tree, StoreFormat::Cbor)
+    }
+
+    /// Create a view over the database for storing key/value pairs at the given prefix,
+    /// serialized with the given format.
+    pub fn with_format(tree: sled::Tree, format: StoreFormat) -> Self {
         Self {
             tree,
+            format,
             marker: PhantomData,
         }
     }
@@ -53,7 +118,7 @@ where
 
         match value {
             Some(bytes) => {
-                let value = serde_cbor::from_slice(&bytes).map_err(Error::serde_cbor)?;
+                let value = self.format.deserialize(&bytes)?;
                 Ok(value)
             },
             None => Ok(None),
@@ -72,7 +137,7 @@ where
     /// Insert a value associated with a height within this tree
     pub fn insert(&self, height: Height, value: &V) -> Result<(), Error> {
         let key = key_bytes(height);
-        let bytes = serde_cbor::to_vec(&value).map_err(Error::serde_cbor)?;
+        let bytes = self.format.serialize(value)?;
 
         self.tree.insert(key, bytes).map_err(Error::sled)?;
 
@@ -88,12 +153,51 @@ where
         Ok(())
     }
 
+    /// Remove all the values whose height lies within the given range, in a single batch,
+    /// and return the heights which were removed.
+    pub fn remove_range<R>(&self, range: R) -> Result<Vec<Height>, Error>
+    where
+        R: RangeBounds<Height>,
+    {
+        let range = (map_bound(range.start_bound()), map_bound(range.end_bound()));
+
+        let mut batch = sled::Batch::default();
+        let mut heights = Vec::new();
+
+        for key in self.tree.range(range).keys() {
+            let key = key.map_err(Error::sled)?;
+            batch.remove(key.clone());
+
+            let height = <[u8; 8]>::try_from(key.as_ref())
+                .ok()
+                .and_then(|bytes| Height::try_from(u64::from_be_bytes(bytes)).ok());
+            heights.extend(height);
+        }
+
+        self.tree.apply_batch(batch).map_err(Error::sled)?;
+
+        Ok(heights)
+    }
+
     /// Return an iterator over all values within this tree
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = V> {
+        let format = self.format;
+
+        self.tree
+            .iter()
+            .flatten()
+            .flat_map(move |(_, v)| format.deserialize(&v))
+    }
+
+    /// Return an iterator over the raw keys of this tree, along with their decoded value,
+    /// including the entries which fail to decode.
+    pub fn entries(&self) -> impl Iterator<Item = (sled::IVec, Result<V, Error>)> {
+        let format = self.format;
+
         self.tree
             .iter()
             .flatten()
-            .flat_map(|(_, v)| serde_cbor::from_slice(&v))
+            .map(move |(k, v)| (k, format.deserialize(&v)))
     }
 
     /// Return an iterator over the given range
@@ -103,10 +207,12 @@ where
     {
         let range = (map_bound(range.start_bound()), map_bound(range.end_bound()));
 
+        let format = self.format;
+
         self.tree
             .range(range)
             .flatten()
-            .flat_map(|(_, v)| serde_cbor::from_slice(&v))
+            .flat_map(move |(_, v)| format.deserialize(&v))
     }
 }
 
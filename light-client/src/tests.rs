@@ -138,11 +138,7 @@ pub fn verify_single(
 
     let verifier = crate::verifier::ProdVerifier::default();
 
-    let options = Options {
-        trust_threshold,
-        trusting_period,
-        clock_drift,
-    };
+    let options = Options::new(trust_threshold, trusting_period, clock_drift);
 
     let result = verifier.verify_update_header(
         input.as_untrusted_state(),
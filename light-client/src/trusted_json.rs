@@ -0,0 +1,183 @@
+//! Bootstrapping trust from a header copied out of a block explorer.
+//!
+//! Operators often seed the light client with a header they copied by hand, as JSON,
+//! rather than with one fetched from a node. [`FromTrustedJson`] turns such a header
+//! and its validator set into a [`LightBlock`], checking on the way that they match.
+
+use crate::{
+    errors::Error,
+    peer_labeler::{DefaultPeerLabeler, PeerLabeler},
+    verifier::types::{LightBlock, PeerId, SignedHeader, Validator, ValidatorSet},
+};
+
+/// Build a [`LightBlock`] out of a header and validator set given as JSON.
+///
+/// This is implemented for [`LightBlock`], so that it can be called as
+/// `LightBlock::from_trusted_json(..)` once this trait is in scope.
+pub trait FromTrustedJson: Sized {
+    /// Parse a light block out of the canonical Tendermint JSON encoding of its
+    /// signed header and of its validator sets.
+    ///
+    /// - `header_json` is a signed header, ie. an object with a `header` and a `commit`,
+    ///   as found in the `signed_header` field of the `/commit` RPC response.
+    /// - `validators_json` is an array of validators, as found in the `validators`
+    ///   field of the `/validators` RPC response.
+    /// - `next_validators_json` is the validator set of the next height, in the same
+    ///   format. If it is `None`, the validator set must not change at the next height.
+    ///
+    /// The validator sets must hash to the header's `validators_hash` and
+    /// `next_validators_hash` respectively.
+    ///
+    /// The returned block is not verified in any way: it is meant to be stored as the
+    /// initial trusted state of the light client, with `Status::Trusted`. Its provider is
+    /// the all-zero peer id, as it was not fetched from any node.
+    fn from_trusted_json(
+        header_json: &str,
+        validators_json: &str,
+        next_validators_json: Option<&str>,
+    ) -> Result<Self, Error>;
+}
+
+impl FromTrustedJson for LightBlock {
+    fn from_trusted_json(
+        header_json: &str,
+        validators_json: &str,
+        next_validators_json: Option<&str>,
+    ) -> Result<Self, Error> {
+        let signed_header: SignedHeader = serde_json::from_str(header_json)
+            .map_err(|e| Error::parse("signed header".to_string(), e))?;
+
+        let validators = parse_validators(validators_json, "validator set")?;
+        let next_validators = match next_validators_json {
+            Some(json) => parse_validators(json, "next validator set")?,
+            None => validators.clone(),
+        };
+
+        // Trusted JSON does not come from a peer
+        let provider = PeerId::new([0; 20]);
+
+        let header = &signed_header.header;
+        for (validators, expected) in [
+            (&validators, header.validators_hash),
+            (&next_validators, header.next_validators_hash),
+        ] {
+            if validators.hash() != expected {
+                return Err(Error::validator_set_hash_mismatch(
+                    provider,
+                    DefaultPeerLabeler.label(&provider),
+                    expected,
+                    validators.hash(),
+                ));
+            }
+        }
+
+        Ok(LightBlock::new(
+            signed_header,
+            validators,
+            next_validators,
+            provider,
+        ))
+    }
+}
+
+fn parse_validators(json: &str, what: &str) -> Result<ValidatorSet, Error> {
+    let validators: Vec<Validator> =
+        serde_json::from_str(json).map_err(|e| Error::parse(what.to_string(), e))?;
+
+    Ok(ValidatorSet::without_proposer(validators))
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint::Time;
+    use tendermint_testgen::{
+        light_block::TmLightBlock as TGLightBlock, Generator, Header,
+        LightBlock as TestgenLightBlock, LightChain, Validator,
+    };
+
+    use super::*;
+    use crate::errors::ErrorDetail;
+
+    fn light_block() -> TGLightBlock {
+        LightChain::default_with_length(1).light_blocks[0]
+            .generate()
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_header_and_validators() {
+        let tm_lb = light_block();
+        let header_json = serde_json::to_string(&tm_lb.signed_header).unwrap();
+        let validators_json = serde_json::to_string(tm_lb.validators.validators()).unwrap();
+
+        let light_block =
+            LightBlock::from_trusted_json(&header_json, &validators_json, None).unwrap();
+
+        assert_eq!(light_block.signed_header, tm_lb.signed_header);
+        assert_eq!(light_block.validators.hash(), tm_lb.validators.hash());
+        assert_eq!(
+            light_block.next_validators.hash(),
+            tm_lb.next_validators.hash()
+        );
+    }
+
+    #[test]
+    fn malformed_json_is_a_parse_error() {
+        let tm_lb = light_block();
+        let validators_json = serde_json::to_string(tm_lb.validators.validators()).unwrap();
+
+        let error =
+            LightBlock::from_trusted_json("{\"header\": 42}", &validators_json, None).unwrap_err();
+        assert!(matches!(error.detail(), ErrorDetail::Parse(_)));
+    }
+
+    #[test]
+    fn mismatched_validators_are_rejected() {
+        let tm_lb = light_block();
+        let header_json = serde_json::to_string(&tm_lb.signed_header).unwrap();
+        let validators_json = "[]";
+
+        let error = LightBlock::from_trusted_json(&header_json, validators_json, None).unwrap_err();
+        assert!(matches!(
+            error.detail(),
+            ErrorDetail::ValidatorSetHashMismatch(_)
+        ));
+    }
+
+    #[test]
+    fn changing_validators_need_the_next_validator_set() {
+        let next_validators = [Validator::new("3"), Validator::new("4")];
+        let header = Header::new(&[Validator::new("1"), Validator::new("2")])
+            .height(1)
+            .chain_id("test-chain")
+            .time(Time::from_unix_timestamp(1, 0).unwrap())
+            .next_validators(&next_validators);
+        let tm_lb = TestgenLightBlock::new_default_with_header(header)
+            .next_validators(&next_validators)
+            .generate()
+            .unwrap();
+
+        let header_json = serde_json::to_string(&tm_lb.signed_header).unwrap();
+        let validators_json = serde_json::to_string(tm_lb.validators.validators()).unwrap();
+        let next_validators_json =
+            serde_json::to_string(tm_lb.next_validators.validators()).unwrap();
+
+        let error =
+            LightBlock::from_trusted_json(&header_json, &validators_json, None).unwrap_err();
+        assert!(matches!(
+            error.detail(),
+            ErrorDetail::ValidatorSetHashMismatch(_)
+        ));
+
+        let light_block = LightBlock::from_trusted_json(
+            &header_json,
+            &validators_json,
+            Some(&next_validators_json),
+        )
+        .unwrap();
+        assert_eq!(
+            light_block.next_validators.hash(),
+            tm_lb.next_validators.hash()
+        );
+    }
+}
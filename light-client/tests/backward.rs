@@ -50,11 +50,11 @@ fn make(chain: LightChain, trusted_height: Height) -> (LightClient, State) {
         now: tendermint_testgen::helpers::get_time(chain.light_blocks.len() as u64 + 1).unwrap(),
     };
 
-    let options = Options {
-        trust_threshold: Default::default(),
-        trusting_period: Duration::from_secs(60 * 60 * 24 * 10),
-        clock_drift: Duration::from_secs(10),
-    };
+    let options = Options::new(
+        Default::default(),
+        Duration::from_secs(60 * 60 * 24 * 10),
+        Duration::from_secs(10),
+    );
 
     let light_blocks = chain
         .light_blocks
@@ -256,3 +256,25 @@ proptest! {
         bad_test(tc)?;
     }
 }
+
+#[test]
+fn stats_count_backward_steps_and_fetches() {
+    let (light_client, mut state) = make(LightChain::default_with_length(10), 10_u32.into());
+
+    let (light_block, stats) = light_client
+        .verify_to_target_with_stats(3_u32.into(), &mut state)
+        .unwrap();
+
+    assert_eq!(light_block.height(), 3_u32.into());
+    assert_eq!(stats.steps, 7);
+    assert_eq!(stats.io_requests, 7);
+    assert_eq!(stats.total_voting_power_verified, 0);
+
+    // All blocks are now in the light store, no further fetch is needed.
+    let (_, stats) = light_client
+        .verify_to_target_with_stats(3_u32.into(), &mut state)
+        .unwrap();
+
+    assert_eq!(stats.steps, 0);
+    assert_eq!(stats.io_requests, 0);
+}
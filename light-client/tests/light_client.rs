@@ -1,22 +1,29 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use tendermint_light_client::{
     components::{
-        io::{AtHeight, Io},
+        block_cache::BlockCache,
+        io::{AtHeight, Io, IoError},
         scheduler,
+        verdict_cache::{VerdictCache, VerdictCacheStats},
     },
-    errors::Error,
-    light_client::LightClient,
+    contracts::{adjacent_blocks_chain, should_refresh_trust},
+    errors::{Error, ErrorDetail},
+    light_client::{LightClient, VerifyCursor},
     state::State,
     store::{memory::MemoryStore, LightStore},
     tests::*,
     verifier::{
+        operations::VotingPowerTally,
         options::Options,
-        types::{LightBlock, Status},
-        ProdVerifier,
+        types::{LightBlock, PeerId, Status, Time, TrustedBlockState, UntrustedBlockState},
+        ProdVerifier, Verdict, Verifier,
     },
 };
-use tendermint_testgen::{light_block::default_peer_id, Tester};
+use tendermint_testgen::{
+    light_block::{default_peer_id, TmLightBlock as TGLightBlock},
+    Generator, LightChain, Tester, Validator,
+};
 
 // Link to JSON test files repo:
 // https://github.com/informalsystems/conformance-tests
@@ -40,11 +47,7 @@ fn run_test(tc: LightClientTest<LightBlock>) -> BisectionTestResult {
 
     let clock = MockClock { now };
 
-    let options = Options {
-        trust_threshold,
-        trusting_period: trusting_period.into(),
-        clock_drift,
-    };
+    let options = Options::new(trust_threshold, trusting_period.into(), clock_drift);
 
     let provider = tc.primary;
     let io = MockIo::new(provider.lite_blocks);
@@ -116,3 +119,1193 @@ fn run_tests() {
     tester.run_foreach_in_dir("bisection/single_peer");
     tester.finalize();
 }
+
+fn testgen_to_lb(tm_lb: TGLightBlock) -> LightBlock {
+    LightBlock {
+        signed_header: tm_lb.signed_header,
+        validators: tm_lb.validators,
+        next_validators: tm_lb.next_validators,
+        provider: tm_lb.provider,
+    }
+}
+
+/// The options of the light clients built by the tests below, unless they override some.
+fn options() -> Options {
+    Options::new(
+        Default::default(),
+        Duration::from_secs(60 * 60 * 24 * 10),
+        Duration::from_secs(10),
+    )
+}
+
+/// The light blocks of a generated chain of the given length.
+fn light_blocks(chain_len: u64) -> Vec<LightBlock> {
+    LightChain::default_with_length(chain_len)
+        .light_blocks
+        .into_iter()
+        .map(|lb| lb.generate().unwrap())
+        .map(testgen_to_lb)
+        .collect()
+}
+
+/// The light blocks of a chain of 9 blocks, whose validators are all replaced from height 3
+/// onwards, so that bisection from the first one tries heights 9, 5 and 3 before verifying
+/// 2 and then 9.
+fn churned_light_blocks() -> Vec<LightBlock> {
+    let replacements = [
+        Validator::new("3").voting_power(50),
+        Validator::new("4").voting_power(50),
+    ];
+    let mut testgen_blocks = LightChain::default_with_length(1).light_blocks;
+    let header = testgen_blocks[0]
+        .header
+        .as_ref()
+        .unwrap()
+        .next()
+        .next_validators(&replacements);
+    testgen_blocks.push(
+        tendermint_testgen::LightBlock::new_default_with_header(header)
+            .next_validators(&replacements),
+    );
+    while testgen_blocks.len() < 9 {
+        let next = testgen_blocks.last().unwrap().next();
+        testgen_blocks.push(next);
+    }
+
+    testgen_blocks
+        .into_iter()
+        .map(|lb| lb.generate().unwrap())
+        .map(testgen_to_lb)
+        .collect()
+}
+
+/// A state whose light store trusts the given light block.
+fn trusting(light_block: LightBlock) -> State {
+    let mut light_store = MemoryStore::new();
+    light_store.insert(light_block, Status::Trusted);
+
+    State {
+        light_store: Box::new(light_store),
+        verification_trace: HashMap::new(),
+    }
+}
+
+/// A light client with the given options, whose primary serves the given light blocks
+/// and whose clock is set right after the last of them, along with a state trusting the
+/// first one.
+fn client_with(light_blocks: Vec<LightBlock>, options: Options) -> (LightClient, State) {
+    let now = tendermint_testgen::helpers::get_time(light_blocks.len() as u64 + 1).unwrap();
+    let state = trusting(light_blocks[0].clone());
+
+    let light_client = LightClient::new(
+        default_peer_id(),
+        options,
+        MockClock { now },
+        scheduler::basic_bisecting_schedule,
+        ProdVerifier::default(),
+        MockIo::new(light_blocks),
+    );
+
+    (light_client, state)
+}
+
+/// A light client serving a generated chain of the given length, see [`client_with`].
+fn client(chain_len: u64, options: Options) -> (LightClient, State) {
+    client_with(light_blocks(chain_len), options)
+}
+
+#[test]
+fn tampered_validator_set_is_reported_as_hash_mismatch() {
+    let mut light_blocks = light_blocks(3);
+
+    // Swap the validator set of the target block for one of the same size,
+    // which does not match its header
+    let tampered = (0..light_blocks[2].validators.validators().len())
+        .map(|i| Validator::new(&format!("tampered-{i}")).generate().unwrap())
+        .collect();
+    light_blocks[2].validators = tendermint::validator::Set::without_proposer(tampered);
+
+    let (light_client, mut state) = client_with(light_blocks, options());
+
+    let result = light_client.verify_to_target(3_u32.into(), &mut state);
+
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::ValidatorSetHashMismatch(e)) => {
+            assert_eq!(e.provider, default_peer_id());
+            assert_ne!(e.header_hash, e.computed_hash)
+        },
+        other => panic!("expected a validator set hash mismatch, got {other:?}"),
+    }
+}
+
+#[test]
+fn truncated_validator_set_is_reported_as_count_mismatch() {
+    let mut light_blocks = light_blocks(3);
+
+    // Drop the last validator of the target block
+    let mut validators = light_blocks[2].validators.validators().clone();
+    let expected = validators.len();
+    validators.pop();
+    light_blocks[2].validators = tendermint::validator::Set::without_proposer(validators);
+
+    let (light_client, mut state) = client_with(light_blocks, options());
+
+    let result = light_client.verify_to_target(3_u32.into(), &mut state);
+
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::ValidatorCountMismatch(e)) => {
+            assert_eq!(e.provider, default_peer_id());
+            assert_eq!(e.expected, expected);
+            assert_eq!(e.got, expected - 1);
+        },
+        other => panic!("expected a validator count mismatch, got {other:?}"),
+    }
+}
+
+#[test]
+fn invalid_light_block_reports_its_provider() {
+    let mut light_blocks = light_blocks(3);
+
+    // Have another peer serve a target block which does not match its commit
+    let faulty_peer = "CEFEEDBADFADAD0C0CEEFACADE0ADEADBEEFC0FF".parse().unwrap();
+    light_blocks[2].provider = faulty_peer;
+    light_blocks[2].signed_header.header.app_hash =
+        tendermint::AppHash::try_from(vec![0xAB; 32]).unwrap();
+
+    let (light_client, mut state) = client_with(light_blocks, options());
+    let light_client =
+        light_client.with_peer_labeler(Arc::new(|_: &PeerId| "faulty.example.com".to_string()));
+
+    let result = light_client.verify_to_target(3_u32.into(), &mut state);
+
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::InvalidLightBlock(e)) => {
+            assert_eq!(e.provider, faulty_peer);
+            assert_eq!(e.provider_label, "faulty.example.com");
+        },
+        other => panic!("expected an invalid light block, got {other:?}"),
+    }
+}
+
+#[test]
+fn verdict_cache_is_hit_when_verifying_again() {
+    let light_blocks = light_blocks(3);
+    let (light_client, _) = client_with(light_blocks.clone(), options());
+    let light_client = light_client.with_verdict_cache(VerdictCache::new(16));
+
+    for _ in 0..2 {
+        let mut state = trusting(light_blocks[0].clone());
+        let verified = light_client.verify_to_target(3_u32.into(), &mut state);
+        assert!(verified.is_ok());
+    }
+
+    let stats = light_client.verdict_cache().unwrap().stats();
+    assert_eq!(stats, VerdictCacheStats { hits: 1, misses: 1 });
+}
+
+/// Serves the given light blocks, reusing the validator sets found in the block cache.
+struct CachingIo(MockIo);
+
+impl Io for CachingIo {
+    fn fetch_light_block(&self, height: AtHeight) -> Result<LightBlock, IoError> {
+        self.0.fetch_light_block(height)
+    }
+
+    fn fetch_light_block_cached(
+        &self,
+        height: AtHeight,
+        cache: &BlockCache,
+    ) -> Result<LightBlock, IoError> {
+        let light_block = self.0.fetch_light_block(height)?;
+        let key = light_block.signed_header.header.hash();
+
+        match cache.get(&key) {
+            Some(cached) => Ok(LightBlock::new(
+                light_block.signed_header,
+                cached.validators.clone(),
+                cached.next_validators.clone(),
+                light_block.provider,
+            )),
+            None => {
+                cache.insert(key, &light_block);
+                Ok(light_block)
+            },
+        }
+    }
+}
+
+#[test]
+fn block_cache_is_scoped_to_a_verification_call() {
+    let light_blocks = light_blocks(3);
+    let now = tendermint_testgen::helpers::get_time(4).unwrap();
+    let mut light_client = LightClient::new(
+        default_peer_id(),
+        options(),
+        MockClock { now },
+        scheduler::basic_bisecting_schedule,
+        ProdVerifier::default(),
+        CachingIo(MockIo::new(light_blocks.clone())),
+    );
+    let mut state = trusting(light_blocks[0].clone());
+
+    // The highest block is fetched again to be verified, reusing its validator sets
+    let verified = light_client.verify_to_highest(&mut state).unwrap();
+    assert_eq!(verified.height(), 3_u32.into());
+    assert_eq!(light_client.block_cache_hits(), 1);
+
+    // The highest block fetched by the previous call was dropped from the cache
+    light_client.verify_to_highest(&mut state).unwrap();
+    assert_eq!(light_client.block_cache_hits(), 1);
+}
+
+#[test]
+fn block_cache_is_shared_between_light_clients() {
+    let light_blocks = light_blocks(3);
+    let now = tendermint_testgen::helpers::get_time(4).unwrap();
+    let cache = Arc::new(BlockCache::new());
+    let caching_client = || {
+        LightClient::new(
+            default_peer_id(),
+            options(),
+            MockClock { now },
+            scheduler::basic_bisecting_schedule,
+            ProdVerifier::default(),
+            CachingIo(MockIo::new(light_blocks.clone())),
+        )
+        .with_block_cache(cache.clone())
+    };
+    let (mut primary, witness) = (caching_client(), caching_client());
+
+    // Keep the blocks fetched by the primary around for the witness
+    let scope = cache.scope();
+    let mut state = trusting(light_blocks[0].clone());
+    primary.verify_to_highest(&mut state).unwrap();
+    let hits = cache.hits();
+
+    let mut state = trusting(light_blocks[0].clone());
+    witness
+        .get_or_fetch_block(3_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(witness.block_cache_hits(), hits + 1);
+
+    drop(scope);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn verdict_cache_is_missed_for_tampered_validators() {
+    let light_blocks = light_blocks(3);
+    let (light_client, _) = client_with(light_blocks.clone(), options());
+    let light_client = light_client.with_verdict_cache(VerdictCache::new(16));
+
+    for _ in 0..2 {
+        let mut state = trusting(light_blocks[0].clone());
+        assert!(light_client
+            .verify_to_target(3_u32.into(), &mut state)
+            .is_ok());
+    }
+
+    // Serve the same header again, but with a validator set which does not match it
+    let mut tampered = light_blocks[2].clone();
+    let validators = (0..tampered.validators.validators().len())
+        .map(|i| Validator::new(&format!("tampered-{i}")).generate().unwrap())
+        .collect();
+    tampered.validators = tendermint::validator::Set::without_proposer(validators);
+
+    let mut state = trusting(light_blocks[0].clone());
+    state.light_store.insert(tampered, Status::Unverified);
+
+    let result = light_client.verify_to_target(3_u32.into(), &mut state);
+
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::ValidatorSetHashMismatch(_)) => {},
+        other => panic!("expected a validator set hash mismatch, got {other:?}"),
+    }
+
+    let stats = light_client.verdict_cache().unwrap().stats();
+    assert_eq!(stats, VerdictCacheStats { hits: 1, misses: 2 });
+}
+
+#[test]
+fn now_override_replaces_the_clock() {
+    let light_blocks = light_blocks(3);
+    let then = tendermint_testgen::helpers::get_time(light_blocks.len() as u64 + 1).unwrap();
+    let trusted_state = light_blocks[0].clone();
+
+    // The clock is well past the trusting period of the trusted state,
+    // but the verification is replayed as of `then`.
+    let now = (then + options().trusting_period * 2).unwrap();
+
+    let options = Options {
+        now_override: Some(then),
+        ..options()
+    };
+
+    let mut light_client = LightClient::new(
+        default_peer_id(),
+        options,
+        MockClock { now },
+        scheduler::basic_bisecting_schedule,
+        ProdVerifier::default(),
+        MockIo::new(light_blocks),
+    );
+
+    let mut state = trusting(trusted_state.clone());
+    let verified = light_client.verify_to_target(3_u32.into(), &mut state);
+    assert!(verified.is_ok());
+
+    // Without the override, the trusted state has expired
+    light_client.options.now_override = None;
+    let mut state = trusting(trusted_state);
+
+    let result = light_client.verify_to_target(3_u32.into(), &mut state);
+    assert!(matches!(
+        result.map_err(|e| e.into_detail()),
+        Err(ErrorDetail::TrustedStateOutsideTrustingPeriod(_))
+    ));
+}
+
+#[test]
+fn multiple_targets_share_intermediate_hops() {
+    let (light_client, mut state) = client(6, options());
+
+    let targets = [5_u32, 3, 5, 9].map(Into::into);
+    let results = light_client.verify_to_targets(targets, &mut state);
+
+    let heights: Vec<u64> = results.keys().map(|h| h.value()).collect();
+    assert_eq!(heights, vec![3, 5, 9]);
+    assert_eq!(
+        results[&3_u32.into()].as_ref().unwrap().height(),
+        3_u32.into()
+    );
+    assert_eq!(
+        results[&5_u32.into()].as_ref().unwrap().height(),
+        5_u32.into()
+    );
+
+    // The block at height 9 does not exist, which does not affect the other targets
+    assert!(results[&9_u32.into()].is_err());
+
+    // Height 5 was verified against height 3
+    assert_eq!(
+        state
+            .get_trace(5_u32.into())
+            .iter()
+            .map(|lb| lb.height().value())
+            .collect::<Vec<_>>(),
+        vec![3, 5]
+    );
+}
+
+#[test]
+fn expired_trusted_state_fails_before_any_fetch() {
+    let trusted_state = light_blocks(1).remove(0);
+    let now = (trusted_state.time() + options().trusting_period * 2).unwrap();
+
+    let io = |_: AtHeight| -> Result<LightBlock, IoError> {
+        panic!("no block should be fetched with an expired trusted state")
+    };
+
+    let mut light_client = LightClient::new(
+        default_peer_id(),
+        options(),
+        MockClock { now },
+        scheduler::basic_bisecting_schedule,
+        ProdVerifier::default(),
+        io,
+    );
+
+    let mut state = trusting(trusted_state);
+
+    let result = light_client.verify_to_highest(&mut state);
+    assert!(matches!(
+        result.map_err(|e| e.into_detail()),
+        Err(ErrorDetail::TrustedStateOutsideTrustingPeriod(_))
+    ));
+
+    let result = light_client.verify_to_target_hybrid(5_u32.into(), 2, &mut state);
+    let detail = result.unwrap_err().into_detail();
+    match &detail {
+        ErrorDetail::TrustedStateOutsideTrustingPeriod(e) => {
+            assert_eq!(e.options, options().snapshot())
+        },
+        other => panic!("expected an expired trusted state, got {other:?}"),
+    }
+
+    // The options in effect are part of the error message
+    assert!(detail
+        .to_string()
+        .ends_with("(trust threshold 1/3, trusting period 864000s, clock drift 10s)"));
+}
+
+#[test]
+fn bisection_stops_at_step_limit() {
+    let (mut light_client, mut state) = client(
+        3,
+        Options {
+            max_bisection_steps: 0,
+            ..options()
+        },
+    );
+
+    let result = light_client.verify_to_target(3_u32.into(), &mut state);
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::BisectionStepLimitExceeded(e)) => {
+            assert_eq!(e.target_height, 3_u32.into());
+            assert_eq!(e.steps, 0);
+        },
+        other => panic!("expected the step limit to be exceeded, got {other:?}"),
+    }
+
+    // Skipping straight to the target takes a single step
+    light_client.options.max_bisection_steps = 1;
+    let verified = light_client.verify_to_target(3_u32.into(), &mut state);
+    assert!(verified.is_ok());
+}
+
+/// A verifier which never finds enough overlap with the trusted validators.
+struct NeverEnoughTrust;
+
+impl Verifier for NeverEnoughTrust {
+    fn verify_update_header(
+        &self,
+        _untrusted: UntrustedBlockState<'_>,
+        _trusted: TrustedBlockState<'_>,
+        options: &Options,
+        _now: Time,
+    ) -> Verdict {
+        Verdict::NotEnoughTrust(VotingPowerTally {
+            total: 100,
+            tallied: 0,
+            trust_threshold: options.trust_threshold,
+        })
+    }
+
+    fn verify_misbehaviour_header(
+        &self,
+        untrusted: UntrustedBlockState<'_>,
+        trusted: TrustedBlockState<'_>,
+        options: &Options,
+        now: Time,
+    ) -> Verdict {
+        self.verify_update_header(untrusted, trusted, options, now)
+    }
+}
+
+#[test]
+fn stalled_verification_aborts_with_no_progress() {
+    // Long enough for bisection to keep halving towards the trusted block for more
+    // than the allowed number of stalled iterations
+    let light_blocks = light_blocks(64);
+    let now = tendermint_testgen::helpers::get_time(light_blocks.len() as u64 + 1).unwrap();
+    let mut state = trusting(light_blocks[0].clone());
+
+    let options = Options {
+        max_stalled_iterations: 5,
+        ..options()
+    };
+
+    let light_client = LightClient::new(
+        default_peer_id(),
+        options,
+        MockClock { now },
+        scheduler::basic_bisecting_schedule,
+        NeverEnoughTrust,
+        MockIo::new(light_blocks),
+    );
+
+    let result = light_client.verify_to_target(64_u32.into(), &mut state);
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::NoProgress(e)) => {
+            assert_eq!(e.stalled_at, 1_u32.into());
+            assert_eq!(e.iterations, 5);
+        },
+        other => panic!("expected verification to stall, got {other:?}"),
+    }
+}
+
+#[test]
+fn verification_resumes_from_cursor() {
+    let (mut light_client, mut state) = client(
+        3,
+        Options {
+            max_bisection_steps: 0,
+            ..options()
+        },
+    );
+    let trusted_state = state.light_store.highest(Status::Trusted).unwrap();
+
+    // Interrupt the verification right away, keeping the last checkpoint
+    let mut saved = None;
+    let result = light_client.verify_to_target_resumable(3_u32.into(), None, &mut state, |c| {
+        saved = Some(c.to_bytes().unwrap())
+    });
+    assert!(result.is_err());
+
+    let cursor = VerifyCursor::from_bytes(&saved.unwrap()).unwrap();
+    assert_eq!(cursor.trusted_height, trusted_state.height());
+    assert_eq!(cursor.current_height, 3_u32.into());
+
+    // A cursor which does not match the store is rejected
+    let stale = VerifyCursor {
+        trusted_height: 2_u32.into(),
+        ..cursor.clone()
+    };
+    let result =
+        light_client.verify_to_target_resumable(3_u32.into(), Some(&stale), &mut state, |_| {});
+    assert!(matches!(
+        result.map_err(|e| e.into_detail()),
+        Err(ErrorDetail::StaleCursor(_))
+    ));
+
+    light_client.options.max_bisection_steps = 1;
+    let verified =
+        light_client.verify_to_target_resumable(3_u32.into(), Some(&cursor), &mut state, |_| {});
+    assert_eq!(verified.unwrap().height(), 3_u32.into());
+
+    // Once the target is verified, it is returned whatever the cursor
+    let verified =
+        light_client.verify_to_target_resumable(3_u32.into(), Some(&stale), &mut state, |_| {});
+    assert_eq!(verified.unwrap().height(), 3_u32.into());
+}
+
+#[test]
+fn hybrid_verification_skips_then_verifies_adjacent_blocks() {
+    let (light_client, mut state) = client(6, options());
+
+    let verified = light_client
+        .verify_to_target_hybrid(6_u32.into(), 2, &mut state)
+        .unwrap();
+    assert_eq!(verified.height(), 6_u32.into());
+
+    // Height 4 was skipped to, and the tail above it verified block by block
+    let verified_heights: Vec<u64> = state
+        .light_store
+        .all(Status::Verified)
+        .map(|lb| lb.height().value())
+        .collect();
+    assert_eq!(verified_heights, vec![4, 5, 6]);
+}
+
+#[test]
+fn stats_sum_the_voting_power_of_verified_blocks() {
+    let (light_client, mut state) = client(5, options());
+
+    let (light_block, stats) = light_client
+        .verify_to_target_with_stats(3_u32.into(), &mut state)
+        .unwrap();
+    let power = light_block.validators.total_voting_power().value();
+    assert_eq!(stats.steps, 1);
+    assert_eq!(stats.total_voting_power_verified, power);
+
+    // The blocks verified by bisection each add up the power which signed them
+    let (_, stats) = light_client
+        .verify_to_target_with_stats(5_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(
+        stats.total_voting_power_verified,
+        stats.steps as u64 * power
+    );
+}
+
+#[test]
+fn dry_run_leaves_the_light_store_untouched() {
+    let (light_client, mut state) = client(5, options());
+    let light_client = light_client.with_verdict_cache(VerdictCache::new(16));
+
+    let (verdict, dry_stats) = light_client
+        .verify_to_target_dry_run(5_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(verdict, Verdict::Success);
+
+    assert_eq!(state.light_store.all(Status::Trusted).count(), 1);
+    assert_eq!(state.light_store.all(Status::Verified).count(), 0);
+    assert_eq!(state.light_store.all(Status::Unverified).count(), 0);
+    assert!(state.verification_trace.is_empty());
+    assert!(light_client.verdict_cache().unwrap().is_empty());
+
+    // Verifying for real takes the same path, and persists its outcome
+    let (_, stats) = light_client
+        .verify_to_target_with_stats(5_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(stats.steps, dry_stats.steps);
+    assert!(state
+        .light_store
+        .get(5_u32.into(), Status::Verified)
+        .is_some());
+}
+
+#[test]
+fn dry_run_reports_invalid_blocks_in_its_verdict() {
+    let mut light_blocks = light_blocks(3);
+    light_blocks[2].signed_header.header.app_hash =
+        tendermint::AppHash::try_from(vec![0xAB; 32]).unwrap();
+
+    let (light_client, mut state) = client_with(light_blocks, options());
+
+    let (verdict, stats) = light_client
+        .verify_to_target_dry_run(3_u32.into(), &mut state)
+        .unwrap();
+    assert!(matches!(verdict, Verdict::Invalid(_)));
+    assert_eq!(stats.steps, 1);
+
+    // The invalid block was not stored as failed either
+    assert_eq!(state.light_store.all(Status::Failed).count(), 0);
+}
+
+#[test]
+fn block_is_verified_from_separate_parts() {
+    let mut light_blocks = light_blocks(3);
+
+    // The primary does not serve the target block, which only comes in parts
+    let target = light_blocks.pop().unwrap();
+    let (light_client, mut state) = client_with(light_blocks, options());
+    // The parts are received from another peer than the primary
+    let provider: PeerId = "badefeeddeadbeefbadefeeddeadbeefbadefeed".parse().unwrap();
+
+    let mut mismatched = target.signed_header.clone();
+    mismatched.commit.height = 2_u32.into();
+    let result = light_client.verify_from_parts(
+        mismatched,
+        target.validators.clone(),
+        target.next_validators.clone(),
+        provider,
+        &mut state,
+    );
+    assert!(matches!(
+        result.map_err(|e| e.into_detail()),
+        Err(ErrorDetail::CommitHeightMismatch(_))
+    ));
+
+    let light_block = light_client
+        .verify_from_parts(
+            target.signed_header.clone(),
+            target.validators.clone(),
+            target.next_validators.clone(),
+            provider,
+            &mut state,
+        )
+        .unwrap();
+    assert_eq!(light_block.signed_header, target.signed_header);
+    assert_eq!(light_block.provider, provider);
+    assert_eq!(
+        state
+            .light_store
+            .get(target.height(), Status::Verified)
+            .map(|lb| lb.provider),
+        Some(provider)
+    );
+}
+
+#[test]
+fn prefetched_block_is_not_fetched_again() {
+    let mut light_blocks = light_blocks(3);
+
+    // The primary does not serve the target block, which is handed over by the caller
+    let target = light_blocks.pop().unwrap();
+    let (light_client, mut state) = client_with(light_blocks, options());
+
+    let result = light_client.verify_prefetched(2_u32.into(), target.clone(), &mut state);
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::PrefetchedHeightMismatch(e)) => {
+            assert_eq!(e.target_height, 2_u32.into());
+            assert_eq!(e.height, target.height());
+        },
+        other => panic!("expected a height mismatch, got {other:?}"),
+    }
+
+    let light_block = light_client
+        .verify_prefetched(target.height(), target.clone(), &mut state)
+        .unwrap();
+    assert_eq!(light_block, target);
+    assert!(state
+        .get_trace(target.height())
+        .iter()
+        .any(|lb| lb.height() == target.height()));
+}
+
+#[test]
+fn verification_can_be_pinned_to_a_trusted_block() {
+    let light_blocks = light_blocks(5);
+    let pinned = light_blocks[1].clone();
+    let (light_client, _) = client_with(light_blocks.clone(), options());
+
+    let light_block = light_client
+        .verify_to_target_from(pinned.clone(), 5_u32.into())
+        .unwrap();
+    assert_eq!(light_block.height(), 5_u32.into());
+
+    assert!(matches!(
+        light_client
+            .verify_to_target_from(pinned.clone(), 1_u32.into())
+            .map_err(|e| e.into_detail()),
+        Err(ErrorDetail::TargetLowerThanTrustedState(_))
+    ));
+
+    // The same block, once out of the trusting period
+    let (expired, _) = client_with(
+        light_blocks,
+        Options {
+            trusting_period: Duration::from_secs(1),
+            ..options()
+        },
+    );
+    assert!(matches!(
+        expired
+            .verify_to_target_from(pinned, 5_u32.into())
+            .map_err(|e| e.into_detail()),
+        Err(ErrorDetail::TrustedStateOutsideTrustingPeriod(_))
+    ));
+}
+
+#[test]
+fn bootstrap_installs_the_block_with_the_trusted_hash() {
+    let light_blocks = light_blocks(3);
+    let trusted_hash = light_blocks[0].signed_header.header.hash();
+    let other_hash = light_blocks[1].signed_header.header.hash();
+    let (light_client, _) = client_with(light_blocks, options());
+
+    let mut state = State::new(MemoryStore::new());
+
+    match light_client
+        .bootstrap(1_u32.into(), other_hash, &mut state)
+        .map_err(|e| e.into_detail())
+    {
+        Err(ErrorDetail::TrustedHashMismatch(e)) => {
+            assert_eq!(e.expected, other_hash);
+            assert_eq!(e.got, trusted_hash);
+        },
+        other => panic!("expected a trusted hash mismatch, got {other:?}"),
+    }
+    assert!(state.light_store.highest(Status::Trusted).is_none());
+
+    let trusted = light_client
+        .bootstrap(1_u32.into(), trusted_hash, &mut state)
+        .unwrap();
+    assert_eq!(
+        state.light_store.highest(Status::Trusted),
+        Some(trusted.clone())
+    );
+
+    // The bootstrapped block is the anchor of further verifications
+    let light_block = light_client
+        .verify_to_target(3_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(light_block.height(), 3_u32.into());
+}
+
+#[test]
+fn targets_below_the_min_verifiable_height_are_refused() {
+    let (light_client, mut state) = client(
+        5,
+        Options {
+            min_verifiable_height: Some(3_u32.into()),
+            ..options()
+        },
+    );
+
+    // The block could be verified from the trusted state, were it not below the floor
+    let result = light_client.verify_to_target(2_u32.into(), &mut state);
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::BelowMinHeight(e)) => {
+            assert_eq!(e.target, 2_u32.into());
+            assert_eq!(e.min, 3_u32.into());
+        },
+        other => panic!("expected a target below the minimum height, got {other:?}"),
+    }
+    assert!(state
+        .light_store
+        .get(1_u32.into(), Status::Unverified)
+        .is_none());
+
+    for height in [3_u32, 5] {
+        let light_block = light_client
+            .verify_to_target(height.into(), &mut state)
+            .unwrap();
+        assert_eq!(light_block.height(), height.into());
+    }
+}
+
+#[test]
+fn adjacent_blocks_must_chain_through_last_block_id() {
+    let light_blocks = light_blocks(3);
+
+    adjacent_blocks_chain(&light_blocks[0], &light_blocks[1]).unwrap();
+
+    // The header of the lower block is hashed, whatever the block id of its commit
+    let mut tampered = light_blocks[0].clone();
+    tampered.signed_header.commit.block_id.hash = light_blocks[2].signed_header.header.hash();
+    adjacent_blocks_chain(&tampered, &light_blocks[1]).unwrap();
+
+    // The block above the next one links to it, not to the first one
+    let result = adjacent_blocks_chain(&light_blocks[0], &light_blocks[2]);
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::InvalidAdjacentHeaders(e)) => {
+            assert_eq!(e.hash1, light_blocks[0].signed_header.header.hash());
+            assert_eq!(e.hash2, light_blocks[1].signed_header.header.hash());
+        },
+        other => panic!("expected a hash mismatch, got {other:?}"),
+    }
+
+    let mut unlinked = light_blocks[1].clone();
+    unlinked.signed_header.header.last_block_id = None;
+    let result = adjacent_blocks_chain(&light_blocks[0], &unlinked);
+    match result.map_err(|e| e.into_detail()) {
+        Err(ErrorDetail::MissingLastBlockId(e)) => assert_eq!(e.height, unlinked.height()),
+        other => panic!("expected a missing last block id, got {other:?}"),
+    }
+}
+
+#[test]
+fn failure_reasons_are_only_retained_when_enabled() {
+    let mut light_blocks = light_blocks(3);
+
+    // The last block no longer matches its commit
+    light_blocks[2].signed_header.header.app_hash =
+        tendermint::AppHash::try_from(vec![0xAB; 32]).unwrap();
+    let failed_block = light_blocks[2].clone();
+
+    for retain_failed in [false, true] {
+        let options = Options {
+            retain_failed,
+            ..options()
+        };
+        let (light_client, mut state) = client_with(light_blocks.clone(), options);
+
+        assert!(light_client
+            .verify_to_target(3_u32.into(), &mut state)
+            .is_err());
+
+        // The block is stored as failed either way
+        assert_eq!(
+            state.light_store.get(3_u32.into(), Status::Failed),
+            Some(failed_block.clone())
+        );
+
+        let failures: Vec<_> = state.light_store.failures().collect();
+        if retain_failed {
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].0, failed_block);
+            assert!(!failures[0].1.message.is_empty());
+        } else {
+            assert!(failures.is_empty());
+        }
+    }
+}
+
+#[test]
+fn backward_verification_hash_chains_down_from_the_lowest_trusted_block() {
+    let light_blocks = light_blocks(5);
+    let (light_client, _) = client_with(light_blocks.clone(), options());
+
+    let mut state = trusting(light_blocks[4].clone());
+    let light_block = light_client
+        .verify_backward(2_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(light_block, light_blocks[1]);
+    assert_eq!(
+        state.light_store.all(Status::Trusted).count(),
+        4,
+        "every block from the target up is trusted"
+    );
+
+    // Nothing to go backward from
+    assert!(matches!(
+        light_client
+            .verify_backward(5_u32.into(), &mut trusting(light_blocks[3].clone()))
+            .map_err(|e| e.into_detail()),
+        Err(ErrorDetail::NoInitialTrustedState(_))
+    ));
+
+    // A block which does not link to the one below it
+    let mut unlinked = light_blocks[2].clone();
+    unlinked.signed_header.header.last_block_id = None;
+    match light_client
+        .verify_backward(1_u32.into(), &mut trusting(unlinked))
+        .map_err(|e| e.into_detail())
+    {
+        Err(ErrorDetail::MissingLastBlockId(e)) => assert_eq!(e.height, 3_u32.into()),
+        other => panic!("expected a missing last block id, got {other:?}"),
+    }
+}
+
+#[test]
+fn revalidation_demotes_blocks_which_no_longer_verify() {
+    let (light_client, mut state) = client(3, options());
+
+    let mut light_block = light_client
+        .verify_to_target(3_u32.into(), &mut state)
+        .unwrap();
+
+    assert_eq!(
+        light_client
+            .revalidate_stored(1_u32.into(), &mut state)
+            .unwrap(),
+        Verdict::Success
+    );
+    assert_eq!(
+        light_client
+            .revalidate_stored(3_u32.into(), &mut state)
+            .unwrap(),
+        Verdict::Success
+    );
+    assert!(matches!(
+        light_client
+            .revalidate_stored(2_u32.into(), &mut state)
+            .map_err(|e| e.into_detail()),
+        Err(ErrorDetail::NotInStore(_))
+    ));
+
+    // Corrupt the stored block, so that it no longer matches its commit
+    light_block.signed_header.header.app_hash =
+        tendermint::AppHash::try_from(vec![0xAB; 32]).unwrap();
+    state
+        .light_store
+        .insert(light_block.clone(), Status::Verified);
+
+    let verdict = light_client
+        .revalidate_stored(3_u32.into(), &mut state)
+        .unwrap();
+    assert!(matches!(verdict, Verdict::Invalid(_)));
+    assert_eq!(
+        state.light_store.get(3_u32.into(), Status::Failed),
+        Some(light_block)
+    );
+}
+
+#[test]
+fn revalidation_checks_root_blocks_on_their_own() {
+    let (light_client, mut state) = client(3, options());
+
+    // The root block no longer comes with the validator set its header commits to
+    let mut root = state
+        .light_store
+        .get(1_u32.into(), Status::Trusted)
+        .unwrap();
+    root.validators =
+        tendermint::validator::Set::without_proposer(root.validators.validators()[..1].to_vec());
+    state.light_store.insert(root.clone(), Status::Trusted);
+
+    let verdict = light_client
+        .revalidate_stored(1_u32.into(), &mut state)
+        .unwrap();
+    assert!(matches!(verdict, Verdict::Invalid(_)));
+    assert_eq!(
+        state.light_store.get(1_u32.into(), Status::Failed),
+        Some(root)
+    );
+}
+
+#[test]
+fn verified_blocks_can_be_trusted_directly() {
+    let (light_client, mut state) = client(
+        3,
+        Options {
+            auto_trust_verified: true,
+            ..options()
+        },
+    );
+
+    light_client
+        .verify_to_target(3_u32.into(), &mut state)
+        .unwrap();
+
+    assert!(state
+        .light_store
+        .get(3_u32.into(), Status::Trusted)
+        .is_some());
+    assert_eq!(state.light_store.all(Status::Verified).count(), 0);
+}
+
+#[test]
+fn adjacent_only_verifies_blocks_right_above_the_trusted_state() {
+    let light_blocks = light_blocks(5);
+    let (light_client, _) = client_with(
+        light_blocks.clone(),
+        Options {
+            adjacent_only: true,
+            ..options()
+        },
+    );
+
+    // Each target is right above the previous one, so it is verified as an adjacent block
+    let mut state = trusting(light_blocks[0].clone());
+    for height in 2..=5_u32 {
+        let (_, stats) = light_client
+            .verify_to_target_with_stats(height.into(), &mut state)
+            .unwrap();
+        assert_eq!(stats.steps, 1);
+        assert_eq!(state.get_trace(height.into()).len(), 2);
+    }
+
+    // Unverified blocks do not link the target to the trusted state, so verification
+    // skips to the target block
+    let mut state = trusting(light_blocks[0].clone());
+    for light_block in &light_blocks[1..] {
+        state
+            .light_store
+            .insert(light_block.clone(), Status::Unverified);
+    }
+
+    let (_, stats) = light_client
+        .verify_to_target_with_stats(5_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(stats.steps, 1);
+    assert_eq!(stats.io_requests, 0);
+    for height in 2..=4_u32 {
+        assert!(state
+            .light_store
+            .get(height.into(), Status::Unverified)
+            .is_some());
+    }
+}
+
+#[test]
+fn adjacent_only_verifies_blocks_right_above_a_stored_chain() {
+    let light_blocks = light_blocks(5);
+
+    // Bisection is not allowed a single step, so only the fast path can succeed
+    let verify = |adjacent_only| {
+        let (light_client, _) = client_with(
+            light_blocks.clone(),
+            Options {
+                adjacent_only,
+                max_bisection_steps: 0,
+                ..options()
+            },
+        );
+
+        let mut state = trusting(light_blocks[0].clone());
+        for light_block in &light_blocks[1..4] {
+            state
+                .light_store
+                .insert(light_block.clone(), Status::Verified);
+        }
+
+        light_client
+            .verify_to_target_with_stats(5_u32.into(), &mut state)
+            .map(|(_, stats)| (stats, state.get_trace(5_u32.into()).len()))
+    };
+
+    let (stats, trace_len) = verify(true).unwrap();
+    assert_eq!(stats.steps, 1);
+    assert_eq!(trace_len, 2);
+
+    assert!(matches!(
+        verify(false).map_err(|e| e.into_detail()),
+        Err(ErrorDetail::BisectionStepLimitExceeded(_))
+    ));
+}
+
+#[test]
+fn validator_churn_is_stepped_through() {
+    let light_blocks = churned_light_blocks();
+
+    let steps = |step_through_validator_churn| {
+        let options = Options {
+            step_through_validator_churn,
+            ..options()
+        };
+        let (light_client, mut state) = client_with(light_blocks.clone(), options);
+
+        let (_, stats) = light_client
+            .verify_to_target_with_stats(9_u32.into(), &mut state)
+            .unwrap();
+        stats.steps
+    };
+
+    // Bisection tries heights 9, 5 and 3 before verifying 2 and then 9
+    assert_eq!(steps(false), 5);
+    // Height 2 is verified as soon as the target is found to be signed by new validators
+    assert_eq!(steps(true), 3);
+}
+
+#[test]
+fn verified_blocks_are_returned_in_increasing_order() {
+    let light_blocks = churned_light_blocks();
+
+    let (light_client, mut state) = client_with(light_blocks.clone(), options());
+
+    let verified = light_client
+        .verify_to_target_with_verified_blocks(9_u32.into(), &mut state)
+        .unwrap();
+    assert_eq!(
+        verified,
+        vec![light_blocks[1].clone(), light_blocks[8].clone()]
+    );
+
+    // Nothing is left to verify
+    let verified = light_client
+        .verify_to_target_with_verified_blocks(9_u32.into(), &mut state)
+        .unwrap();
+    assert!(verified.is_empty());
+}
+
+#[test]
+fn verified_blocks_are_streamed_as_they_are_verified() {
+    let light_blocks = churned_light_blocks();
+
+    let (light_client, _) = client_with(light_blocks.clone(), options());
+    let new_state = || trusting(light_blocks[0].clone());
+
+    // The consumer runs alongside the verification, through a channel of a single block
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    let consumer = std::thread::spawn(move || receiver.iter().collect::<Vec<LightBlock>>());
+
+    let mut state = new_state();
+    let light_block = light_client
+        .verify_to_target_streaming(9_u32.into(), &mut state, &sender)
+        .unwrap();
+    assert_eq!(light_block, light_blocks[8]);
+
+    drop(sender);
+    assert_eq!(
+        consumer.join().unwrap(),
+        vec![light_blocks[1].clone(), light_blocks[8].clone()]
+    );
+
+    // Verification stops once nobody listens anymore
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    drop(receiver);
+
+    let result = light_client.verify_to_target_streaming(9_u32.into(), &mut new_state(), &sender);
+    assert!(matches!(
+        result.map_err(|e| e.into_detail()),
+        Err(ErrorDetail::ChannelDisconnected(_))
+    ));
+}
+
+#[test]
+fn trust_is_refreshed_after_fraction_of_trusting_period() {
+    let light_block = light_blocks(1).remove(0);
+    let trusting_period = Duration::from_secs(300);
+    let should_refresh = |fraction, secs| {
+        let now = (light_block.time() + Duration::from_secs(secs)).unwrap();
+        should_refresh_trust(&light_block, trusting_period, fraction, now).unwrap()
+    };
+
+    assert!(!should_refresh(2.0 / 3.0, 0));
+    assert!(!should_refresh(2.0 / 3.0, 200));
+    assert!(should_refresh(2.0 / 3.0, 201));
+    assert!(!should_refresh(1.0, 300));
+    assert!(should_refresh(1.0, 301));
+}
+
+#[test]
+fn refresh_fraction_is_validated() {
+    let light_block = light_blocks(1).remove(0);
+
+    for fraction in [0.0, -0.5, 1.5, f64::NAN] {
+        let result = should_refresh_trust(
+            &light_block,
+            Duration::from_secs(300),
+            fraction,
+            light_block.time(),
+        );
+
+        match result.map_err(|e| e.into_detail()) {
+            Err(ErrorDetail::InvalidRefreshFraction(_)) => {},
+            other => panic!("expected an invalid refresh fraction, got {other:?}"),
+        }
+    }
+}
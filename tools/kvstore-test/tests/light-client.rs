@@ -57,11 +57,11 @@ fn make_primary() -> Instance {
     // by the nodes.
     let node_address: rpc::Url = "http://127.0.0.1:26657".parse().unwrap();
 
-    let options = LightClientOptions {
-        trust_threshold: TrustThreshold::new(1, 3).unwrap(),
-        trusting_period: Duration::from_secs(60 * 60), // 60 minutes
-        clock_drift: Duration::from_secs(5 * 60),      // 5 minutes
-    };
+    let options = LightClientOptions::new(
+        TrustThreshold::new(1, 3).unwrap(),
+        Duration::from_secs(60 * 60), // 60 minutes
+        Duration::from_secs(5 * 60),  // 5 minutes
+    );
 
     make_instance(primary, options, node_address)
 }